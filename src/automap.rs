@@ -0,0 +1,281 @@
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{
+        Canvas,
+        BlendMode,
+    },
+    mouse::MouseWheelDirection,
+    video::Window,
+};
+
+use controls::controls::*;
+
+use math_2d::Vec2;
+
+use level::Level;
+
+use map_render::MapView;
+
+use cvars::{
+    CVar,
+    CVarRegistry,
+};
+
+use timer;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PanKey {
+    Left = 0,
+    Right = 1,
+    Up = 2,
+    Down = 3,
+}
+
+/// input received by the control actions, and processed by Automap::update
+#[derive(Default)]
+struct AutomapInput {
+    toggle: bool,
+    /// held pan keys, indexed by PanKey
+    pan: [bool; 4],
+    /// zoom steps, positive to zoom in
+    zoom: i32,
+}
+
+/// Top-down map of the level, drawn from the primitives of the tiles already seen.
+/// It is shown as a minimap in a corner of the screen, or as a full-screen map with pan and zoom.
+pub struct Automap<'a> {
+    pub show_minimap: CVar<bool>,
+    /// the full-screen map is open
+    pub is_open: bool,
+    input: Rc<RefCell<AutomapInput>>,
+    /// offset of the full-screen map from the player, in tiles
+    pan: Vec2,
+    zoom: f32,
+    _handles: Vec<ControlHandle<'a>>,
+}
+
+impl<'a> Automap<'a> {
+    const TOGGLE_KEY: Scancode = Scancode::M;
+    /// minimap size, as a fraction of the window height
+    const MINIMAP_SCALE: f32 = 0.25;
+    /// number of tiles shown across the minimap
+    const MINIMAP_TILES: f32 = 12.0;
+    /// tiles per second
+    const PAN_SPEED: f32 = 8.0;
+    const ZOOM_STEP: f32 = 1.25;
+    const MIN_ZOOM: f32 = 0.25;
+    const MAX_ZOOM: f32 = 8.0;
+    /// length of the view cone, in tiles
+    const VIEW_CONE_LENGTH: f32 = 2.0;
+    const BACKGROUND_COLOR: Color = Color { r: 0x10, g: 0x10, b: 0x10, a: 0xc0 };
+    const FLOOR_COLOR: Color = Color { r: 0x30, g: 0x30, b: 0x38, a: 0xff };
+    const PORTAL_COLOR: Color = Color { r: 0x50, g: 0x30, b: 0x70, a: 0xff };
+    const WALL_COLOR: Color = Color { r: 0xd0, g: 0xd0, b: 0xd0, a: 0xff };
+    const MOVER_COLOR: Color = Color { r: 0xe0, g: 0xa0, b: 0x40, a: 0xff };
+    const ENEMY_COLOR: Color = Color { r: 0xe0, g: 0x40, b: 0x40, a: 0xff };
+    const PLAYER_COLOR: Color = Color { r: 0x40, g: 0xe0, b: 0x60, a: 0xff };
+    const BORDER_COLOR: Color = Color { r: 0x80, g: 0x80, b: 0x80, a: 0xff };
+
+    fn keydown(scancode: Scancode) -> Event {
+        Event::KeyDown {
+            timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+        }
+    }
+
+    fn keyup(scancode: Scancode) -> Event {
+        Event::KeyUp {
+            timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+        }
+    }
+
+    pub fn new(control_handler: &mut ControlHandler<'a>) -> Result<Self, Box<dyn Error>> {
+        let input = Rc::new(RefCell::new(AutomapInput::default()));
+        let mut handles = vec![];
+
+        let clone_input = Rc::downgrade(&input);
+        let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().toggle = true; });
+        handles.push(control_handler.add_control(ControlManagerType::Game, Self::keydown(Self::TOGGLE_KEY), action)?);
+
+        let pan_keys = [
+            (Scancode::Left, PanKey::Left),
+            (Scancode::Right, PanKey::Right),
+            (Scancode::Up, PanKey::Up),
+            (Scancode::Down, PanKey::Down),
+        ];
+        for &(scancode, key) in &pan_keys {
+            for (event, value) in [(Self::keydown(scancode), true), (Self::keyup(scancode), false)] {
+                let clone_input = Rc::downgrade(&input);
+                let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().pan[key as usize] = value; });
+                handles.push(control_handler.add_control(ControlManagerType::Game, event, action)?);
+            }
+        }
+
+        let zoom_keys = [
+            (Scancode::Equals, 1),
+            (Scancode::KpPlus, 1),
+            (Scancode::Minus, -1),
+            (Scancode::KpMinus, -1),
+        ];
+        for &(scancode, step) in &zoom_keys {
+            let clone_input = Rc::downgrade(&input);
+            let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().zoom += step; });
+            handles.push(control_handler.add_control(ControlManagerType::Game, Self::keydown(scancode), action)?);
+        }
+
+        let clone_input = Rc::downgrade(&input);
+        let event = Event::MouseWheel { timestamp: 0, window_id: 0, which: 0, x: 0, y: 0, direction: MouseWheelDirection::Normal };
+        let action = Box::new(move |wheel_event: Event| {
+            match wheel_event {
+                Event::MouseWheel{ y, .. } => {
+                    clone_input.upgrade().unwrap().borrow_mut().zoom += y.signum();
+                },
+                _ => {
+                    panic!("mouse wheel action: expected MouseWheel event, found {:#?}", wheel_event);
+                }
+            }
+        });
+        handles.push(control_handler.add_control(ControlManagerType::Game, event, action)?);
+
+        Ok(Self {
+            show_minimap: CVar::new(true),
+            is_open: false,
+            input,
+            pan: Vec2::default(),
+            zoom: 1.0,
+            _handles: handles,
+        })
+    }
+
+    pub fn register_cvars(&self, cvars: &mut CVarRegistry) -> Result<(), Box<dyn Error>> {
+        cvars.register("minimap", "shows the minimap in a corner of the screen", &self.show_minimap)
+    }
+
+    /// opens or closes the full-screen map, and pans and zooms it
+    pub fn update(&mut self) {
+        let mut input = self.input.borrow_mut();
+        if std::mem::replace(&mut input.toggle, false) {
+            self.is_open = !self.is_open;
+            self.pan = Vec2::default();
+        }
+        let zoom = std::mem::replace(&mut input.zoom, 0);
+        if !self.is_open {
+            return;
+        }
+        self.zoom = (self.zoom * Self::ZOOM_STEP.powi(zoom)).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+
+        let mut pan = Vec2::default();
+        if input.pan[PanKey::Left as usize] {
+            pan.x -= 1.0;
+        }
+        if input.pan[PanKey::Right as usize] {
+            pan.x += 1.0;
+        }
+        if input.pan[PanKey::Up as usize] {
+            pan.y -= 1.0;
+        }
+        if input.pan[PanKey::Down as usize] {
+            pan.y += 1.0;
+        }
+        pan.normalize();
+        // the pan speed on screen does not depend on the zoom
+        self.pan += pan * (Self::PAN_SPEED * timer::get_delta_time() / self.zoom);
+    }
+
+    /// draws the full-screen map if it is open, otherwise the minimap if it is enabled
+    pub fn draw(&self, canvas: &mut Canvas<Window>, level: &Level, pos: Vec2, dir: Vec2, fov: f32) -> Result<(), Box<dyn Error>> {
+        let (width, height) = canvas.window().drawable_size();
+        let view = if self.is_open {
+            let map_size = level.map.width.max(level.map.height) as f32;
+            MapView {
+                rect: Rect::new(0, 0, width, height),
+                focus: pos + self.pan,
+                scale: height as f32 / map_size * self.zoom,
+            }
+        } else if self.show_minimap.get() {
+            let size = (height as f32 * Self::MINIMAP_SCALE) as u32;
+            let margin = height as i32 / 40;
+            MapView {
+                // under the frame rate of the hud
+                rect: Rect::new(width as i32 - size as i32 - margin, margin * 3, size, size),
+                focus: pos,
+                scale: size as f32 / Self::MINIMAP_TILES,
+            }
+        } else {
+            return Ok(());
+        };
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.fill_rect(view.rect)?;
+        canvas.set_blend_mode(BlendMode::None);
+        canvas.set_clip_rect(view.rect);
+        let result = self.draw_map(canvas, level, &view, pos, dir, fov);
+        canvas.set_clip_rect(None);
+        result?;
+        if !self.is_open {
+            canvas.set_draw_color(Self::BORDER_COLOR);
+            canvas.draw_rect(view.rect)?;
+        }
+        Ok(())
+    }
+
+    fn draw_map(&self, canvas: &mut Canvas<Window>, level: &Level, view: &MapView, pos: Vec2, dir: Vec2, fov: f32) -> Result<(), Box<dyn Error>> {
+        // floors first, so that the walls are drawn over them
+        for (tile_y, line) in level.map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                if !level.seen[tile_y][tile_x] {
+                    continue;
+                }
+                let is_portal = level.mtl_set.material_properties[tile.mtl_idx].portal.is_some();
+                canvas.set_draw_color(if is_portal { Self::PORTAL_COLOR } else { Self::FLOOR_COLOR });
+                canvas.fill_rect(view.tile_rect(tile_x, tile_y))?;
+            }
+        }
+
+        for (tile_y, line) in level.map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                if !level.seen[tile_y][tile_x] {
+                    continue;
+                }
+                let origin = Vec2 { x: tile_x as f32, y: tile_y as f32 } + level.map.tile_offset(tile_x, tile_y);
+                canvas.set_draw_color(if tile.mover.is_some() { Self::MOVER_COLOR } else { Self::WALL_COLOR });
+                for primitive in level.geo_set.geometries[tile.geo_idx].iter().flatten() {
+                    view.draw_primitive(canvas, primitive, origin)?;
+                }
+            }
+        }
+        // the primitives of the map overlapping a seen tile
+        canvas.set_draw_color(Self::WALL_COLOR);
+        for (idx, primitive) in level.map.primitives.iter().enumerate() {
+            let seen = level.map.tiles.iter().zip(&level.seen)
+                .any(|(line, seen_line)| line.iter().zip(seen_line).any(|(tile, &seen)| seen && tile.primitives.contains(&idx)));
+            if seen {
+                view.draw_primitive(canvas, primitive, Vec2::default())?;
+            }
+        }
+
+        // enemies on the seen tiles
+        let radius = (view.scale / 8.0).max(2.0) as i32;
+        canvas.set_draw_color(Self::ENEMY_COLOR);
+        for enemy in &level.enemies {
+            if level.seen[enemy.position.y as usize][enemy.position.x as usize] {
+                let center = view.to_screen(enemy.position);
+                canvas.fill_rect(Rect::new(center.x() - radius, center.y() - radius, radius as u32 * 2, radius as u32 * 2))?;
+            }
+        }
+
+        // player and view cone
+        let camera_plane = dir.orthogonal(true) * (fov / 2.0).to_radians().tan();
+        let player = view.to_screen(pos);
+        canvas.set_draw_color(Self::PLAYER_COLOR);
+        for side in &[-1.0, 1.0] {
+            let mut ray_dir = dir + camera_plane * *side;
+            ray_dir.normalize();
+            canvas.draw_line(player, view.to_screen(pos + ray_dir * Self::VIEW_CONE_LENGTH))?;
+        }
+        let radius = (view.scale / 6.0).max(2.0) as i32;
+        canvas.fill_rect(Rect::new(player.x() - radius, player.y() - radius, radius as u32 * 2, radius as u32 * 2))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,399 @@
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{
+        Canvas,
+        TextureCreator,
+        BlendMode,
+    },
+    keyboard::TextInputUtil,
+    ttf::Sdl2TtfContext,
+    video::{
+        Window,
+        WindowContext,
+    },
+};
+
+use controls::controls::*;
+
+use level::Level;
+
+use save::{
+    QUICKSAVE_SLOT,
+    save_slots,
+};
+
+use text::ScaledFont;
+
+use cvars::CVarRegistry;
+
+use math_2d::Vec2;
+
+/// what the console asks the game to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleRequest {
+    LoadLevel(String),
+    SaveGame(String),
+    LoadGame(String),
+    Teleport(Vec2),
+    Screenshot,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ConsoleKey {
+    Backspace,
+    Return,
+    Up,
+    Down,
+    Tab,
+}
+
+/// input received by the control actions, and processed by Console::update
+#[derive(Default)]
+struct ConsoleInput {
+    toggle: bool,
+    text: String,
+    keys: Vec<ConsoleKey>,
+}
+
+/// name, arguments and help of the commands
+const COMMANDS: [(&str, &str, &str); 13] = [
+    ("help", "", "lists the commands"),
+    ("clear", "", "clears the console"),
+    ("cvars", "", "lists the config variables"),
+    ("set", "<cvar> <value>", "sets a config variable, same as <cvar> <value>"),
+    ("toggle", "<cvar>", "inverts a boolean config variable"),
+    ("map", "<name>", "loads a level"),
+    ("save", "[slot]", "saves the game, in the quicksave slot by default"),
+    ("load", "[slot]", "loads a saved game, from the quicksave slot by default"),
+    ("noclip", "", "lets the player walk through walls"),
+    ("god", "", "makes the player invulnerable"),
+    ("fov", "<degrees>", "sets the field of view"),
+    ("tp", "<x> <y>", "teleports the player"),
+    ("screenshot", "", "saves a screenshot in the captures directory"),
+];
+
+/// Drop-down console, toggled with the grave key.
+/// It has its own control manager, and reads the keyboard through SDL text input.
+pub struct Console<'a> {
+    pub is_open: bool,
+    input: Rc<RefCell<ConsoleInput>>,
+    /// line being typed
+    line: String,
+    log: Vec<String>,
+    history: Vec<String>,
+    /// position in the history while browsing it with the arrow keys
+    history_idx: Option<usize>,
+    /// control manager active before the console opened
+    previous_manager: ControlManagerType,
+    _handles: Vec<ControlHandle<'a>>,
+}
+
+impl<'a> Console<'a> {
+    const TOGGLE_KEY: Scancode = Scancode::Grave;
+    const MAX_LOG: usize = 256;
+    const MAX_HISTORY: usize = 64;
+    /// height of the console, as a fraction of the window height
+    const HEIGHT_SCALE: f32 = 0.5;
+    const FONT_SCALE: f32 = 1.0 / 40.0;
+    const BACKGROUND_COLOR: Color = Color { r: 0x10, g: 0x10, b: 0x18, a: 0xd0 };
+    const TEXT_COLOR: Color = Color { r: 0xd0, g: 0xd0, b: 0xd0, a: 0xff };
+    const INPUT_COLOR: Color = Color { r: 0xff, g: 0xff, b: 0x80, a: 0xff };
+
+    fn keydown(scancode: Scancode) -> Event {
+        Event::KeyDown {
+            timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+        }
+    }
+
+    pub fn new(control_handler: &mut ControlHandler<'a>) -> Result<Self, Box<dyn Error>> {
+        let input = Rc::new(RefCell::new(ConsoleInput::default()));
+        let mut handles = vec![];
+
+        for &manager in &[ControlManagerType::Game, ControlManagerType::Console] {
+            let clone_input = Rc::downgrade(&input);
+            let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().toggle = true; });
+            handles.push(control_handler.add_control(manager, Self::keydown(Self::TOGGLE_KEY), action)?);
+        }
+        let clone_input = Rc::downgrade(&input);
+        let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().toggle = true; });
+        handles.push(control_handler.add_control(ControlManagerType::Console, Self::keydown(Scancode::Escape), action)?);
+
+        let keys = [
+            (Scancode::Backspace, ConsoleKey::Backspace),
+            (Scancode::Return, ConsoleKey::Return),
+            (Scancode::KpEnter, ConsoleKey::Return),
+            (Scancode::Up, ConsoleKey::Up),
+            (Scancode::Down, ConsoleKey::Down),
+            (Scancode::Tab, ConsoleKey::Tab),
+        ];
+        for &(scancode, key) in &keys {
+            let clone_input = Rc::downgrade(&input);
+            let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().keys.push(key); });
+            handles.push(control_handler.add_control(ControlManagerType::Console, Self::keydown(scancode), action)?);
+        }
+
+        let clone_input = Rc::downgrade(&input);
+        let event = Event::TextInput { timestamp: 0, window_id: 0, text: String::new() };
+        let action = Box::new(move |text_event: Event| {
+            match text_event {
+                Event::TextInput{ text, .. } => {
+                    clone_input.upgrade().unwrap().borrow_mut().text.push_str(&text);
+                },
+                _ => {
+                    panic!("text input action: expected TextInput event, found {:#?}", text_event);
+                }
+            }
+        });
+        handles.push(control_handler.add_control(ControlManagerType::Console, event, action)?);
+
+        Ok(Self {
+            is_open: false,
+            input,
+            line: String::new(),
+            log: vec![],
+            history: vec![],
+            history_idx: None,
+            previous_manager: ControlManagerType::Game,
+            _handles: handles,
+        })
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.log.len() == Self::MAX_LOG {
+                self.log.remove(0);
+            }
+            self.log.push(line.to_string());
+        }
+    }
+
+    fn toggle(&mut self, control_handler: &mut ControlHandler<'a>, text_input: &TextInputUtil) {
+        self.is_open = !self.is_open;
+        if self.is_open {
+            self.previous_manager = control_handler.active_manager();
+            control_handler.set_active_manager(ControlManagerType::Console);
+            text_input.start();
+        } else {
+            control_handler.set_active_manager(self.previous_manager);
+            text_input.stop();
+        }
+    }
+
+    /// closes the console when the game state changes, the controls of the new state are set by the caller
+    pub fn close(&mut self, text_input: &TextInputUtil) {
+        if self.is_open {
+            self.is_open = false;
+            text_input.stop();
+        }
+    }
+
+    /// processes the input of the console, and returns the requests of the executed commands
+    pub fn update(&mut self, control_handler: &mut ControlHandler<'a>, text_input: &TextInputUtil, cvars: &CVarRegistry) -> Vec<ConsoleRequest> {
+        let input = std::mem::take(&mut *self.input.borrow_mut());
+        let mut requests = vec![];
+        if input.toggle {
+            self.toggle(control_handler, text_input);
+        }
+        if !self.is_open {
+            return requests;
+        }
+        // the toggle key also produces text
+        self.line.extend(input.text.chars().filter(|&c| c != '`' && c != '~'));
+        for key in input.keys {
+            match key {
+                ConsoleKey::Backspace => { self.line.pop(); },
+                ConsoleKey::Return => {
+                    let line = std::mem::take(&mut self.line);
+                    self.history_idx = None;
+                    if let Some(request) = self.execute(&line, cvars) {
+                        requests.push(request);
+                    }
+                },
+                ConsoleKey::Up => self.browse_history(true),
+                ConsoleKey::Down => self.browse_history(false),
+                ConsoleKey::Tab => self.complete(cvars),
+            }
+        }
+        requests
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let idx = match (self.history_idx, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(0), true) => Some(0),
+            (Some(idx), true) => Some(idx - 1),
+            (Some(idx), false) if idx + 1 < self.history.len() => Some(idx + 1),
+            (Some(_), false) => None,
+        };
+        self.history_idx = idx;
+        self.line = match idx {
+            Some(idx) => self.history[idx].clone(),
+            None => String::new(),
+        };
+    }
+
+    /// completes the command, config variable or level name being typed
+    fn complete(&mut self, cvars: &CVarRegistry) {
+        let words: Vec<&str> = self.line.split_whitespace().collect();
+        let typing_new_word = self.line.ends_with(' ');
+        let (prefix, candidates): (String, Vec<String>) = match (words.len(), typing_new_word) {
+            (0, _) | (1, false) => {
+                let candidates = COMMANDS.iter().map(|command| command.0.to_string())
+                    .chain(cvars.names().map(|name| name.to_string()))
+                    .collect();
+                (String::new(), candidates)
+            },
+            (1, true) | (2, false) if words[0] == "map" => {
+                (String::from("map "), Level::names())
+            },
+            (1, true) | (2, false) if words[0] == "save" || words[0] == "load" => {
+                (format!("{} ", words[0]), save_slots())
+            },
+            (1, true) | (2, false) if words[0] == "set" || words[0] == "toggle" => {
+                (format!("{} ", words[0]), cvars.names().map(|name| name.to_string()).collect())
+            },
+            _ => return,
+        };
+        let partial = if typing_new_word { "" } else { words.last().cloned().unwrap_or("") };
+        let matches: Vec<&String> = candidates.iter().filter(|candidate| candidate.starts_with(partial)).collect();
+        match matches.len() {
+            0 => {},
+            1 => self.line = format!("{}{} ", prefix, matches[0]),
+            _ => {
+                // completes up to the longest common prefix, and shows the matches
+                let mut common = matches[0].clone();
+                for candidate in &matches[1..] {
+                    while !candidate.starts_with(common.as_str()) {
+                        common.pop();
+                    }
+                }
+                self.line = format!("{}{}", prefix, common);
+                let list = matches.iter().map(|candidate| candidate.as_str()).collect::<Vec<&str>>().join("  ");
+                self.print(&list);
+            },
+        }
+    }
+
+    fn parse_args<T: std::str::FromStr>(args: &[&str], n: usize) -> Result<Vec<T>, String> {
+        if args.len() != n {
+            return Err(format!("expected {} arguments, found {}", n, args.len()));
+        }
+        args.iter().map(|arg| arg.parse().map_err(|_| format!("invalid argument '{}'", arg))).collect()
+    }
+
+    fn toggle_cvar(&mut self, cvars: &CVarRegistry, name: &str) {
+        match cvars.toggle(name) {
+            Ok(value) => self.print(&format!("{} {}", name, value)),
+            Err(err) => self.print(&err),
+        }
+    }
+
+    fn set_cvar(&mut self, cvars: &CVarRegistry, name: &str, value: &str) {
+        if let Err(err) = cvars.set(name, value) {
+            self.print(&err);
+        }
+    }
+
+    /// executes a command line, and returns the request the game has to fulfil
+    pub fn execute(&mut self, line: &str, cvars: &CVarRegistry) -> Option<ConsoleRequest> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            return None;
+        }
+        self.print(&format!("> {}", line));
+        if self.history.last().map(|last| last.as_str()) != Some(line) {
+            if self.history.len() == Self::MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.to_string());
+        }
+
+        let (command, args) = (words[0], &words[1..]);
+        match command {
+            "help" => {
+                for &(name, args, help) in COMMANDS.iter() {
+                    self.print(&format!("{} {} : {}", name, args, help));
+                }
+            },
+            "clear" => self.log.clear(),
+            "cvars" => {
+                let lines: Vec<String> = cvars.names().map(|name| {
+                    format!("{} = {} : {}", name, cvars.get(name).unwrap(), cvars.description(name).unwrap())
+                }).collect();
+                for line in lines {
+                    self.print(&line);
+                }
+            },
+            "set" if args.len() == 2 => self.set_cvar(cvars, args[0], args[1]),
+            "toggle" if args.len() == 1 => self.toggle_cvar(cvars, args[0]),
+            "map" if args.len() == 1 => return Some(ConsoleRequest::LoadLevel(args[0].to_string())),
+            "save" if args.len() <= 1 => {
+                let slot = args.first().cloned().unwrap_or(QUICKSAVE_SLOT);
+                return Some(ConsoleRequest::SaveGame(slot.to_string()));
+            },
+            "load" if args.len() <= 1 => {
+                let slot = args.first().cloned().unwrap_or(QUICKSAVE_SLOT);
+                return Some(ConsoleRequest::LoadGame(slot.to_string()));
+            },
+            "noclip" if args.is_empty() => self.toggle_cvar(cvars, "noclip"),
+            "god" if args.is_empty() => self.toggle_cvar(cvars, "god"),
+            "fov" if args.len() == 1 => self.set_cvar(cvars, "fov", args[0]),
+            "tp" => {
+                match Self::parse_args::<f32>(args, 2) {
+                    Ok(coords) => return Some(ConsoleRequest::Teleport(Vec2 { x: coords[0], y: coords[1] })),
+                    Err(err) => self.print(&err),
+                }
+            },
+            "screenshot" if args.is_empty() => return Some(ConsoleRequest::Screenshot),
+            name if cvars.contains(name) && args.is_empty() => {
+                let value = cvars.get(name).unwrap();
+                self.print(&format!("{} = {}", name, value));
+            },
+            name if cvars.contains(name) && args.len() == 1 => self.set_cvar(cvars, name, args[0]),
+            name => {
+                match COMMANDS.iter().find(|command| command.0 == name) {
+                    Some(&(name, args, _)) => self.print(&format!("usage: {} {}", name, args)),
+                    None => self.print(&format!("unknown command '{}'", name)),
+                }
+            },
+        }
+        None
+    }
+
+    /// draws the console over the top of the screen, if it is open
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &mut ScaledFont) -> Result<(), Box<dyn Error>> {
+        if !self.is_open {
+            return Ok(());
+        }
+        font.fit(canvas)?;
+        let (width, height) = canvas.window().drawable_size();
+        let console_height = (height as f32 * Self::HEIGHT_SCALE) as i32;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.fill_rect(Rect::new(0, 0, width, console_height as u32))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let line_height = font.line_height().max(1);
+        let margin = line_height / 2;
+        let mut y = console_height - margin - line_height;
+        font.draw(canvas, texture_creator, &format!("] {}_", self.line), Self::INPUT_COLOR, margin, y)?;
+        for line in self.log.iter().rev() {
+            y -= line_height;
+            if y < 0 {
+                break;
+            }
+            font.draw(canvas, texture_creator, line, Self::TEXT_COLOR, margin, y)?;
+        }
+        Ok(())
+    }
+
+    /// the font used by the console
+    pub fn create_font<'ttf>(ttf_context: &'ttf Sdl2TtfContext, font_name: &str, window_height: u32) -> Result<ScaledFont<'ttf>, Box<dyn Error>> {
+        ScaledFont::new(ttf_context, font_name, Self::FONT_SCALE, window_height)
+    }
+}
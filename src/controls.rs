@@ -63,6 +63,7 @@ pub mod controls {
             mousedown_controls: Vec<Option<Box<dyn Control + 'a>>>,
             mouseup_controls: Vec<Option<Box<dyn Control + 'a>>>,
             mousewheel_controls: Vec<Option<Box<dyn Control + 'a>>>,
+            textinput_controls: Vec<Option<Box<dyn Control + 'a>>>,
         }
         
         impl<'a> ControlManager<'a> {
@@ -76,6 +77,7 @@ pub mod controls {
                     mousedown_controls: vec![],
                     mouseup_controls: vec![],
                     mousewheel_controls: vec![],
+                    textinput_controls: vec![],
                 }))
             }
         
@@ -124,6 +126,10 @@ pub mod controls {
                         let trimmed = Event::MouseWheel { timestamp: 0, window_id: 0, which: 0, x: 0, y: 0, direction };
                         Some((&mut self.mousewheel_controls, trimmed))
                     },
+                    Event::TextInput { .. } => {
+                        let trimmed = Event::TextInput { timestamp: 0, window_id: 0, text: String::new() };
+                        Some((&mut self.textinput_controls, trimmed))
+                    },
                     _ => { None }
                 }
             }
@@ -220,26 +226,36 @@ pub mod controls {
     }
 
     #[repr(usize)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
     pub enum ControlManagerType {
         Game = 0,
         Menu = 1,
+        Console = 2,
         // etc.
     }
 
     pub struct ControlHandler<'a> {
-        control_managers: [Rc<RefCell<ControlManager<'a>>>; 2],
+        control_managers: [Rc<RefCell<ControlManager<'a>>>; 3],
         active_manager: ControlManagerType
     }
 
     impl<'a> ControlHandler<'a> {
         pub fn new() -> Self {
             Self {
-                control_managers: [ControlManager::new(), ControlManager::new()],
+                control_managers: [ControlManager::new(), ControlManager::new(), ControlManager::new()],
                 active_manager: ControlManagerType::Game
             }
         }
 
+        pub fn active_manager(&self) -> ControlManagerType {
+            self.active_manager
+        }
+
+        /// only the controls of the active manager are called
+        pub fn set_active_manager(&mut self, ctrl_manager_type: ControlManagerType) {
+            self.active_manager = ctrl_manager_type;
+        }
+
         pub fn call_loop(&mut self, event_pump: &mut EventPump) {
             let manager = &mut self.control_managers[self.active_manager as usize];
             manager.borrow_mut().call_loop(event_pump);
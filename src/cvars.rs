@@ -0,0 +1,156 @@
+use std::rc::Rc;
+use std::cell::Cell;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use parser::Error;
+
+/// types a config variable can hold
+pub trait CVarType: Copy + PartialOrd + Display + FromStr + 'static {
+    /// the opposite value, for the types that can be toggled
+    fn toggled(self) -> Option<Self> {
+        None
+    }
+}
+
+impl CVarType for bool {
+    fn toggled(self) -> Option<Self> {
+        Some(!self)
+    }
+}
+impl CVarType for i32 {}
+impl CVarType for u32 {}
+impl CVarType for f32 {}
+
+/// Typed config variable.
+/// The module owning the variable keeps a clone of it, the registry lets the console read and modify it.
+#[derive(Debug)]
+pub struct CVar<T: CVarType> {
+    value: Rc<Cell<T>>,
+    min: Option<T>,
+    max: Option<T>,
+}
+
+impl<T: CVarType> Clone for CVar<T> {
+    fn clone(&self) -> Self {
+        Self { value: Rc::clone(&self.value), min: self.min, max: self.max }
+    }
+}
+
+impl<T: CVarType> CVar<T> {
+    pub fn new(value: T) -> Self {
+        Self { value: Rc::new(Cell::new(value)), min: None, max: None }
+    }
+
+    /// values set through the registry must be within min and max
+    pub fn with_bounds(value: T, min: T, max: T) -> Self {
+        Self { value: Rc::new(Cell::new(value)), min: Some(min), max: Some(max) }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.get()
+    }
+
+    pub fn set(&self, value: T) {
+        self.value.set(value);
+    }
+}
+
+trait RegisteredCVar {
+    fn value_string(&self) -> String;
+    fn parse_and_set(&self, value: &str) -> Result<(), String>;
+    fn toggle(&self) -> Result<String, String>;
+}
+
+impl<T: CVarType> RegisteredCVar for CVar<T> {
+    fn value_string(&self) -> String {
+        self.get().to_string()
+    }
+
+    fn parse_and_set(&self, value: &str) -> Result<(), String> {
+        let value: T = match value.parse() {
+            Ok(value) => value,
+            Err(_) => return Err(format!("invalid value '{}'", value)),
+        };
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if value < min || value > max {
+                return Err(format!("{} is out of bounds [{}, {}]", value, min, max));
+            }
+        }
+        self.set(value);
+        Ok(())
+    }
+
+    fn toggle(&self) -> Result<String, String> {
+        match self.get().toggled() {
+            Some(value) => {
+                self.set(value);
+                Ok(value.to_string())
+            },
+            None => Err("only boolean variables can be toggled".to_string()),
+        }
+    }
+}
+
+struct CVarEntry {
+    name: String,
+    description: String,
+    cvar: Box<dyn RegisteredCVar>,
+}
+
+/// Registry of the config variables of the game, looked up by name
+#[derive(Default)]
+pub struct CVarRegistry {
+    entries: Vec<CVarEntry>,
+}
+
+impl CVarRegistry {
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn register<T: CVarType>(&mut self, name: &str, description: &str, cvar: &CVar<T>) -> Result<(), Box<dyn Error>> {
+        if self.entries.iter().any(|entry| entry.name == name) {
+            return Err(format!("config variable '{}' is already registered", name).into());
+        }
+        self.entries.push(CVarEntry {
+            name: name.to_string(),
+            description: description.to_string(),
+            cvar: Box::new(cvar.clone()),
+        });
+        Ok(())
+    }
+
+    fn entry(&self, name: &str) -> Result<&CVarEntry, String> {
+        match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => Ok(entry),
+            None => Err(format!("unknown config variable '{}'", name)),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<String, String> {
+        Ok(self.entry(name)?.cvar.value_string())
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        self.entry(name)?.cvar.parse_and_set(value)
+    }
+
+    /// inverts a boolean variable and returns its new value
+    pub fn toggle(&self, name: &str) -> Result<String, String> {
+        self.entry(name)?.cvar.toggle()
+    }
+
+    pub fn description(&self, name: &str) -> Result<&str, String> {
+        Ok(&self.entry(name)?.description)
+    }
+
+    /// names of the variables, in registration order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+}
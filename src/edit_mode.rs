@@ -0,0 +1,229 @@
+use controls::controls::*;
+
+use math_2d::{
+    Vec2,
+    Ray,
+};
+
+use level::{
+    Level,
+    MapEditor,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditAction {
+    PreviousGeometry,
+    NextGeometry,
+    PreviousMaterial,
+    NextMaterial,
+    Portal,
+    Spawn,
+    Undo,
+    Redo,
+    Save,
+}
+
+/// input received by the control actions, and processed by EditMode::update
+#[derive(Default)]
+struct EditModeInput {
+    toggle: bool,
+    actions: Vec<EditAction>,
+}
+
+/// Edit mode of the game, in which the player changes the geometry and material of the tile aimed at, and places spawns and portals.
+/// The level shows the map of its files with the changes, its doors and push walls are reset by each change.
+pub struct EditMode<'a> {
+    map_editor: Option<MapEditor>,
+    /// tile aimed at by the player
+    target: Option<(usize, usize)>,
+    input: Rc<RefCell<EditModeInput>>,
+    _handles: Vec<ControlHandle<'a>>,
+}
+
+impl<'a> EditMode<'a> {
+    const TOGGLE_KEY: Scancode = Scancode::F2;
+    const ACTION_KEYS: [(Scancode, EditAction); 9] = [
+        (Scancode::LeftBracket, EditAction::PreviousGeometry),
+        (Scancode::RightBracket, EditAction::NextGeometry),
+        (Scancode::Comma, EditAction::PreviousMaterial),
+        (Scancode::Period, EditAction::NextMaterial),
+        (Scancode::P, EditAction::Portal),
+        (Scancode::K, EditAction::Spawn),
+        (Scancode::Z, EditAction::Undo),
+        (Scancode::Y, EditAction::Redo),
+        (Scancode::F6, EditAction::Save),
+    ];
+    /// distance from the player to the tile aimed at when no wall is closer, in tiles
+    const REACH: f32 = 1.5;
+
+    fn keydown(scancode: Scancode) -> Event {
+        Event::KeyDown {
+            timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+        }
+    }
+
+    pub fn new(control_handler: &mut ControlHandler<'a>) -> Result<Self, Box<dyn Error>> {
+        let input = Rc::new(RefCell::new(EditModeInput::default()));
+        let mut handles = vec![];
+
+        let clone_input = Rc::downgrade(&input);
+        let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().toggle = true; });
+        handles.push(control_handler.add_control(ControlManagerType::Game, Self::keydown(Self::TOGGLE_KEY), action)?);
+
+        for &(scancode, edit_action) in &Self::ACTION_KEYS {
+            let clone_input = Rc::downgrade(&input);
+            let action = Box::new(move |_: Event| { clone_input.upgrade().unwrap().borrow_mut().actions.push(edit_action); });
+            handles.push(control_handler.add_control(ControlManagerType::Game, Self::keydown(scancode), action)?);
+        }
+
+        Ok(Self {
+            map_editor: None,
+            target: None,
+            input,
+            _handles: handles,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.map_editor.is_some()
+    }
+
+    /// shows the edited map in the level
+    fn show_map(map_editor: &MapEditor, level: &mut Level) -> Result<(), Box<dyn Error>> {
+        let (map, spawns) = map_editor.level_map.rebuild_map()?;
+        level.map = map;
+        level.spawns = spawns;
+        level.bake_lights();
+        Ok(())
+    }
+
+    /// edits the map of the files of the level, and shows it in the level
+    fn open(level: &mut Level) -> Result<MapEditor, Box<dyn Error>> {
+        let map_editor = MapEditor::load(&level.name)?;
+        Self::show_map(&map_editor, level)?;
+        Ok(map_editor)
+    }
+
+    fn apply(map_editor: &mut MapEditor, action: EditAction, target: Option<(usize, usize)>) -> Result<Option<String>, Box<dyn Error>> {
+        let (tile_x, tile_y) = match (action, target) {
+            (EditAction::Undo, _) => {
+                return if map_editor.undo() { Ok(None) } else { Err("nothing to undo".into()) };
+            },
+            (EditAction::Redo, _) => {
+                return if map_editor.redo() { Ok(None) } else { Err("nothing to redo".into()) };
+            },
+            (EditAction::Save, _) => {
+                map_editor.save()?;
+                return Ok(Some(format!("Level '{}' saved", map_editor.level_map.name)));
+            },
+            (_, Some(target)) => target,
+            (_, None) => return Err("no tile aimed at".into()),
+        };
+        match action {
+            EditAction::PreviousGeometry => map_editor.cycle_geometry(tile_x, tile_y, -1)?,
+            EditAction::NextGeometry => map_editor.cycle_geometry(tile_x, tile_y, 1)?,
+            EditAction::PreviousMaterial => map_editor.cycle_material(tile_x, tile_y, -1)?,
+            EditAction::NextMaterial => map_editor.cycle_material(tile_x, tile_y, 1)?,
+            EditAction::Portal => map_editor.cycle_portal(tile_x, tile_y)?,
+            EditAction::Spawn => map_editor.cycle_spawn(tile_x, tile_y)?,
+            EditAction::Undo | EditAction::Redo | EditAction::Save => {},
+        }
+        Ok(None)
+    }
+
+    /// Opens or closes the edit mode, and applies the edit actions to the tile aimed at from `pos` along `dir`.
+    /// Returns the messages for the hud.
+    pub fn update(&mut self, level: &mut Level, pos: Vec2, dir: Vec2) -> Vec<String> {
+        let (toggle, actions) = {
+            let mut input = self.input.borrow_mut();
+            (std::mem::replace(&mut input.toggle, false), std::mem::take(&mut input.actions))
+        };
+        let mut messages = vec![];
+        // another level was loaded
+        if self.map_editor.as_ref().is_some_and(|map_editor| map_editor.level_map.name != level.name) {
+            self.map_editor = None;
+        }
+        if toggle {
+            match self.map_editor.take() {
+                Some(map_editor) if map_editor.modified => {
+                    messages.push("Edit mode closed, the changes are not saved".to_string());
+                    // the level shows the map of its files again
+                    if let Err(err) = MapEditor::load(&level.name).and_then(|map_editor| Self::show_map(&map_editor, level)) {
+                        messages.push(format!("Cannot show the saved map: {}", err));
+                    }
+                },
+                Some(_) => messages.push("Edit mode closed".to_string()),
+                None => {
+                    match Self::open(level) {
+                        Ok(map_editor) => {
+                            self.map_editor = Some(map_editor);
+                            messages.push("Edit mode".to_string());
+                        },
+                        Err(err) => messages.push(format!("Cannot edit the level: {}", err)),
+                    }
+                },
+            }
+        }
+
+        let map_editor = match self.map_editor {
+            Some(ref mut map_editor) => map_editor,
+            None => {
+                self.target = None;
+                return messages;
+            },
+        };
+        self.target = level.aimed_tile(Ray::new(pos, dir), Self::REACH);
+        let mut changed = false;
+        for action in actions {
+            match Self::apply(map_editor, action, self.target) {
+                Ok(message) => {
+                    if action == EditAction::Save {
+                        // the level already shows the saved map
+                        level.acknowledge_file_changes();
+                    } else {
+                        changed = true;
+                    }
+                    messages.extend(message);
+                },
+                Err(err) => messages.push(format!("Cannot edit: {}", err)),
+            }
+        }
+        if changed {
+            if let Err(err) = Self::show_map(map_editor, level) {
+                messages.push(format!("Cannot show the changes: {}", err));
+            }
+        }
+        messages
+    }
+
+    /// Edits the map of the files of a reloaded level, if the edit mode is open.
+    /// Returns the messages for the hud.
+    pub fn reload(&mut self, level: &mut Level) -> Vec<String> {
+        let modified = match self.map_editor {
+            Some(ref map_editor) => map_editor.modified,
+            None => return vec![],
+        };
+        let mut messages = vec![];
+        if modified {
+            messages.push("The changes of the edit mode are lost".to_string());
+        }
+        match Self::open(level) {
+            Ok(map_editor) => self.map_editor = Some(map_editor),
+            Err(err) => {
+                self.map_editor = None;
+                messages.push(format!("Cannot edit the level: {}", err));
+            },
+        }
+        messages
+    }
+
+    /// the tile aimed at, shown by the hud in edit mode
+    pub fn status(&self) -> Option<String> {
+        let map_editor = self.map_editor.as_ref()?;
+        let tile = match self.target {
+            Some((tile_x, tile_y)) => map_editor.describe_tile(tile_x, tile_y),
+            None => "no tile aimed at".to_string(),
+        };
+        Some(format!("Edit{}  {}", if map_editor.modified { "*" } else { "" }, tile))
+    }
+}
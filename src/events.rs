@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+
+pub type Listener<'a, E> = Box<dyn FnMut(&E) + 'a>;
+
+/// Queue of typed events.
+/// Events are published during the frame, then popped and notified to every listener in publication order.
+pub struct EventBus<'a, E> {
+    queue: VecDeque<E>,
+    listeners: Vec<Option<Listener<'a, E>>>,
+}
+
+impl<'a, E> Default for EventBus<'a, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, E> EventBus<'a, E> {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            listeners: vec![],
+        }
+    }
+
+    pub fn publish(&mut self, event: E) {
+        self.queue.push_back(event);
+    }
+
+    /// returns the id used to unsubscribe the listener
+    pub fn subscribe(&mut self, listener: Listener<'a, E>) -> usize {
+        if let Some(idx) = self.listeners.iter().position(|listener| listener.is_none()) {
+            self.listeners[idx] = Some(listener);
+            idx
+        } else {
+            self.listeners.push(Some(listener));
+            self.listeners.len() - 1
+        }
+    }
+
+    pub fn unsubscribe(&mut self, id: usize) {
+        assert!(self.listeners[id].take().is_some(), "missing listener to unsubscribe");
+    }
+
+    /// next pending event, in publication order
+    pub fn pop(&mut self) -> Option<E> {
+        self.queue.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// calls every listener with the event
+    pub fn notify(&mut self, event: &E) {
+        for listener in self.listeners.iter_mut().flatten() {
+            listener(event);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use controls::controls::ControlManagerType;
+
+use level::Level;
+
+use save::save_slots;
+
+use menu::{
+    Menu,
+    MenuChoice,
+};
+
+/// States of the game.
+/// Each state has its own control manager and rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameState {
+    Title,
+    LevelSelect,
+    Playing,
+    Paused,
+    /// `next_level` is the level of the portal, none at the end of the game
+    LevelComplete {
+        next_level: Option<String>,
+    },
+    GameOver,
+    /// lists the save slots, `in_game` is true when opened from the pause menu
+    LoadGame {
+        in_game: bool,
+    },
+}
+
+/// what the game does after a choice in a menu
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameTransition {
+    To(GameState),
+    /// loads the level and plays it from its spawn
+    StartLevel(String),
+    /// loads the next level, keeping the health and ammunition of the player
+    NextLevel(String),
+    RestartLevel,
+    /// loads the save of a slot
+    LoadGame(String),
+    Quit,
+}
+
+impl GameState {
+    /// the control manager receiving the input in this state
+    pub fn control_manager(&self) -> ControlManagerType {
+        match self {
+            GameState::Playing => ControlManagerType::Game,
+            _ => ControlManagerType::Menu,
+        }
+    }
+
+    /// the level is drawn under the menu of the state
+    pub fn shows_level(&self) -> bool {
+        !matches!(self, GameState::Title | GameState::LevelSelect | GameState::LoadGame{ in_game: false })
+    }
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    /// the menu of the state, none while playing
+    pub fn menu(&self) -> Option<Menu> {
+        let menu = match self {
+            GameState::Title => Menu::new("Wolf3d-rs", Self::items(&["Select level", "Load game", "Quit"])),
+            GameState::LevelSelect => {
+                let mut menu = Menu::new("Select level", Level::names());
+                if menu.items.is_empty() {
+                    menu.message = Some("no level in assets/maps/".to_string());
+                }
+                menu
+            },
+            GameState::LoadGame{ in_game } => {
+                let mut menu = Menu::new("Load game", save_slots());
+                if menu.items.is_empty() {
+                    menu.message = Some("no saved game".to_string());
+                }
+                if *in_game {
+                    menu = menu.overlay();
+                }
+                menu
+            },
+            GameState::Playing => return None,
+            GameState::Paused => {
+                Menu::new("Paused", Self::items(&["Resume", "Restart level", "Load game", "Main menu", "Quit"])).overlay()
+            },
+            GameState::LevelComplete{ next_level: Some(_) } => {
+                Menu::new("Level complete", Self::items(&["Next level", "Main menu"])).overlay()
+            },
+            GameState::LevelComplete{ next_level: None } => {
+                Menu::new("The end!", Self::items(&["Main menu", "Quit"])).overlay()
+            },
+            GameState::GameOver => {
+                Menu::new("Game over", Self::items(&["Retry", "Main menu", "Quit"])).overlay()
+            },
+        };
+        Some(menu)
+    }
+
+    /// the transition following the choice made in the menu of the state
+    pub fn choose(&self, menu: &Menu, choice: MenuChoice) -> Option<GameTransition> {
+        let item = match choice {
+            MenuChoice::Item(idx) => idx,
+            MenuChoice::Back => {
+                return match self {
+                    GameState::LevelSelect => Some(GameTransition::To(GameState::Title)),
+                    GameState::Paused => Some(GameTransition::To(GameState::Playing)),
+                    GameState::LoadGame{ in_game: false } => Some(GameTransition::To(GameState::Title)),
+                    GameState::LoadGame{ in_game: true } => Some(GameTransition::To(GameState::Paused)),
+                    _ => None,
+                };
+            },
+        };
+        let transition = match (self, item) {
+            (GameState::Title, 0) => GameTransition::To(GameState::LevelSelect),
+            (GameState::Title, 1) => GameTransition::To(GameState::LoadGame { in_game: false }),
+            (GameState::Title, _) => GameTransition::Quit,
+            (GameState::LevelSelect, idx) => GameTransition::StartLevel(menu.items[idx].clone()),
+            (GameState::LoadGame{ .. }, idx) => GameTransition::LoadGame(menu.items[idx].clone()),
+            (GameState::Playing, _) => return None,
+            (GameState::Paused, 0) => GameTransition::To(GameState::Playing),
+            (GameState::Paused, 1) => GameTransition::RestartLevel,
+            (GameState::Paused, 2) => GameTransition::To(GameState::LoadGame { in_game: true }),
+            (GameState::Paused, 3) => GameTransition::To(GameState::Title),
+            (GameState::Paused, _) => GameTransition::Quit,
+            (GameState::LevelComplete{ next_level: Some(level_name) }, 0) => GameTransition::NextLevel(level_name.clone()),
+            (GameState::LevelComplete{ next_level: Some(_) }, _) => GameTransition::To(GameState::Title),
+            (GameState::LevelComplete{ next_level: None }, 0) => GameTransition::To(GameState::Title),
+            (GameState::LevelComplete{ next_level: None }, _) => GameTransition::Quit,
+            (GameState::GameOver, 0) => GameTransition::RestartLevel,
+            (GameState::GameOver, 1) => GameTransition::To(GameState::Title),
+            (GameState::GameOver, _) => GameTransition::Quit,
+        };
+        Some(transition)
+    }
+}
@@ -0,0 +1,163 @@
+use sdl2::{
+    pixels::Color,
+    render::{
+        Canvas,
+        TextureCreator,
+    },
+    ttf::Sdl2TtfContext,
+    video::{
+        Window,
+        WindowContext,
+    },
+};
+
+use parser::Error;
+
+use text::ScaledFont;
+
+use cvars::{
+    CVar,
+    CVarRegistry,
+};
+
+use timer;
+
+/// average number of frames per second, refreshed every `REFRESH` seconds
+struct FpsCounter {
+    frames: u32,
+    elapsed: f32,
+    fps: f32,
+}
+
+impl FpsCounter {
+    const REFRESH: f32 = 0.5;
+
+    fn update(&mut self, delta_time: f32) {
+        self.frames += 1;
+        self.elapsed += delta_time;
+        if self.elapsed >= Self::REFRESH {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+}
+
+struct Message {
+    text: String,
+    /// seconds before the message disappears
+    remaining: f32,
+}
+
+/// what the hud shows about the game
+pub struct HudStatus<'s> {
+    pub health: u32,
+    pub ammo: u32,
+    pub level_name: &'s str,
+    /// the tile aimed at in edit mode
+    pub edit_status: Option<&'s str>,
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// Heads-up display drawn over the level.
+/// Its layout is relative to the window size.
+pub struct Hud<'ttf> {
+    font: ScaledFont<'ttf>,
+    fps: FpsCounter,
+    messages: Vec<Message>,
+    pub show_fps: CVar<bool>,
+}
+
+impl<'ttf> Hud<'ttf> {
+    /// font height, as a fraction of the window height
+    const FONT_SCALE: f32 = 1.0 / 24.0;
+    /// margin around the screen, as a fraction of the window height
+    const MARGIN_SCALE: f32 = 1.0 / 40.0;
+    const MESSAGE_DURATION: f32 = 3.0;
+    const MAX_MESSAGES: usize = 4;
+    const TEXT_COLOR: Color = Color { r: 0xf0, g: 0xe6, b: 0x8c, a: 0xff };
+    const MESSAGE_COLOR: Color = Color { r: 0xff, g: 0xff, b: 0xff, a: 0xff };
+
+    /// `font_name` is the name of a ttf file of the `assets/fonts/` directory
+    pub fn new(ttf_context: &'ttf Sdl2TtfContext, font_name: &str, window_height: u32) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            font: ScaledFont::new(ttf_context, font_name, Self::FONT_SCALE, window_height)?,
+            fps: FpsCounter { frames: 0, elapsed: 0.0, fps: 0.0 },
+            messages: vec![],
+            show_fps: CVar::new(true),
+        })
+    }
+
+    pub fn register_cvars(&self, cvars: &mut CVarRegistry) -> Result<(), Box<dyn Error>> {
+        cvars.register("hud_fps", "shows the frame rate", &self.show_fps)
+    }
+
+    /// shows a message for a few seconds
+    pub fn show_message(&mut self, text: &str) {
+        if self.messages.len() == Self::MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.messages.push(Message { text: text.to_string(), remaining: Self::MESSAGE_DURATION });
+    }
+
+    pub fn update(&mut self) {
+        let delta_time = timer::get_delta_time();
+        self.fps.update(delta_time);
+        for message in &mut self.messages {
+            message.remaining -= delta_time;
+        }
+        self.messages.retain(|message| message.remaining > 0.0);
+    }
+
+    fn draw_text(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>,
+            text: &str, color: Color, anchor: Anchor, line: i32) -> Result<(), Box<dyn Error>> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let (width, height) = canvas.window().drawable_size();
+        let margin = (height as f32 * Self::MARGIN_SCALE) as i32;
+        let (text_width, text_height) = self.font.size_of(text)?;
+        let (text_width, text_height) = (text_width as i32, text_height as i32);
+        let line_offset = line * text_height;
+
+        let (x, y) = match anchor {
+            Anchor::TopLeft => (margin, margin + line_offset),
+            Anchor::TopRight => (width as i32 - margin - text_width, margin + line_offset),
+            Anchor::BottomLeft => (margin, height as i32 - margin - text_height - line_offset),
+            Anchor::BottomRight => (width as i32 - margin - text_width, height as i32 - margin - text_height - line_offset),
+            Anchor::Center => ((width as i32 - text_width) / 2, height as i32 / 4 + line_offset),
+        };
+        self.font.draw(canvas, texture_creator, text, color, x, y)
+    }
+
+    /// draws the hud, after the level
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, status: &HudStatus) -> Result<(), Box<dyn Error>> {
+        self.font.fit(canvas)?;
+
+        self.draw_text(canvas, texture_creator, status.level_name, Self::TEXT_COLOR, Anchor::TopLeft, 0)?;
+        if let Some(edit_status) = status.edit_status {
+            self.draw_text(canvas, texture_creator, edit_status, Self::TEXT_COLOR, Anchor::TopLeft, 1)?;
+        }
+        if self.show_fps.get() {
+            let fps = format!("{:.0} FPS", self.fps.fps);
+            self.draw_text(canvas, texture_creator, &fps, Self::TEXT_COLOR, Anchor::TopRight, 0)?;
+        }
+        let health = format!("Health {}", status.health);
+        self.draw_text(canvas, texture_creator, &health, Self::TEXT_COLOR, Anchor::BottomLeft, 0)?;
+        let ammo = format!("Ammo {}", status.ammo);
+        self.draw_text(canvas, texture_creator, &ammo, Self::TEXT_COLOR, Anchor::BottomRight, 0)?;
+
+        for (line, message) in self.messages.iter().enumerate() {
+            self.draw_text(canvas, texture_creator, &message.text, Self::MESSAGE_COLOR, Anchor::Center, line as i32)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use super::*;
+
+use parser::geometry::GeometryMap;
+
+use sdl2::{
+    image::LoadSurface,
+    pixels::PixelFormatEnum,
+    render::BlendMode,
+    surface::Surface,
+};
+
+/// The geometric tile of each marching-squares case, indexed by the solid corners of the tile:
+/// 1 for the north-west corner, 2 for the north-east, 4 for the south-east and 8 for the south-west.
+/// The walls go through the middle of the tiles, the solid side on their right.
+pub const CORNER_CASES: [&str; 16] = [
+    "empty",
+    "nw_corner",
+    "ne_corner",
+    "n_wall",
+    "se_corner",
+    "nw_to_se_diagonal_bridge",
+    "e_wall",
+    "sw_corner_cut",
+    "sw_corner",
+    "w_wall",
+    "ne_to_sw_diagonal_bridge",
+    "se_corner_cut",
+    "s_wall",
+    "ne_corner_cut",
+    "nw_corner_cut",
+    "full",
+];
+pub const EMPTY_CASE: usize = 0;
+pub const FULL_CASE: usize = 15;
+/// the two cases with opposite solid corners, whose corners are either linked by a bridge or separated by a gap
+const SADDLE_CASES: [(usize, &str); 2] = [(5, "nw_to_se_diagonal_gap"), (10, "ne_to_sw_diagonal_gap")];
+
+/// symbols of the solid and empty corners in a text grid
+const SOLID_SYMBOLS: [char; 3] = ['#', 'X', '1'];
+const EMPTY_SYMBOLS: [char; 3] = ['.', ' ', '0'];
+
+/// A grid of samples on the corners of the tiles, from 0 (empty) to 1 (solid).
+/// A map built from the grid has one tile less than the grid has samples, in each direction.
+pub struct CornerGrid {
+    samples: Vec<Vec<f32>>,
+    threshold: f32,
+}
+
+impl CornerGrid {
+    /// the grid of `height` lines of `width` corners, which must be at least 2 by 2
+    pub fn new(samples: Vec<Vec<f32>>, threshold: f32) -> Result<Self, Box<dyn Error>> {
+        let width = samples.first().map_or(0, |line| line.len());
+        if samples.len() < 2 || width < 2 {
+            return Err("the corner grid must be at least 2 by 2".into());
+        }
+        if let Some(line) = samples.iter().position(|line| line.len() != width) {
+            return Err(format!("corner grid line {}: different line length", line).into());
+        }
+        Ok(Self { samples, threshold })
+    }
+
+    pub fn from_solid(solid: &[Vec<bool>]) -> Result<Self, Box<dyn Error>> {
+        let samples = solid.iter().map(|line| line.iter().map(|&solid| if solid { 1.0 } else { 0.0 }).collect()).collect();
+        Self::new(samples, 0.5)
+    }
+
+    /// one line of text per line of corners, with `#` for the solid corners and `.` for the empty ones
+    pub fn from_text(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut solid = vec![];
+        for (line_idx, line) in text.lines().enumerate() {
+            let mut solid_line = vec![];
+            for symbol in line.chars() {
+                if SOLID_SYMBOLS.contains(&symbol) {
+                    solid_line.push(true);
+                } else if EMPTY_SYMBOLS.contains(&symbol) {
+                    solid_line.push(false);
+                } else {
+                    return Err(format!("corner grid line {}: unknown symbol '{}'", line_idx, symbol).into());
+                }
+            }
+            solid.push(solid_line);
+        }
+        Self::from_solid(&solid)
+    }
+
+    /// one corner per pixel of a grayscale picture: the dark pixels are solid, the light and the transparent ones empty
+    pub fn from_image<P: AsRef<Path>>(path: P, threshold: f32) -> Result<Self, Box<dyn Error>> {
+        let mut image = Surface::from_file(path)?;
+        // converted to a known pixel format by copying it, alpha included
+        image.set_blend_mode(BlendMode::None)?;
+        let mut surface = Surface::new(image.width(), image.height(), PixelFormatEnum::ARGB8888)?;
+        image.blit(None, &mut surface, None)?;
+        let (width, height, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+        let samples = surface.with_lock(|pixels| {
+            (0..height).map(|y| {
+                (0..width).map(|x| {
+                    // ARGB8888 is stored as a little endian u32: blue, green, red, alpha
+                    let pixel = &pixels[y * pitch + x * 4..y * pitch + x * 4 + 4];
+                    // a transparent pixel is empty
+                    if pixel[3] == 0 {
+                        return 0.0;
+                    }
+                    let luminance = 0.2126 * pixel[2] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[0] as f32;
+                    1.0 - luminance / 255.0
+                }).collect()
+            }).collect()
+        });
+        Self::new(samples, threshold)
+    }
+
+    /// width and height of the map built from the grid, in tiles
+    pub fn tile_size(&self) -> (usize, usize) {
+        (self.samples[0].len() - 1, self.samples.len() - 1)
+    }
+
+    fn is_solid(&self, corner_x: usize, corner_y: usize) -> bool {
+        self.samples[corner_y][corner_x] >= self.threshold
+    }
+
+    /// the marching-squares case of a tile
+    pub fn case(&self, tile_x: usize, tile_y: usize) -> usize {
+        let corners = [(tile_x, tile_y), (tile_x + 1, tile_y), (tile_x + 1, tile_y + 1), (tile_x, tile_y + 1)];
+        corners.iter().enumerate()
+            .filter(|&(_, &(corner_x, corner_y))| self.is_solid(corner_x, corner_y))
+            .fold(0, |case, (bit, _)| case | 1 << bit)
+    }
+
+    /// The name of the geometric tile of a tile.
+    /// The opposite solid corners of a saddle are linked, unless the average of the four corners is below the threshold.
+    pub fn tile_name(&self, tile_x: usize, tile_y: usize) -> &'static str {
+        let case = self.case(tile_x, tile_y);
+        if let Some(&(_, gap)) = SADDLE_CASES.iter().find(|&&(saddle, _)| saddle == case) {
+            let center = (self.samples[tile_y][tile_x] + self.samples[tile_y][tile_x + 1]
+                + self.samples[tile_y + 1][tile_x] + self.samples[tile_y + 1][tile_x + 1]) / 4.0;
+            if center < self.threshold {
+                return gap;
+            }
+        }
+        CORNER_CASES[case]
+    }
+
+    /// The geometry map with tile names, one line per line of tiles.
+    /// All the tiles of the map which the geometry set lacks are reported in the error.
+    pub fn tile_names(&self, geo_set: &GeometrySet) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+        let (width, height) = self.tile_size();
+        let mut missing = vec![];
+        let lines = (0..height).map(|tile_y| {
+            (0..width).map(|tile_x| {
+                let name = self.tile_name(tile_x, tile_y);
+                if !geo_set.names.iter().any(|set_name| set_name == name) && !missing.contains(&name) {
+                    missing.push(name);
+                }
+                name.to_string()
+            }).collect()
+        }).collect();
+        if !missing.is_empty() {
+            return Err(format!("no tile named {}", missing.join(", ")).into());
+        }
+        Ok(lines)
+    }
+
+    /// The geometry map with the symbols of a geometry set.
+    /// All the tiles of the map which the set lacks are reported in the error.
+    pub fn tile_symbols(&self, geo_set: &GeometrySet) -> Result<Vec<String>, Box<dyn Error>> {
+        let symbols: Vec<char> = geo_set.symbols.chars().collect();
+        let (width, height) = self.tile_size();
+        let mut missing = vec![];
+        let lines = (0..height).map(|tile_y| {
+            (0..width).map(|tile_x| {
+                let name = self.tile_name(tile_x, tile_y);
+                match geo_set.names[1..].iter().position(|set_name| set_name == name) {
+                    Some(idx) => symbols[idx],
+                    None => {
+                        if !missing.contains(&name) {
+                            missing.push(name);
+                        }
+                        ' '
+                    },
+                }
+            }).collect()
+        }).collect();
+        if !missing.is_empty() {
+            return Err(format!("no tile named {}", missing.join(", ")).into());
+        }
+        Ok(lines)
+    }
+}
+
+/// The json text of a geometry map file built from a corner grid, read from a text file (`.txt`) or a grayscale picture.
+/// The tiles are written with the symbols of the geometry set, or with their names which are then the only ones the set must hold.
+pub fn autotile_geometry_map<P: AsRef<Path>>(input: P, geometry_set: &str, spawn_symbol_set: &str, threshold: f32, encoding: MapEncoding) -> Result<String, Box<dyn Error>> {
+    let input = input.as_ref();
+    let grid = match input.extension().and_then(|extension| extension.to_str()) {
+        Some("txt") => CornerGrid::from_text(&std::fs::read_to_string(input)?),
+        _ => CornerGrid::from_image(input, threshold),
+    }.map_err(|err| format!("'{}': {}", input.display(), err))?;
+
+    let geo_set = GeometrySet::new(geometry_set)
+        .map_err(|err| format!("geometry set '{}': {}", geometry_set, err))?;
+    let map = match encoding {
+        MapEncoding::Symbols => grid.tile_symbols(&geo_set).map(TileMap::Symbols),
+        MapEncoding::Names => grid.tile_names(&geo_set).map(TileMap::Names),
+    }.map_err(|err| format!("geometry set '{}': {}", geometry_set, err))?;
+    let geo_map = GeometryMap {
+        geometry_set: geometry_set.to_string(),
+        spawn_symbol_set: spawn_symbol_set.to_string(),
+        primitives: vec![],
+        map,
+    };
+    map_file_json(&geo_map)
+}
@@ -0,0 +1,316 @@
+use super::*;
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// [x, y] of the tile concerned, if any
+    pub tile: Option<[usize; 2]>,
+    pub message: String,
+}
+
+/// Everything wrong with the asset files of a level.
+/// Unlike Map::new, the checks go on after the first error, and then go further:
+/// closed border, reachable portals, existing portal targets, duplicate spawns and unused symbols.
+#[derive(Serialize, Debug)]
+pub struct CheckReport {
+    pub level: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// the tiles next to a tile, in a map of `width` by `height` tiles
+fn neighbours(tile_x: usize, tile_y: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut neighbours = Vec::with_capacity(4);
+    if tile_x > 0 {
+        neighbours.push((tile_x - 1, tile_y));
+    }
+    if tile_y > 0 {
+        neighbours.push((tile_x, tile_y - 1));
+    }
+    if tile_x + 1 < width {
+        neighbours.push((tile_x + 1, tile_y));
+    }
+    if tile_y + 1 < height {
+        neighbours.push((tile_x, tile_y + 1));
+    }
+    neighbours
+}
+
+impl CheckReport {
+    pub fn run(level_name: &str) -> Self {
+        let mut report = Self {
+            level: level_name.to_string(),
+            diagnostics: vec![],
+        };
+        if !Level::names().iter().any(|name| name == level_name) {
+            report.error(None, format!("no level directory 'assets/maps/{}'", level_name));
+            return report;
+        }
+        match LevelFiles::read(level_name) {
+            Ok(files) => report.check_files(&files),
+            Err(err) => report.error(None, err.to_string()),
+        }
+        report
+    }
+
+    /// checks asset files that may not be written yet
+    pub fn run_files(level_name: &str, files: &LevelFiles) -> Self {
+        let mut report = Self {
+            level: level_name.to_string(),
+            diagnostics: vec![],
+        };
+        report.check_files(files);
+        report
+    }
+
+    fn check_files(&mut self, files: &LevelFiles) {
+        if let Err(err) = self.check_level(files) {
+            self.error(None, err.to_string());
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.diagnostics.len() - self.error_count()
+    }
+
+    /// one line per diagnostic, then a summary
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for diagnostic in &self.diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            match diagnostic.tile {
+                Some([x, y]) => text += &format!("{}: {}: tile {}:{}: {}\n", self.level, severity, y, x, diagnostic.message),
+                None => text += &format!("{}: {}: {}\n", self.level, severity, diagnostic.message),
+            }
+        }
+        text += &format!("{}: {} error(s), {} warning(s)\n", self.level, self.error_count(), self.warning_count());
+        text
+    }
+
+    fn error(&mut self, tile: Option<[usize; 2]>, message: String) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, tile, message });
+    }
+
+    fn warning(&mut self, tile: Option<[usize; 2]>, message: String) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, tile, message });
+    }
+
+    /// the errors returned here stop the checks, as the files cannot be read
+    fn check_level(&mut self, files: &LevelFiles) -> Result<(), Box<dyn Error>> {
+        let LevelFiles { geo_map: ref raw_geo_map, mtl_map: ref raw_mtl_map, .. } = *files;
+
+        let geo_set = GeometrySet::new(&raw_geo_map.geometry_set)
+            .map_err(|err| format!("geometry set '{}': {}", raw_geo_map.geometry_set, err))?;
+        let spawn_symbol_set = SpawnSymbolSet::new(&raw_geo_map.spawn_symbol_set)
+            .map_err(|err| format!("spawn symbol set '{}': {}", raw_geo_map.spawn_symbol_set, err))?;
+        let mtl_set = MaterialSet::new(raw_mtl_map)
+            .map_err(|err| format!("material set '{}': {}", raw_mtl_map.material_set, err))?;
+        if let Some(ref raw_fog) = raw_mtl_map.fog {
+            if let Err(err) = Fog::new(raw_fog) {
+                self.error(None, err.to_string());
+            }
+        }
+        if let Some(ref raw_lighting) = raw_mtl_map.lighting {
+            if let Err(err) = Lighting::new(raw_lighting) {
+                self.error(None, format!("lighting: {}", err));
+            }
+        }
+
+        let (geo_names, geo_symbols) = geometry::tile_names(&geo_set, &spawn_symbol_set);
+        let geo_map = raw_geo_map.map.to_symbols(&geo_names, &geo_symbols)
+            .map_err(|err| format!("geometry map: {}", err))?;
+        let mtl_symbols: Vec<char> = mtl_set.symbols.chars().collect();
+        let mtl_map = raw_mtl_map.map.to_symbols(&mtl_set.material_names, &mtl_symbols)
+            .map_err(|err| format!("material map: {}", err))?;
+
+        let data = MapData {
+            geo_map,
+            mtl_map,
+            geo_set: &geo_set,
+            mtl_set: &mtl_set,
+            spawn_symbol_set: &spawn_symbol_set,
+            primitives: &raw_geo_map.primitives,
+        };
+        self.check_portal_targets(&mtl_set);
+        self.check_unused_symbols(&data);
+        let (map, spawns) = match Map::build(&data, &mut |tile, message| self.error(tile, message)) {
+            Some(built) => built,
+            // the other checks need the map
+            None => return Ok(()),
+        };
+        self.check_border(&map, &geo_set, &spawns);
+        self.check_spawns(&spawns);
+        self.check_reachable_portals(&map, &spawns, &geo_set, &mtl_set);
+        Ok(())
+    }
+
+    fn check_portal_targets(&mut self, mtl_set: &MaterialSet) {
+        let names = Level::names();
+        for (name, properties) in mtl_set.material_names.iter().zip(&mtl_set.material_properties) {
+            if let Some(Portal::ToLevel(ref target)) = properties.portal {
+                if !names.contains(target) {
+                    self.error(None, format!("portal '{}' leads to the unknown level '{}'", name, target));
+                }
+            }
+        }
+    }
+
+    fn check_unused_symbols(&mut self, data: &MapData) {
+        let geo_symbols: Vec<char> = data.geo_set.symbols.chars().collect();
+        let mtl_symbols: Vec<char> = data.mtl_set.symbols.chars().collect();
+        for (idx, symbol) in geo_symbols.iter().enumerate() {
+            if !data.geo_map.iter().any(|line| line.contains(*symbol)) {
+                self.warning(None, format!("geometry symbol '{}' ({}) is never used", symbol, data.geo_set.names[idx + 1]));
+            }
+        }
+        for (idx, symbol) in mtl_symbols.iter().enumerate() {
+            if !data.mtl_map.iter().any(|line| line.contains(*symbol)) {
+                self.warning(None, format!("material symbol '{}' ({}) is never used", symbol, data.mtl_set.material_names[idx]));
+            }
+        }
+    }
+
+    /// The part of the map reachable from the spawns, walking around the walls at eye level, must not reach the border of the map,
+    /// so that rays and players can not leave it. The doors and push walls of the border are reported on their own.
+    fn check_border(&mut self, map: &Map, geo_set: &GeometrySet, spawns: &[Spawn]) {
+        // the tiles are cut into cells, linked when no wall stands between their centers
+        const CELLS_PER_TILE: usize = 4;
+        let (width, height) = (map.width * CELLS_PER_TILE, map.height * CELLS_PER_TILE);
+        let cell_center = |cell_x: usize, cell_y: usize| Vec2 {
+            x: (cell_x as f32 + 0.5) / CELLS_PER_TILE as f32,
+            y: (cell_y as f32 + 0.5) / CELLS_PER_TILE as f32,
+        };
+        // the doors and push walls may move away, the walls of the other tiles stay
+        let blocked_in = |(tile_x, tile_y): (usize, usize), from: Vec2, to: Vec2| {
+            let tile = &map.tiles[tile_y][tile_x];
+            let origin = Vec2 { x: tile_x as f32, y: tile_y as f32 };
+            let geometry = if tile.mover.is_some() { None } else { geo_set.geometries[tile.geo_idx].as_ref() };
+            let ray = Ray::new(from, to - from);
+            geometry.into_iter().flatten().map(|primitive| (primitive, origin))
+                .chain(tile.primitives.iter().map(|&idx| (&map.primitives[idx], Vec2::default())))
+                .any(|(primitive, origin)| {
+                    primitive.elevation().at_eye_level() && primitive.ray_hit(ray, origin).is_some_and(|hit| hit.t <= 1.0)
+                })
+        };
+
+        let mut open_tiles = vec![];
+        for (tile_y, line) in map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                let on_border = tile_x == 0 || tile_y == 0 || tile_x == map.width - 1 || tile_y == map.height - 1;
+                if on_border && tile.mover.is_some() {
+                    self.error(Some([tile_x, tile_y]), "a door or push wall opens the border of the map".to_string());
+                }
+            }
+        }
+        let mut visited = vec![vec![false; width]; height];
+        let mut queue = VecDeque::new();
+        for spawn in spawns {
+            let cell = ((spawn.coordinates.x * CELLS_PER_TILE as f32) as usize, (spawn.coordinates.y * CELLS_PER_TILE as f32) as usize);
+            if !visited[cell.1][cell.0] {
+                visited[cell.1][cell.0] = true;
+                queue.push_back(cell);
+            }
+        }
+        while let Some((cell_x, cell_y)) = queue.pop_front() {
+            let tile = (cell_x / CELLS_PER_TILE, cell_y / CELLS_PER_TILE);
+            let center = cell_center(cell_x, cell_y);
+            // the points of the border of the map facing the cell
+            let mut border_points = Vec::with_capacity(2);
+            if cell_x == 0 {
+                border_points.push(Vec2 { x: 0.0, y: center.y });
+            }
+            if cell_x == width - 1 {
+                border_points.push(Vec2 { x: map.width as f32, y: center.y });
+            }
+            if cell_y == 0 {
+                border_points.push(Vec2 { x: center.x, y: 0.0 });
+            }
+            if cell_y == height - 1 {
+                border_points.push(Vec2 { x: center.x, y: map.height as f32 });
+            }
+            if border_points.into_iter().any(|point| !blocked_in(tile, center, point)) && !open_tiles.contains(&tile) {
+                open_tiles.push(tile);
+            }
+            for (next_x, next_y) in neighbours(cell_x, cell_y, width, height) {
+                let next_center = cell_center(next_x, next_y);
+                let next_tile = (next_x / CELLS_PER_TILE, next_y / CELLS_PER_TILE);
+                if visited[next_y][next_x] || blocked_in(tile, center, next_center) || blocked_in(next_tile, center, next_center) {
+                    continue;
+                }
+                visited[next_y][next_x] = true;
+                queue.push_back((next_x, next_y));
+            }
+        }
+        for (tile_x, tile_y) in open_tiles {
+            self.error(Some([tile_x, tile_y]), "the border of the map is open".to_string());
+        }
+    }
+
+    fn check_spawns(&mut self, spawns: &[Spawn]) {
+        if spawns.is_empty() {
+            self.error(None, "no player spawn".to_string());
+        }
+        for (idx, spawn) in spawns.iter().enumerate() {
+            if spawns[..idx].iter().any(|other| other.player == spawn.player) {
+                let tile = [spawn.coordinates.x as usize, spawn.coordinates.y as usize];
+                self.error(Some(tile), format!("duplicate spawn of player {}", spawn.player));
+            }
+        }
+    }
+
+    /// every spawn must lead to a portal, through the empty tiles, the doors and the push walls
+    fn check_reachable_portals(&mut self, map: &Map, spawns: &[Spawn], geo_set: &GeometrySet, mtl_set: &MaterialSet) {
+        let is_portal = |tile_x: usize, tile_y: usize| mtl_set.material_properties[map.tiles[tile_y][tile_x].mtl_idx].portal.is_some();
+        let is_passable = |tile_x: usize, tile_y: usize| {
+            let tile = &map.tiles[tile_y][tile_x];
+            tile.mover.is_some() || geo_set.geometries[tile.geo_idx].is_none()
+        };
+
+        if !(0..map.height).any(|tile_y| (0..map.width).any(|tile_x| is_portal(tile_x, tile_y))) {
+            self.error(None, "no portal".to_string());
+            return;
+        }
+        for spawn in spawns {
+            let start = (spawn.coordinates.x as usize, spawn.coordinates.y as usize);
+            let mut visited = vec![vec![false; map.width]; map.height];
+            let mut queue = VecDeque::new();
+            visited[start.1][start.0] = true;
+            queue.push_back(start);
+            let mut found = false;
+            // the portals are used from a neighbouring tile
+            while let Some((tile_x, tile_y)) = queue.pop_front() {
+                let next = neighbours(tile_x, tile_y, map.width, map.height);
+                if next.iter().any(|&(x, y)| is_portal(x, y)) {
+                    found = true;
+                    break;
+                }
+                for (x, y) in next {
+                    if !visited[y][x] && is_passable(x, y) {
+                        visited[y][x] = true;
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+            if !found {
+                self.error(Some([start.0, start.1]), format!("the spawn of player {} cannot reach any portal", spawn.player));
+            }
+        }
+    }
+}
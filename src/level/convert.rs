@@ -0,0 +1,133 @@
+use super::*;
+
+use parser::{
+    tiled::TiledMap,
+    wolf3d::{
+        Wolf3dMaps,
+        Wolf3dMap,
+    },
+};
+
+/// how the tiles of the geometry and material maps are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapEncoding {
+    /// one string of symbols per line, which needs the font of the project to be read
+    Symbols,
+    /// one array of tile names per line
+    Names,
+}
+
+impl MapEncoding {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "symbols" => Ok(MapEncoding::Symbols),
+            "names" => Ok(MapEncoding::Names),
+            _ => Err(format!("unknown map encoding '{}', expected 'symbols' or 'names'", name).into()),
+        }
+    }
+
+    /// the encoding of a map
+    pub fn of(tile_map: &TileMap) -> Self {
+        match tile_map {
+            TileMap::Symbols(_) => MapEncoding::Symbols,
+            TileMap::Names(_) => MapEncoding::Names,
+        }
+    }
+
+    fn encode(self, tile_map: &TileMap, names: &[String], symbols: &[char]) -> Result<TileMap, Box<dyn Error>> {
+        let tile_map = match self {
+            MapEncoding::Symbols => TileMap::Symbols(tile_map.to_symbols(names, symbols)?),
+            MapEncoding::Names => TileMap::Names(tile_map.to_names(names, symbols)?),
+        };
+        // the tile names of a set may not be unique, in which case the map could not be read back
+        tile_map.to_symbols(names, symbols)?;
+        Ok(tile_map)
+    }
+}
+
+/// writes the geometry map and the material map of the files with the given encoding
+pub fn encode_files(files: &mut LevelFiles, encoding: MapEncoding, geo_set: &GeometrySet, spawn_symbol_set: &SpawnSymbolSet, mtl_set: &MaterialSet) -> Result<(), Box<dyn Error>> {
+    let (geo_names, geo_symbols) = geometry::tile_names(geo_set, spawn_symbol_set);
+    let geo_map = encoding.encode(&files.geo_map.map, &geo_names, &geo_symbols)
+        .map_err(|err| format!("geometry map: {}", err))?;
+    let mtl_symbols: Vec<char> = mtl_set.symbols.chars().collect();
+    let mtl_map = encoding.encode(&files.mtl_map.map, &mtl_set.material_names, &mtl_symbols)
+        .map_err(|err| format!("material map: {}", err))?;
+
+    // both maps are converted before either is changed
+    files.geo_map.map = geo_map;
+    files.mtl_map.map = mtl_map;
+    Ok(())
+}
+
+/// rewrites the geometry map and the material map of a level with the given encoding
+pub fn convert_level(level_name: &str, encoding: MapEncoding) -> Result<(), Box<dyn Error>> {
+    let mut files = LevelFiles::read(level_name)?;
+
+    let geo_set = GeometrySet::new(&files.geo_map.geometry_set)?;
+    let spawn_symbol_set = SpawnSymbolSet::new(&files.geo_map.spawn_symbol_set)?;
+    let mtl_set = MaterialSet::new(&files.mtl_map)?;
+    encode_files(&mut files, encoding, &geo_set, &spawn_symbol_set, &mtl_set)?;
+    files.write(level_name)
+}
+
+/// moves the maps of a level to `level.json`, or back to `geometry_map.json` and `material_map.json`
+pub fn migrate_level(level_name: &str, single_file: bool) -> Result<(), Box<dyn Error>> {
+    let mut files = LevelFiles::read(level_name)?;
+    if files.single_file == single_file {
+        return Err(format!("level '{}' is already in this form", level_name).into());
+    }
+    files.single_file = single_file;
+    files.write(level_name)
+}
+
+/// the name of an imported or generated level must be a directory name, and not be taken unless the level is replaced
+pub fn check_new_level_name(level_name: &str, replace: bool) -> Result<(), Box<dyn Error>> {
+    if level_name.is_empty() || level_name == ".." || level_name.contains(['/', '\\']) {
+        return Err(format!("invalid level name '{}'", level_name).into());
+    }
+    if !replace && Level::names().iter().any(|name| name == level_name) {
+        return Err(format!("level '{}' already exists", level_name).into());
+    }
+    Ok(())
+}
+
+/// the maps of a single-file level read from a map of the Tiled editor
+pub fn read_tiled<P: AsRef<Path>>(path: P) -> Result<LevelFiles, Box<dyn Error>> {
+    TiledMap::read(&path)
+        .and_then(TiledMap::into_level_files)
+        .map_err(|err| format!("'{}': {}", path.as_ref().display(), err).into())
+}
+
+/// Imports a map of the Tiled editor as a single-file level.
+/// The level is built before being written, and an existing level is only replaced if `replace` is set.
+pub fn import_tiled<P: AsRef<Path>>(path: P, level_name: &str, replace: bool) -> Result<(), Box<dyn Error>> {
+    check_new_level_name(level_name, replace)?;
+    let files = read_tiled(path)?;
+    LevelMap::from_files(level_name, &files)?;
+    std::fs::create_dir_all(assets_path(&["maps"], level_name))?;
+    files.write(level_name)
+}
+
+/// Imports the maps of the MAPHEAD and GAMEMAPS files of the original game, found in `dir` with the given extension.
+/// The levels are named from `prefix`, and they are all built before any of them is written.
+pub fn import_wolf3d<P: AsRef<Path>>(dir: P, extension: &str, prefix: &str, replace: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let maps = Wolf3dMaps::read(dir, extension)?;
+    let mut levels = vec![];
+    for index in maps.indices() {
+        let level_name = Wolf3dMap::level_name(prefix, index);
+        check_new_level_name(&level_name, replace)?;
+        let files = maps.map(index)
+            .and_then(|map| map.into_level_files(prefix))
+            .map_err(|err| format!("map {}: {}", index, err))?;
+        LevelMap::from_files(&level_name, &files)
+            .map_err(|err| format!("level '{}': {}", level_name, err))?;
+        levels.push((level_name, files));
+    }
+    let level_names = levels.iter().map(|(level_name, _)| level_name.clone()).collect();
+    for (level_name, files) in levels {
+        std::fs::create_dir_all(assets_path(&["maps"], &level_name))?;
+        files.write(&level_name)?;
+    }
+    Ok(level_names)
+}
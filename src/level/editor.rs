@@ -0,0 +1,232 @@
+use super::*;
+
+use super::convert::encode_files;
+
+/// the change of one tile, with the spawns before and after it
+struct Edit {
+    tile_x: usize,
+    tile_y: usize,
+    /// geometry and material indices of the tile
+    before: (usize, usize),
+    after: (usize, usize),
+    spawns_before: Vec<Spawn>,
+    spawns_after: Vec<Spawn>,
+}
+
+/// The choices following `current` in the direction of `step`, wrapping around.
+/// The search starts at the first (or last) choice when `current` is not one of them.
+fn cycled(current: usize, choices: &[usize], step: isize) -> Vec<usize> {
+    let n = choices.len() as isize;
+    let start = match choices.iter().position(|&choice| choice == current) {
+        Some(idx) => idx as isize,
+        None if step > 0 => -1,
+        None => n,
+    };
+    (1..=n)
+        .map(|offset| choices[(start + offset * step).rem_euclid(n) as usize])
+        .filter(|&choice| choice != current)
+        .collect()
+}
+
+/// Editor of the map of a level, as written in its files.
+/// Every change passes the checks of `Map::new` before it is kept, and can be undone.
+pub struct MapEditor {
+    pub level_map: LevelMap,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// the map has changes which are not saved
+    pub modified: bool,
+}
+
+impl MapEditor {
+    const MAX_UNDO: usize = 256;
+
+    pub fn new(level_map: LevelMap) -> Self {
+        Self {
+            level_map,
+            undo: vec![],
+            redo: vec![],
+            modified: false,
+        }
+    }
+
+    /// edits the map read from the files of the level
+    pub fn load(level_name: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(LevelMap::load(level_name)?))
+    }
+
+    fn check_tile(&self, tile_x: usize, tile_y: usize) -> Result<(), Box<dyn Error>> {
+        if tile_x >= self.level_map.map.width || tile_y >= self.level_map.map.height {
+            return Err(format!("tile {}:{} is out of the map", tile_y, tile_x).into());
+        }
+        Ok(())
+    }
+
+    fn spawn_at(&self, tile_x: usize, tile_y: usize) -> Option<&Spawn> {
+        self.level_map.spawns.iter().find(|spawn| spawn.coordinates.x as usize == tile_x && spawn.coordinates.y as usize == tile_y)
+    }
+
+    fn set(&mut self, tile_x: usize, tile_y: usize, (geo_idx, mtl_idx): (usize, usize), spawns: Vec<Spawn>) {
+        let tile = &mut self.level_map.map.tiles[tile_y][tile_x];
+        tile.geo_idx = geo_idx;
+        tile.mtl_idx = mtl_idx;
+        self.level_map.spawns = spawns;
+    }
+
+    /// changes the tile and the spawns, unless the map does not pass the checks of `Map::new` anymore
+    fn apply(&mut self, tile_x: usize, tile_y: usize, after: (usize, usize), spawns_after: Vec<Spawn>) -> Result<(), Box<dyn Error>> {
+        let tile = &self.level_map.map.tiles[tile_y][tile_x];
+        let edit = Edit {
+            tile_x,
+            tile_y,
+            before: (tile.geo_idx, tile.mtl_idx),
+            after,
+            spawns_before: self.level_map.spawns.clone(),
+            spawns_after,
+        };
+        self.set(tile_x, tile_y, edit.after, edit.spawns_after.clone());
+        if let Err(err) = self.level_map.rebuild_map() {
+            self.set(tile_x, tile_y, edit.before, edit.spawns_before);
+            return Err(err);
+        }
+        if self.undo.len() == Self::MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.undo.push(edit);
+        self.redo.clear();
+        self.modified = true;
+        Ok(())
+    }
+
+    /// applies the first of the tiles which passes the checks, or returns the error of the last one
+    fn apply_first(&mut self, tile_x: usize, tile_y: usize, tiles: Vec<(usize, usize)>) -> Result<(), Box<dyn Error>> {
+        let mut result = Err("no other choice for this tile".into());
+        for tile in tiles {
+            result = self.apply(tile_x, tile_y, tile, self.level_map.spawns.clone());
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    /// gives the tile the next geometry of the set, in the direction of `step`
+    pub fn cycle_geometry(&mut self, tile_x: usize, tile_y: usize, step: isize) -> Result<(), Box<dyn Error>> {
+        self.check_tile(tile_x, tile_y)?;
+        if let Some(spawn) = self.spawn_at(tile_x, tile_y) {
+            return Err(format!("tile {}:{} holds the spawn of player {}", tile_y, tile_x, spawn.player).into());
+        }
+        let tile = &self.level_map.map.tiles[tile_y][tile_x];
+        let choices: Vec<usize> = (1..self.level_map.geo_set.geometries.len()).collect();
+        let tiles = cycled(tile.geo_idx, &choices, step).into_iter().map(|geo_idx| (geo_idx, tile.mtl_idx)).collect();
+        self.apply_first(tile_x, tile_y, tiles)
+    }
+
+    fn materials(&self, portals: bool) -> Vec<usize> {
+        let properties = &self.level_map.mtl_set.material_properties;
+        (0..properties.len()).filter(|&mtl_idx| properties[mtl_idx].portal.is_some() == portals).collect()
+    }
+
+    /// gives the tile the next material of the set which is not a portal, in the direction of `step`
+    pub fn cycle_material(&mut self, tile_x: usize, tile_y: usize, step: isize) -> Result<(), Box<dyn Error>> {
+        self.check_tile(tile_x, tile_y)?;
+        let tile = &self.level_map.map.tiles[tile_y][tile_x];
+        let tiles = cycled(tile.mtl_idx, &self.materials(false), step).into_iter().map(|mtl_idx| (tile.geo_idx, mtl_idx)).collect();
+        self.apply_first(tile_x, tile_y, tiles)
+    }
+
+    /// gives the tile the next portal of the set
+    pub fn cycle_portal(&mut self, tile_x: usize, tile_y: usize) -> Result<(), Box<dyn Error>> {
+        self.check_tile(tile_x, tile_y)?;
+        let portals = self.materials(true);
+        if portals.is_empty() {
+            return Err(format!("material set '{}' has no portal", self.level_map.mtl_set.name).into());
+        }
+        let tile = &self.level_map.map.tiles[tile_y][tile_x];
+        let tiles = cycled(tile.mtl_idx, &portals, 1).into_iter().map(|mtl_idx| (tile.geo_idx, mtl_idx)).collect();
+        self.apply_first(tile_x, tile_y, tiles)
+    }
+
+    /// Places the spawn of the next player of the spawn symbol set on the tile, after the player of the spawn already there.
+    /// The spawn of the last player is removed, unless it is the last spawn of the map.
+    /// A spawn moves from its previous tile, and empties the geometry of its new tile.
+    pub fn cycle_spawn(&mut self, tile_x: usize, tile_y: usize) -> Result<(), Box<dyn Error>> {
+        self.check_tile(tile_x, tile_y)?;
+        let players = &self.level_map.spawn_symbol_set.players;
+        let next_idx = match self.spawn_at(tile_x, tile_y) {
+            Some(spawn) => players.iter().position(|&player| player == spawn.player).map_or(0, |idx| idx + 1),
+            None => 0,
+        };
+        let is_on_tile = |spawn: &Spawn| spawn.coordinates.x as usize == tile_x && spawn.coordinates.y as usize == tile_y;
+        let mut spawns: Vec<Spawn> = self.level_map.spawns.iter().filter(|spawn| !is_on_tile(spawn)).cloned().collect();
+        match players.get(next_idx) {
+            Some(&player) => {
+                spawns.retain(|spawn| spawn.player != player);
+                spawns.push(Spawn {
+                    player,
+                    coordinates: Vec2 { x: tile_x as f32 + 0.5, y: tile_y as f32 + 0.5 },
+                });
+            },
+            None if spawns.is_empty() => return Err(format!("tile {}:{} holds the last spawn of the map", tile_y, tile_x).into()),
+            None => {},
+        }
+        let mtl_idx = self.level_map.map.tiles[tile_y][tile_x].mtl_idx;
+        self.apply(tile_x, tile_y, (0, mtl_idx), spawns)
+    }
+
+    /// returns false if there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                self.set(edit.tile_x, edit.tile_y, edit.before, edit.spawns_before.clone());
+                self.redo.push(edit);
+                self.modified = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                self.set(edit.tile_x, edit.tile_y, edit.after, edit.spawns_after.clone());
+                self.undo.push(edit);
+                self.modified = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// the geometry, material and spawn of a tile
+    pub fn describe_tile(&self, tile_x: usize, tile_y: usize) -> String {
+        if self.check_tile(tile_x, tile_y).is_err() {
+            return format!("tile {}:{} is out of the map", tile_y, tile_x);
+        }
+        let tile = &self.level_map.map.tiles[tile_y][tile_x];
+        let mut description = format!(
+            "tile {}:{}  {}  {}",
+            tile_y,
+            tile_x,
+            self.level_map.geo_set.names[tile.geo_idx],
+            self.level_map.mtl_set.material_names[tile.mtl_idx],
+        );
+        if let Some(spawn) = self.spawn_at(tile_x, tile_y) {
+            description += &format!("  spawn {}", spawn.player);
+        }
+        description
+    }
+
+    /// writes the map back to the files of the level, in the form and with the encoding they were read with
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let level_map = &self.level_map;
+        let saved = LevelFiles::read(&level_map.name)?;
+        let mut files = level_map.to_files(saved.single_file)?;
+        encode_files(&mut files, MapEncoding::of(&saved.geo_map.map), &level_map.geo_set, &level_map.spawn_symbol_set, &level_map.mtl_set)?;
+        files.write(&level_map.name)?;
+        self.modified = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+use super::*;
+
+use std::collections::VecDeque;
+
+use parser::geometry::GeometryMap;
+
+use super::{
+    convert::check_new_level_name,
+    autotile::{
+        CornerGrid,
+        CORNER_CASES,
+        EMPTY_CASE,
+        FULL_CASE,
+    },
+};
+
+/// sets used by the generated levels
+pub const GEOMETRY_SET: &str = "default_geo_set";
+pub const SPAWN_SYMBOL_SET: &str = "default_spawn_symbols";
+pub const MATERIAL_SET: &str = "default_mtl_set";
+pub const PORTAL_SYMBOL_SET: &str = "default_portal_symbols";
+
+/// bounds of the width and the height of a generated level, in tiles
+pub const MIN_SIZE: usize = 8;
+pub const MAX_SIZE: usize = 1024;
+
+/// the full tiles have no primitives, the solid tiles of the border are cells to keep the map closed
+const BORDER_GEOMETRY: &str = "cell";
+const PILLAR_GEOMETRY: &str = "cylinder";
+const SPAWN_GEOMETRY: &str = "spawn_1";
+
+const FLOOR_MATERIAL: &str = "default";
+const PORTAL_MATERIAL: &str = "next_level";
+const WALL_MATERIALS: [&str; 3] = ["reflective", "refractive", "absorbent"];
+
+/// bounds of the sides of the rooms, and number of tiles of the level per room
+const MIN_ROOM: usize = 3;
+const MAX_ROOM: usize = 9;
+const TILES_PER_ROOM: usize = 80;
+const ROOM_ATTEMPTS: usize = 20;
+/// the rooms of at least this side may hold pillars
+const PILLAR_ROOM: usize = 5;
+const PILLAR_CHANCE: f64 = 0.3;
+
+/// initial share of floor tiles of the caves, and number of smoothing steps of the cellular automaton
+const CAVE_FLOOR_CHANCE: f64 = 0.55;
+const CAVE_STEPS: usize = 4;
+
+/// how the floor of a generated level is laid out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeneratorStyle {
+    /// rectangular rooms linked by corridors
+    Rooms,
+    /// caves smoothed by a cellular automaton
+    Caves,
+}
+
+impl GeneratorStyle {
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "rooms" => Ok(GeneratorStyle::Rooms),
+            "caves" => Ok(GeneratorStyle::Caves),
+            _ => Err(format!("unknown generator style '{}', expected 'rooms' or 'caves'", name).into()),
+        }
+    }
+}
+
+/// a reproducible sequence of pseudo-random numbers (splitmix64)
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// a number in `min..max`, which must not be empty
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        min + (self.next_u64() % (max - min) as u64) as usize
+    }
+
+    /// true with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Room {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// the tiles of the room, and around it those of `margin` tiles, within a map of the given size
+    fn tiles(&self, margin: usize, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+        let tiles_x = self.x.saturating_sub(margin)..(self.x + self.width + margin).min(width);
+        let tiles_y = self.y.saturating_sub(margin)..(self.y + self.height + margin).min(height);
+        tiles_y.flat_map(move |tile_y| tiles_x.clone().map(move |tile_x| (tile_x, tile_y)))
+    }
+}
+
+/// Builds the maps of a level from a seed.
+/// The floor tiles are carved first, the walls then follow the edges of the floor through the marching-squares corner tiles:
+/// a corner of a tile is solid when none of the four tiles sharing it is a floor tile.
+pub struct LevelGenerator {
+    width: usize,
+    height: usize,
+    rng: Rng,
+    floor: Vec<Vec<bool>>,
+    pillars: Vec<Vec<bool>>,
+}
+
+impl LevelGenerator {
+    pub fn new(seed: u64, width: usize, height: usize) -> Result<Self, Box<dyn Error>> {
+        if !(MIN_SIZE..=MAX_SIZE).contains(&width) || !(MIN_SIZE..=MAX_SIZE).contains(&height) {
+            return Err(format!("the width and the height of a generated level must be between {} and {}", MIN_SIZE, MAX_SIZE).into());
+        }
+        Ok(Self {
+            width,
+            height,
+            rng: Rng::new(seed),
+            floor: vec![vec![false; width]; height],
+            pillars: vec![vec![false; width]; height],
+        })
+    }
+
+    /// the maps of a single-file level with tile names, whose `next_level` portal leads to `next_level`
+    pub fn generate(mut self, style: GeneratorStyle, next_level: &str) -> Result<LevelFiles, Box<dyn Error>> {
+        match style {
+            GeneratorStyle::Rooms => self.carve_rooms(),
+            GeneratorStyle::Caves => self.carve_caves(),
+        }
+
+        let corners: Vec<Vec<bool>> = (0..=self.height).map(|corner_y| {
+            (0..=self.width).map(|corner_x| self.is_solid_corner(corner_x, corner_y)).collect()
+        }).collect();
+        let grid = CornerGrid::from_solid(&corners)?;
+        let mut geo_names: Vec<Vec<String>> = (0..self.height).map(|tile_y| {
+            (0..self.width).map(|tile_x| self.geometry(&grid, tile_x, tile_y).to_string()).collect()
+        }).collect();
+        let wall_material = WALL_MATERIALS[self.rng.range(0, WALL_MATERIALS.len())];
+        let pillar_material = WALL_MATERIALS[self.rng.range(0, WALL_MATERIALS.len())];
+        let mut mtl_names: Vec<Vec<String>> = geo_names.iter().map(|line| {
+            line.iter().map(|geometry| {
+                match geometry.as_str() {
+                    "empty" => FLOOR_MATERIAL,
+                    PILLAR_GEOMETRY => pillar_material,
+                    _ => wall_material,
+                }.to_string()
+            }).collect()
+        }).collect();
+
+        let floor_tiles: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|tile_y| (0..self.width).map(move |tile_x| (tile_x, tile_y)))
+            .filter(|&(tile_x, tile_y)| geo_names[tile_y][tile_x] == CORNER_CASES[EMPTY_CASE])
+            .collect();
+        if floor_tiles.is_empty() {
+            return Err("no floor was generated".into());
+        }
+        let spawn = floor_tiles[self.rng.range(0, floor_tiles.len())];
+        let (portal_x, portal_y) = Self::farthest_wall(&geo_names, spawn)
+            .ok_or("no wall can hold the portal")?;
+        geo_names[spawn.1][spawn.0] = SPAWN_GEOMETRY.to_string();
+        mtl_names[portal_y][portal_x] = PORTAL_MATERIAL.to_string();
+
+        Ok(LevelFiles {
+            geo_map: GeometryMap {
+                geometry_set: GEOMETRY_SET.to_string(),
+                spawn_symbol_set: SPAWN_SYMBOL_SET.to_string(),
+                primitives: vec![],
+                map: TileMap::Names(geo_names),
+            },
+            mtl_map: MaterialMap {
+                material_set: MATERIAL_SET.to_string(),
+                portal_symbol_set: PORTAL_SYMBOL_SET.to_string(),
+                previous_level: None,
+                next_level: Some(next_level.to_string()),
+                bonus_level: None,
+                skybox: None,
+                fog: None,
+                lighting: None,
+                map: TileMap::Names(mtl_names),
+            },
+            single_file: true,
+        })
+    }
+
+    /// rooms placed at random, each one linked to the previous one by a corridor
+    fn carve_rooms(&mut self) {
+        let (max_width, max_height) = (MAX_ROOM.min(self.width - 2), MAX_ROOM.min(self.height - 2));
+        let room_count = (self.width * self.height / TILES_PER_ROOM).max(2);
+        let mut rooms: Vec<Room> = vec![];
+        // the rooms are kept two tiles apart, so that a wall stands between them
+        let mut near_rooms = vec![vec![false; self.width]; self.height];
+        for _ in 0..room_count * ROOM_ATTEMPTS {
+            if rooms.len() == room_count {
+                break;
+            }
+            let (width, height) = (self.rng.range(MIN_ROOM, max_width + 1), self.rng.range(MIN_ROOM, max_height + 1));
+            let room = Room {
+                x: self.rng.range(1, self.width - width),
+                y: self.rng.range(1, self.height - height),
+                width,
+                height,
+            };
+            if room.tiles(0, self.width, self.height).all(|(tile_x, tile_y)| !near_rooms[tile_y][tile_x]) {
+                for (tile_x, tile_y) in room.tiles(2, self.width, self.height) {
+                    near_rooms[tile_y][tile_x] = true;
+                }
+                rooms.push(room);
+            }
+        }
+
+        for (idx, room) in rooms.iter().enumerate() {
+            for (tile_x, tile_y) in room.tiles(0, self.width, self.height) {
+                self.floor[tile_y][tile_x] = true;
+            }
+            if idx > 0 {
+                let horizontal_first = self.rng.chance(0.5);
+                self.carve_corridor(rooms[idx - 1].center(), room.center(), horizontal_first);
+            }
+        }
+
+        // the pillars are one tile apart, off the middle lines of the room where the corridors arrive
+        for room in &rooms {
+            if room.width < PILLAR_ROOM || room.height < PILLAR_ROOM || !self.rng.chance(PILLAR_CHANCE) {
+                continue;
+            }
+            let (center_x, center_y) = room.center();
+            for tile_y in (room.y + 1..room.y + room.height - 1).step_by(2) {
+                for tile_x in (room.x + 1..room.x + room.width - 1).step_by(2) {
+                    if tile_x != center_x && tile_y != center_y {
+                        self.pillars[tile_y][tile_x] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn carve_corridor(&mut self, from: (usize, usize), to: (usize, usize), horizontal_first: bool) {
+        let corner = if horizontal_first { (to.0, from.1) } else { (from.0, to.1) };
+        for &(start, end) in &[(from, corner), (corner, to)] {
+            for tile_y in start.1.min(end.1)..=start.1.max(end.1) {
+                for tile_x in start.0.min(end.0)..=start.0.max(end.0) {
+                    self.floor[tile_y][tile_x] = true;
+                }
+            }
+        }
+    }
+
+    /// random floor smoothed by a cellular automaton, of which only the largest cave is kept
+    fn carve_caves(&mut self) {
+        let (width, height) = (self.width, self.height);
+        for row in &mut self.floor[1..height - 1] {
+            for tile in &mut row[1..width - 1] {
+                *tile = self.rng.chance(CAVE_FLOOR_CHANCE);
+            }
+        }
+        for _ in 0..CAVE_STEPS {
+            self.floor = (0..self.height).map(|tile_y| {
+                (0..self.width).map(|tile_x| self.smoothed_floor(tile_x, tile_y)).collect()
+            }).collect();
+        }
+
+        let mut cave = vec![vec![false; self.width]; self.height];
+        let mut largest = 0;
+        let mut visited = vec![vec![false; self.width]; self.height];
+        for tile_y in 0..self.height {
+            for tile_x in 0..self.width {
+                if !self.floor[tile_y][tile_x] || visited[tile_y][tile_x] {
+                    continue;
+                }
+                let tiles = self.flood_floor(tile_x, tile_y, &mut visited);
+                if tiles.len() > largest {
+                    largest = tiles.len();
+                    cave = vec![vec![false; self.width]; self.height];
+                    for (x, y) in tiles {
+                        cave[y][x] = true;
+                    }
+                }
+            }
+        }
+        if largest == 0 {
+            // nothing survived the smoothing, a small room is carved in the middle
+            let (center_x, center_y) = (self.width / 2, self.height / 2);
+            for row in &mut cave[center_y - 1..=center_y + 1] {
+                for tile in &mut row[center_x - 1..=center_x + 1] {
+                    *tile = true;
+                }
+            }
+        }
+        self.floor = cave;
+    }
+
+    /// a tile becomes a wall among more than four walls, and a floor among less than four
+    fn smoothed_floor(&self, tile_x: usize, tile_y: usize) -> bool {
+        if tile_x == 0 || tile_y == 0 || tile_x == self.width - 1 || tile_y == self.height - 1 {
+            return false;
+        }
+        let walls = (tile_y - 1..=tile_y + 1)
+            .flat_map(|y| (tile_x - 1..=tile_x + 1).map(move |x| (x, y)))
+            .filter(|&(x, y)| (x, y) != (tile_x, tile_y) && !self.floor[y][x])
+            .count();
+        match walls {
+            4 => self.floor[tile_y][tile_x],
+            _ => walls < 4,
+        }
+    }
+
+    /// the floor tiles connected to the given one
+    fn flood_floor(&self, tile_x: usize, tile_y: usize, visited: &mut [Vec<bool>]) -> Vec<(usize, usize)> {
+        let mut tiles = vec![];
+        let mut queue = VecDeque::new();
+        visited[tile_y][tile_x] = true;
+        queue.push_back((tile_x, tile_y));
+        while let Some((x, y)) = queue.pop_front() {
+            tiles.push((x, y));
+            for (next_x, next_y) in neighbours(x, y, self.width, self.height) {
+                if self.floor[next_y][next_x] && !visited[next_y][next_x] {
+                    visited[next_y][next_x] = true;
+                    queue.push_back((next_x, next_y));
+                }
+            }
+        }
+        tiles
+    }
+
+    /// a corner of the tiles, on the grid of the `width + 1` by `height + 1` corners
+    fn is_solid_corner(&self, corner_x: usize, corner_y: usize) -> bool {
+        !(corner_y.saturating_sub(1)..(corner_y + 1).min(self.height)).any(|tile_y| {
+            (corner_x.saturating_sub(1)..(corner_x + 1).min(self.width)).any(|tile_x| self.floor[tile_y][tile_x])
+        })
+    }
+
+    fn geometry(&self, grid: &CornerGrid, tile_x: usize, tile_y: usize) -> &'static str {
+        let on_border = tile_x == 0 || tile_y == 0 || tile_x == self.width - 1 || tile_y == self.height - 1;
+        if self.pillars[tile_y][tile_x] {
+            PILLAR_GEOMETRY
+        } else if on_border && grid.case(tile_x, tile_y) == FULL_CASE {
+            BORDER_GEOMETRY
+        } else {
+            grid.tile_name(tile_x, tile_y)
+        }
+    }
+
+    /// the wall tile next to the floor tile the farthest from the spawn, walking through the floor
+    fn farthest_wall(geo_names: &[Vec<String>], spawn: (usize, usize)) -> Option<(usize, usize)> {
+        let (width, height) = (geo_names[0].len(), geo_names.len());
+        let mut distances = vec![vec![None; width]; height];
+        let mut queue = VecDeque::new();
+        distances[spawn.1][spawn.0] = Some(0);
+        queue.push_back(spawn);
+        let mut farthest: Option<((usize, usize), usize)> = None;
+        while let Some((tile_x, tile_y)) = queue.pop_front() {
+            let distance = distances[tile_y][tile_x].unwrap_or(0);
+            for (x, y) in neighbours(tile_x, tile_y, width, height) {
+                match geo_names[y][x].as_str() {
+                    "empty" => {
+                        if distances[y][x].is_none() {
+                            distances[y][x] = Some(distance + 1);
+                            queue.push_back((x, y));
+                        }
+                    },
+                    "full" | PILLAR_GEOMETRY => {},
+                    _ => {
+                        if farthest.is_none_or(|(_, farthest_distance)| distance > farthest_distance) {
+                            farthest = Some(((x, y), distance));
+                        }
+                    },
+                }
+            }
+        }
+        farthest.map(|(tile, _)| tile)
+    }
+}
+
+/// the tiles sharing an edge with the given one
+fn neighbours(tile_x: usize, tile_y: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut neighbours = Vec::with_capacity(4);
+    if tile_x > 0 {
+        neighbours.push((tile_x - 1, tile_y));
+    }
+    if tile_y > 0 {
+        neighbours.push((tile_x, tile_y - 1));
+    }
+    if tile_x + 1 < width {
+        neighbours.push((tile_x + 1, tile_y));
+    }
+    if tile_y + 1 < height {
+        neighbours.push((tile_x, tile_y + 1));
+    }
+    neighbours
+}
+
+/// Generates `count` single-file levels from consecutive seeds, each one leading to the next and the last one to the first.
+/// A single level is named `level_name` and leads to itself, several ones are numbered from `level_name_1`.
+/// The levels are all built before any of them is written.
+pub fn generate_levels(level_name: &str, style: GeneratorStyle, seed: u64, width: usize, height: usize, count: usize, replace: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    let level_names: Vec<String> = match count {
+        0 => return Err("the number of levels must be positive".into()),
+        1 => vec![level_name.to_string()],
+        _ => (1..=count).map(|number| format!("{}_{}", level_name, number)).collect(),
+    };
+    let mut levels = vec![];
+    for (idx, name) in level_names.iter().enumerate() {
+        check_new_level_name(name, replace)?;
+        let files = LevelGenerator::new(seed.wrapping_add(idx as u64), width, height)?
+            .generate(style, &level_names[(idx + 1) % count])?;
+        LevelMap::from_files(name, &files)
+            .map_err(|err| format!("level '{}': {}", name, err))?;
+        levels.push((name, files));
+    }
+    for (name, files) in levels {
+        std::fs::create_dir_all(assets_path(&["maps"], name))?;
+        files.write(name)?;
+    }
+    Ok(level_names)
+}
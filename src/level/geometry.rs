@@ -1,9 +1,19 @@
 use super::*;
 
-use math_2d::PrimitiveGeometry;
+use serde::{
+    Serialize,
+    Serializer,
+};
+
+use math_2d::{
+    Vec2,
+    PrimitiveGeometry,
+};
 
 #[derive(Debug)]
 pub struct SpawnSymbolSet {
+    /// file name of the set in `assets/spawn_symbol_sets/`
+    pub name: String,
     pub symbols: String,
     pub players: Vec<usize>
 }
@@ -15,6 +25,7 @@ impl SpawnSymbolSet {
 
         let n = raw_set.spawn_symbol_set.len();
         let mut set = Self {
+            name: set_name.to_string(),
             symbols: String::with_capacity(n),
             players: Vec::with_capacity(n),
         };
@@ -25,42 +36,137 @@ impl SpawnSymbolSet {
         }
         Ok(set)
     }
+
+    /// the tile names of the spawns in the maps: "spawn_<player>"
+    pub fn names(&self) -> Vec<String> {
+        self.players.iter().map(|player| format!("spawn_{}", player)).collect()
+    }
+
+    pub fn to_raw(&self) -> parser::geometry::SpawnSymbolSet {
+        parser::geometry::SpawnSymbolSet {
+            spawn_symbol_set: self.symbols.chars().zip(&self.players).map(|(symbol, &player)| {
+                parser::geometry::SpawnSymbol { player, symbol }
+            }).collect(),
+        }
+    }
+}
+
+/// written as the file it was read from
+impl Serialize for SpawnSymbolSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_raw().serialize(serializer)
+    }
+}
+
+/// the names and symbols of the tiles of a geometry map: the geometries, then the spawns
+pub fn tile_names(geo_set: &GeometrySet, spawn_symbol_set: &SpawnSymbolSet) -> (Vec<String>, Vec<char>) {
+    let mut names: Vec<String> = geo_set.names[1..].to_vec();
+    names.extend(spawn_symbol_set.names());
+    let symbols = geo_set.symbols.chars().chain(spawn_symbol_set.symbols.chars()).collect();
+    (names, symbols)
+}
+
+/// how a geometric tile behaves over time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeometryKind {
+    Static,
+    /// the primitives slide by `slide` (in tile units) when the door opens
+    Door {
+        slide: Vec2,
+        open_time: f32,
+        close_delay: f32,
+    },
+    /// the whole tile moves `distance` tiles away from the player who pushes it
+    PushWall {
+        distance: usize,
+        move_time: f32,
+    },
+}
+
+impl GeometryKind {
+    fn new(raw_kind: parser::geometry::GeometryKind) -> Self {
+        match raw_kind {
+            parser::geometry::GeometryKind::Static => Self::Static,
+            parser::geometry::GeometryKind::Door{ slide, open_time, close_delay } => {
+                Self::Door { slide, open_time: open_time.max(0.0), close_delay: close_delay.max(0.0) }
+            },
+            parser::geometry::GeometryKind::PushWall{ distance, move_time } => {
+                Self::PushWall { distance, move_time: move_time.max(0.0) }
+            },
+        }
+    }
+
+    fn to_raw(self) -> parser::geometry::GeometryKind {
+        match self {
+            Self::Static => parser::geometry::GeometryKind::Static,
+            Self::Door { slide, open_time, close_delay } => parser::geometry::GeometryKind::Door { slide, open_time, close_delay },
+            Self::PushWall { distance, move_time } => parser::geometry::GeometryKind::PushWall { distance, move_time },
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct GeometrySet {
+    /// file name of the set in `assets/geometry_sets/`
+    pub name: String,
+    /// symbols of the geometries, from index 1 as the empty geometry at index 0 has none
+    pub symbols: String,
     pub names: Vec<String>,
     pub geometries: Vec<Option<Vec<PrimitiveGeometry>>>,
+    pub kinds: Vec<GeometryKind>,
 }
 
 impl GeometrySet {
-    pub fn new(set_name: &str) -> Result<(Self, String), Box<dyn Error>> {
+    pub fn new(set_name: &str) -> Result<Self, Box<dyn Error>> {
         let reader = read_assets_file(&["geometry_sets"], set_name)?;
         let raw_set: parser::geometry::GeometrySet = serde_json::from_reader(reader)?;
 
         let n = raw_set.geometry_set.len();
         let mut set = Self {
+            name: set_name.to_string(),
+            symbols: String::with_capacity(n),
             names: Vec::with_capacity(n + 1),
             geometries: Vec::with_capacity(n + 1),
+            kinds: Vec::with_capacity(n + 1),
         };
-        let mut symbols = String::with_capacity(n);
 
         // empty geometry at index 0
         set.names.push(String::from("empty"));
         set.geometries.push(None);
+        set.kinds.push(GeometryKind::Static);
 
         for geometry in raw_set.geometry_set {
-            symbols.push(geometry.symbol);
-            set.names.push(geometry.name);
-            let primitives = {
-                if geometry.primitives.is_some() {
-                    Some(geometry.primitives.unwrap().into_iter().map(|raw_prim| { PrimitiveGeometry::new(raw_prim) }).collect())
-                } else {
-                    None
-                }
+            let name = &geometry.name;
+            let primitives = match geometry.primitives {
+                Some(raw_primitives) => Some(raw_primitives.into_iter().map(PrimitiveGeometry::new).collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| format!("geometry '{}': {}", name, err))?),
+                None => None,
             };
+            set.symbols.push(geometry.symbol);
+            set.names.push(geometry.name);
+            set.kinds.push(GeometryKind::new(geometry.kind));
             set.geometries.push(primitives);
         }
-        Ok((set, symbols))
+        Ok(set)
+    }
+
+    pub fn to_raw(&self) -> parser::geometry::GeometrySet {
+        parser::geometry::GeometrySet {
+            geometry_set: self.symbols.chars().enumerate().map(|(idx, symbol)| {
+                parser::geometry::Geometry {
+                    name: self.names[idx + 1].clone(),
+                    symbol,
+                    kind: self.kinds[idx + 1].to_raw(),
+                    primitives: self.geometries[idx + 1].as_ref().map(|primitives| primitives.iter().map(PrimitiveGeometry::to_raw).collect()),
+                }
+            }).collect(),
+        }
+    }
+}
+
+/// written as the file it was read from
+impl Serialize for GeometrySet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_raw().serialize(serializer)
     }
 }
@@ -0,0 +1,191 @@
+use super::*;
+
+/// red, green and blue light, 1 for a fully lit wall
+pub type Rgb = [f32; 3];
+
+/// the light of a 0xRRGGBBAA color, whose alpha is the intensity
+fn color_light(color: u32) -> Rgb {
+    let intensity = (color & 0xff) as f32 / 255.0;
+    let channel = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.0 * intensity;
+    [channel(24), channel(16), channel(8)]
+}
+
+fn add_light(light: &mut Rgb, other: Rgb) {
+    for (channel, other) in light.iter_mut().zip(&other) {
+        *channel += other;
+    }
+}
+
+/// a light whose color fades out at `radius`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec2,
+    /// 0xRRGGBBAA, the alpha is the intensity of the light
+    pub color: u32,
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(raw_light: &parser::material::Light) -> Result<Self, Box<dyn Error>> {
+        let color = MaterialSet::parse_color(&raw_light.color)?;
+        if raw_light.radius <= 0.0 {
+            return Err(format!("the radius {} must be positive", raw_light.radius).into());
+        }
+        Ok(Self { position: raw_light.position, color, radius: raw_light.radius })
+    }
+
+    pub fn to_raw(&self) -> parser::material::Light {
+        parser::material::Light {
+            position: self.position,
+            color: format!("0x{:08x}", self.color),
+            radius: self.radius,
+        }
+    }
+
+    /// The light received by a point of a surface facing the light.
+    /// `normal` is the normal of the surface, and `two_sided` surfaces are lit from both sides.
+    pub fn light_at(&self, point: Vec2, normal: Vec2, two_sided: bool) -> Rgb {
+        let mut to_light = self.position - point;
+        let dist = to_light.length();
+        to_light.normalize();
+        let cos = if two_sided { normal.dot(to_light).abs() } else { normal.dot(to_light) };
+        self.received(dist, cos)
+    }
+
+    /// the light received at a distance, by a surface whose normal makes an angle of cosine `cos` with the light
+    fn received(&self, dist: f32, cos: f32) -> Rgb {
+        if dist >= self.radius || cos <= 0.0 {
+            return [0.0; 3];
+        }
+        let falloff = 1.0 - dist / self.radius;
+        let factor = falloff * falloff * cos;
+        let light = color_light(self.color);
+        [light[0] * factor, light[1] * factor, light[2] * factor]
+    }
+}
+
+/// the lights of a level, set in its material map
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lighting {
+    /// 0xRRGGBBAA, the light of the walls that no light reaches
+    pub ambient: u32,
+    pub lights: Vec<PointLight>,
+}
+
+impl Lighting {
+    pub fn new(raw_lighting: &parser::material::Lighting) -> Result<Self, Box<dyn Error>> {
+        let ambient = MaterialSet::parse_color(&raw_lighting.ambient).map_err(|err| format!("ambient light: {}", err))?;
+        let lights = raw_lighting.lights.iter().enumerate()
+            .map(|(idx, raw_light)| PointLight::new(raw_light).map_err(|err| format!("light {}: {}", idx, err).into()))
+            .collect::<Result<Vec<PointLight>, Box<dyn Error>>>()?;
+        Ok(Self { ambient, lights })
+    }
+
+    pub fn to_raw(&self) -> parser::material::Lighting {
+        parser::material::Lighting {
+            ambient: format!("0x{:08x}", self.ambient),
+            lights: self.lights.iter().map(PointLight::to_raw).collect(),
+        }
+    }
+}
+
+/// The light of the lights of a level on its walls, baked when the level is loaded.
+/// The primitives are cut into segments, and the light of each segment comes from the lights that no wall hides from its middle.
+pub struct Lightmap {
+    ambient: Rgb,
+    /// light at the center of each tile, for the doors and push walls which move away from their segments
+    tiles: Vec<Vec<Rgb>>,
+    /// light of the segments of each primitive of the geometry of each tile
+    tile_segments: Vec<Vec<Vec<Vec<Rgb>>>>,
+    /// light of the segments of each primitive of the map
+    map_segments: Vec<Vec<Rgb>>,
+}
+
+impl Lightmap {
+    /// number of segments per tile length of a primitive
+    const SEGMENTS_PER_TILE: f32 = 4.0;
+    /// distance from the lit points to the rays cast towards the lights, so that the rays leave the surface
+    const SURFACE_OFFSET: f32 = 0.001;
+
+    pub fn bake(map: &Map, geo_set: &GeometrySet, lighting: &Lighting) -> Self {
+        let mut lightmap = Self {
+            ambient: color_light(lighting.ambient),
+            tiles: Vec::with_capacity(map.height),
+            tile_segments: Vec::with_capacity(map.height),
+            map_segments: vec![],
+        };
+        for (tile_y, line) in map.tiles.iter().enumerate() {
+            let origin_y = tile_y as f32;
+            lightmap.tiles.push(line.iter().enumerate().map(|(tile_x, _)| {
+                let center = Vec2 { x: tile_x as f32 + 0.5, y: origin_y + 0.5 };
+                lightmap.light_at(map, geo_set, lighting, center, None, Some((tile_x, tile_y)))
+            }).collect());
+            lightmap.tile_segments.push(line.iter().enumerate().map(|(tile_x, tile)| {
+                let origin = Vec2 { x: tile_x as f32, y: origin_y };
+                geo_set.geometries[tile.geo_idx].iter().flatten()
+                    .map(|primitive| lightmap.bake_primitive(map, geo_set, lighting, primitive, origin))
+                    .collect()
+            }).collect());
+        }
+        lightmap.map_segments = map.primitives.iter()
+            .map(|primitive| lightmap.bake_primitive(map, geo_set, lighting, primitive, Vec2::default()))
+            .collect();
+        lightmap
+    }
+
+    fn bake_primitive(&self, map: &Map, geo_set: &GeometrySet, lighting: &Lighting, primitive: &PrimitiveGeometry, origin: Vec2) -> Vec<Rgb> {
+        let n_segments = (primitive.length() * Self::SEGMENTS_PER_TILE).ceil().max(1.0) as usize;
+        (0..n_segments).map(|segment| {
+            let (point, normal) = primitive.surface_point((segment as f32 + 0.5) / n_segments as f32, origin);
+            self.light_at(map, geo_set, lighting, point, Some((normal, primitive.is_two_sided())), None)
+        }).collect()
+    }
+
+    /// The light reaching a point, from the lights that no wall at eye level hides.
+    /// `surface` is the normal of the surface of the point and whether it is two-sided, and the walls of `own_tile` are ignored.
+    fn light_at(&self, map: &Map, geo_set: &GeometrySet, lighting: &Lighting, point: Vec2, surface: Option<(Vec2, bool)>, own_tile: Option<(usize, usize)>) -> Rgb {
+        let mut light = self.ambient;
+        for point_light in &lighting.lights {
+            let mut to_light = point_light.position - point;
+            let dist = to_light.length();
+            to_light.normalize();
+            let received = match surface {
+                Some((normal, two_sided)) => point_light.light_at(point, normal, two_sided),
+                None => point_light.received(dist, 1.0),
+            };
+            if received == [0.0; 3] || Self::hidden(map, geo_set, point + to_light * Self::SURFACE_OFFSET, to_light, dist, own_tile) {
+                continue;
+            }
+            add_light(&mut light, received);
+        }
+        light
+    }
+
+    /// true if a wall at eye level, outside of `own_tile`, is hit by the ray before `dist`
+    pub fn hidden(map: &Map, geo_set: &GeometrySet, origin: Vec2, dir: Vec2, dist: f32, own_tile: Option<(usize, usize)>) -> bool {
+        if origin.x < 0.0 || origin.y < 0.0 || origin.x >= map.width as f32 || origin.y >= map.height as f32 {
+            return true;
+        }
+        let blocks = |hit: &RayHit| hit.elevation.at_eye_level() && Some((hit.tile_x, hit.tile_y)) != own_tile;
+        map.cast_ray(Ray::new(origin, dir), dist - Self::SURFACE_OFFSET, None, geo_set, blocks).pop()
+            .is_some_and(|hit| blocks(&hit))
+    }
+
+    /// the baked light of a wall hit
+    pub fn light(&self, hit: &RayHit) -> Rgb {
+        let segments = match hit.primitive {
+            HitPrimitive::Tile(idx) => self.tile_segments[hit.tile_y][hit.tile_x].get(idx),
+            HitPrimitive::Map(idx) => self.map_segments.get(idx),
+            HitPrimitive::Mover => None,
+        };
+        match segments {
+            Some(segments) => segments[((hit.u * segments.len() as f32) as usize).min(segments.len() - 1)],
+            None => self.tiles[hit.tile_y][hit.tile_x],
+        }
+    }
+
+    /// the light of the walls that no light reaches
+    pub fn ambient(&self) -> Rgb {
+        self.ambient
+    }
+}
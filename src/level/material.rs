@@ -1,16 +1,23 @@
 use super::*;
 
+use serde::{
+    Serialize,
+    Serializer,
+};
+
 use parser::material::PortalSymbolSet;
 
 pub use parser::material::PhysicsProperties;
 
 #[derive(Debug)]
 pub enum Shading {
-    Texture(String), //Texture(SDL_Texture),
+    /// file name of a texture of `assets/material_sets/textures/`
+    Texture(String),
+    /// 0xRRGGBBAA
     Color(u32),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Portal {
     ToLevel(String),
     ToEnd,
@@ -24,6 +31,13 @@ pub struct MaterialProperties {
 
 #[derive(Debug)]
 pub struct MaterialSet {
+    /// file name of the set in `assets/material_sets/`
+    pub name: String,
+    /// file name of the portal symbol set in `assets/portal_symbol_sets/`
+    pub portal_symbol_set: String,
+    pub portal_symbols: PortalSymbolSet,
+    /// symbols of the materials, then of the portals
+    pub symbols: String,
     pub material_names: Vec<String>,
     pub material_properties: Vec<MaterialProperties>,
     pub material_shadings: Vec<Shading>,
@@ -31,10 +45,22 @@ pub struct MaterialSet {
 
 impl MaterialSet {
     const N_PORTALS: usize = 4;
+    const PORTAL_COLOR: u32 = 0x8040c0ff;
 
-    fn add_portals(&mut self, mtl_map: &MaterialMap, symbols: &mut String) -> Result<(), Box<dyn Error>> { 
+    /// parses a "0xRRGGBBAA" color
+    pub fn parse_color(color: &str) -> Result<u32, Box<dyn Error>> {
+        let invalid = || format!("invalid color '{}', expected 0xRRGGBBAA", color);
+        if !color.starts_with("0x") || color.len() != 10 {
+            return Err(invalid().into());
+        }
+        u32::from_str_radix(&color[2..], 16).map_err(|_| invalid().into())
+    }
+
+    fn add_portals(&mut self, mtl_map: &MaterialMap) -> Result<(), Box<dyn Error>> { 
         let reader = read_assets_file(&["portal_symbol_sets"], &mtl_map.portal_symbol_set)?;
         let portal_symbol_set: PortalSymbolSet = serde_json::from_reader(reader)?;
+        self.portal_symbol_set = mtl_map.portal_symbol_set.clone();
+        self.portal_symbols = portal_symbol_set.clone();
 
         let mut add_portal = |portal_type, level: &Option<String>, symbol: Option<char>| {
             if symbol.is_some() {
@@ -49,12 +75,12 @@ impl MaterialSet {
                     },
                     _ => { return; }
                 };
-                symbols.push(symbol.unwrap());
+                self.symbols.push(symbol.unwrap());
                 self.material_properties.push(MaterialProperties {
                     physics_properties: PhysicsProperties::Absorption,
                     portal
                 });
-                //self.material_shadings.push(Shading::Texture(SDL_LoadTexture(name + png)); ou Color
+                self.material_shadings.push(Shading::Color(Self::PORTAL_COLOR));
             }
         };
         add_portal(Portal::ToLevel(String::from("previous_level")), &mtl_map.previous_level, portal_symbol_set.previous_level_symbol);
@@ -64,39 +90,110 @@ impl MaterialSet {
         Ok(())
     }
 
-    pub fn new(mtl_map: &MaterialMap) -> Result<(Self, String), Box<dyn Error>> {
+    pub fn new(mtl_map: &MaterialMap) -> Result<Self, Box<dyn Error>> {
         let reader = read_assets_file(&["material_sets"], &mtl_map.material_set)?;
         let raw_set: parser::material::MaterialSet = serde_json::from_reader(reader)?;
         
         let n = raw_set.material_set.len() + Self::N_PORTALS;
         let mut set = Self {
+            name: mtl_map.material_set.clone(),
+            portal_symbol_set: String::new(),
+            portal_symbols: PortalSymbolSet::default(),
+            symbols: String::with_capacity(n),
             material_names: Vec::with_capacity(n),
             material_properties: Vec::with_capacity(n),
             material_shadings: Vec::with_capacity(n)
         };
-        let mut symbols = String::with_capacity(n);
 
         for material in raw_set.material_set {
-            symbols.push(material.symbol);
+            set.symbols.push(material.symbol);
             set.material_names.push(material.name);
             set.material_properties.push(MaterialProperties {
                 physics_properties: material.properties.physics_properties,
                 portal: None,
             });
-            /*let shading = match mtl.shading {
-                parser::material::Shading::Texture(file) => {
-                    let reader = 
-                    // tex = SDL_LoadTexture(path)?;
-                    Shading::Texture(tex)
+            let shading = match material.shading {
+                parser::material::Shading::Texture(file) => Shading::Texture(file),
+                parser::material::Shading::Color(color) => {
+                    let color = Self::parse_color(&color).map_err(|err| format!("material '{}': {}", set.material_names.last().unwrap(), err))?;
+                    Shading::Color(color)
                 },
-                parser::material::Shading::Color(clr) => {
-                    //Shading::Color(clr.parse().unwrap()) ??
-                    Shading::Color(0)
-                }
             };
-            set.material_shadings.push(shading);*/
+            set.material_shadings.push(shading);
         }
-        set.add_portals(mtl_map, &mut symbols)?;
-        Ok((set, symbols))
+        set.add_portals(mtl_map)?;
+        Ok(set)
+    }
+
+    /// the level a portal leads to, from the name of the portal: "previous_level", "next_level" or "bonus_level"
+    pub fn portal_level(&self, portal_name: &str) -> Option<String> {
+        let idx = self.material_names.iter().position(|name| name == portal_name)?;
+        match self.material_properties[idx].portal {
+            Some(Portal::ToLevel(ref level)) => Some(level.clone()),
+            _ => None,
+        }
+    }
+
+    /// the materials of the set file, without the portals
+    pub fn to_raw(&self) -> parser::material::MaterialSet {
+        parser::material::MaterialSet {
+            material_set: self.symbols.chars().enumerate()
+                .filter(|&(idx, _)| self.material_properties[idx].portal.is_none())
+                .map(|(idx, symbol)| {
+                    parser::material::Material {
+                        name: self.material_names[idx].clone(),
+                        symbol,
+                        properties: parser::material::MaterialProperties {
+                            physics_properties: self.material_properties[idx].physics_properties.clone(),
+                        },
+                        shading: match self.material_shadings[idx] {
+                            Shading::Texture(ref file_name) => parser::material::Shading::Texture(file_name.clone()),
+                            Shading::Color(color) => parser::material::Shading::Color(format!("0x{:08x}", color)),
+                        },
+                    }
+                }).collect(),
+        }
+    }
+}
+
+/// distance fog of a level, set in its material map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    /// 0xRRGGBBAA, the alpha is the opacity of the whole fog
+    pub color: u32,
+    /// distance where the fog starts
+    pub start: f32,
+    /// distance where the whole fog is reached
+    pub end: f32,
+}
+
+impl Fog {
+    pub fn new(raw_fog: &parser::material::Fog) -> Result<Self, Box<dyn Error>> {
+        let color = MaterialSet::parse_color(&raw_fog.color).map_err(|err| format!("fog: {}", err))?;
+        if raw_fog.start < 0.0 || raw_fog.end <= raw_fog.start {
+            return Err(format!("fog: the start {} must be positive and the end {} greater than the start", raw_fog.start, raw_fog.end).into());
+        }
+        Ok(Self { color, start: raw_fog.start, end: raw_fog.end })
+    }
+
+    /// opacity of the fog over a wall at a distance, from 0 to the alpha of its color
+    pub fn opacity(&self, dist: f32) -> f32 {
+        let alpha = (self.color & 0xff) as f32 / 255.0;
+        ((dist - self.start) / (self.end - self.start)).clamp(0.0, 1.0) * alpha
+    }
+
+    pub fn to_raw(&self) -> parser::material::Fog {
+        parser::material::Fog {
+            color: format!("0x{:08x}", self.color),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+/// written as the file it was read from, without the portals
+impl Serialize for MaterialSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_raw().serialize(serializer)
     }
 }
\ No newline at end of file
@@ -1,6 +1,11 @@
 use sdl2::{
     video::Window,
-    render::Canvas,
+    render::{
+        Canvas,
+        BlendMode,
+    },
+    pixels::Color,
+    rect::Rect,
 };
 
 use parser;
@@ -10,25 +15,88 @@ use parser::{
     *,
     geometry::GeometryMap,
     material::MaterialMap,
+    level::LevelFiles,
 };
 
 use math_2d::{
     Vec2,
     Ray,
+    PrimitiveGeometry,
+    PrimitiveHit,
+    Elevation,
 };
 
 use skybox::*;
 
+use timer;
+
+use events::EventBus;
+
+use std::cell::Cell;
+
 pub mod material;
 pub mod geometry;
+pub mod mover;
+pub mod trigger;
+pub mod script;
+pub mod check;
+pub mod convert;
+pub mod generator;
+pub mod autotile;
+pub mod editor;
+pub mod watch;
+pub mod light;
+mod save;
 
 pub use self::{
     geometry::GeometrySet,
-    material::MaterialSet,
+    material::{
+        MaterialSet,
+        Portal,
+        Fog,
+    },
+    mover::Mover,
+    trigger::{
+        Trigger,
+        TriggerCondition,
+        LevelEvent,
+        EnemyMarker,
+    },
+    script::LevelScript,
+    check::CheckReport,
+    convert::{
+        MapEncoding,
+        convert_level,
+        migrate_level,
+        import_tiled,
+        import_wolf3d,
+    },
+    generator::{
+        GeneratorStyle,
+        generate_levels,
+    },
+    autotile::{
+        CornerGrid,
+        autotile_geometry_map,
+    },
+    editor::MapEditor,
+    watch::FileWatcher,
+    light::{
+        PointLight,
+        Lighting,
+        Lightmap,
+        Rgb,
+    },
 };
 
-use self::geometry::SpawnSymbolSet;
+use self::material::Shading;
 
+use self::geometry::{
+    SpawnSymbolSet,
+    GeometryKind,
+};
+
+#[derive(Debug, Clone)]
 pub struct Spawn {
     pub player: usize,
     pub coordinates: Vec2
@@ -39,15 +107,18 @@ struct MapData<'a> {
     mtl_map: Vec<String>,
     geo_set: &'a GeometrySet,
     mtl_set: &'a MaterialSet,
-    geo_set_symbols: String,
-    mtl_set_symbols: String,
-    spawn_symbol_set: SpawnSymbolSet,
+    spawn_symbol_set: &'a SpawnSymbolSet,
+    primitives: &'a [parser::geometry::PrimitiveGeometry],
 }
 
 #[derive(Debug, Default)]
 pub struct MapTile {
     pub geo_idx: usize,
     pub mtl_idx: usize,
+    /// index of the door or push wall of the tile in `Map.movers`
+    pub mover: Option<usize>,
+    /// indices of the primitives of the map overlapping the tile in `Map.primitives`
+    pub primitives: Vec<usize>,
 }
 
 #[derive(Debug)]
@@ -55,45 +126,79 @@ pub struct Map {
     pub height: usize,
     pub width: usize,
     pub tiles: Vec<Vec<MapTile>>,
+    pub movers: Vec<Mover>,
+    /// primitives in map coordinates, each one registered in the tiles it overlaps
+    pub primitives: Vec<PrimitiveGeometry>,
 }
 
 impl Map {
     fn new(data: MapData) -> Result<(Self, Vec<Spawn>), Box<dyn Error>> {
-        if let Some(symbol) = check_for_duplicate_symbols(&format!("{}{}", data.geo_set_symbols, data.spawn_symbol_set.symbols)) {
-            return Err(format!("duplicate geometry/spawn symbol '{}'", symbol).into());
+        let mut first_error = None;
+        let built = Self::build(&data, &mut |tile, message| {
+            if first_error.is_none() {
+                first_error = Some(match tile {
+                    Some([col, line]) => format!("tile {}:{}: {}", line, col, message),
+                    None => message,
+                });
+            }
+        });
+        match (built, first_error) {
+            (_, Some(err)) => Err(err.into()),
+            (Some(built), None) => Ok(built),
+            (None, None) => unreachable!(),
         }
-        if let Some(symbol) = check_for_duplicate_symbols(&data.mtl_set_symbols) {
-            return Err(format!("duplicate material/portal symbol '{}'", symbol).into());
+    }
+
+    /// Builds the map, going on after the errors to report them all with the `[x, y]` of their tile, if any.
+    /// Returns None if there was an error.
+    fn build(data: &MapData, report: &mut dyn FnMut(Option<[usize; 2]>, String)) -> Option<(Self, Vec<Spawn>)> {
+        let valid = Cell::new(true);
+        let mut error = |tile, message| {
+            valid.set(false);
+            report(tile, message);
+        };
+        for symbol in duplicate_symbols(&format!("{}{}", data.geo_set.symbols, data.spawn_symbol_set.symbols)) {
+            error(None, format!("duplicate geometry/spawn symbol '{}'", symbol));
+        }
+        for symbol in duplicate_symbols(&data.mtl_set.symbols) {
+            error(None, format!("duplicate material/portal symbol '{}'", symbol));
         }
 
         let height = data.geo_map.len();
         if height == 0 {
-            return Err("empty geometry map".into());
+            error(None, "empty geometry map".to_string());
+            return None;
         }
         if height != data.mtl_map.len() {
-            return Err("map dimensions mismatch: different number of lines".into());
+            error(None, format!("map dimensions mismatch: {} geometry lines, {} material lines", height, data.mtl_map.len()));
         }
 
         let width = data.geo_map[0].chars().count();
         if width == 0 {
-            return Err("empty geometry line".into());
+            error(None, "empty geometry line".to_string());
+            return None;
         }
 
         let mut tiles = Vec::with_capacity(height);
+        let mut movers = Vec::new();
         let mut spawns = Vec::with_capacity(Level::MAX_PLAYERS);
-        for (line, (geo_symbol_line, mtl_symbol_line)) in data.geo_map.iter().zip(data.mtl_map).enumerate() {
+        for (line, (geo_symbol_line, mtl_symbol_line)) in data.geo_map.iter().zip(&data.mtl_map).enumerate() {
             if geo_symbol_line.chars().count() != width {
-                return Err(format!("geometry map line {}: different line length", line).into());
+                error(None, format!("geometry map line {}: different line length", line));
             }
             if mtl_symbol_line.chars().count() != width {
-                return Err(format!("material map line {}: different line length", line).into());
+                error(None, format!("material map line {}: different line length", line));
             }
-            tiles.push(Vec::with_capacity(geo_symbol_line.chars().count()));
+            tiles.push(Vec::with_capacity(width));
             for (col, (geo_symbol, mtl_symbol)) in geo_symbol_line.chars().zip(mtl_symbol_line.chars()).enumerate() {
                 let mut tile = MapTile::default();
-                
-                if let Some(idx) = data.geo_set_symbols.chars().position(|symbol| { symbol == geo_symbol }) {
+
+                if let Some(idx) = data.geo_set.symbols.chars().position(|symbol| { symbol == geo_symbol }) {
                     tile.geo_idx = idx + 1;
+                    if let Some(mover) = Mover::new(data.geo_set.kinds[tile.geo_idx], col, line) {
+                        tile.mover = Some(movers.len());
+                        movers.push(mover);
+                    }
                 } else if let Some(idx) = data.spawn_symbol_set.symbols.chars().position(|symbol| { symbol == geo_symbol }) {
                     spawns.push(Spawn {
                         player: data.spawn_symbol_set.players[idx],
@@ -104,67 +209,134 @@ impl Map {
                     });
                     tile.geo_idx = 0; // "empty" geometry
                 } else {
-                    return Err(format!("unknown geometry/spawn symbol '{}' at tile {}:{}", geo_symbol, line, col).into());
+                    error(Some([col, line]), format!("unknown geometry/spawn symbol '{}'", geo_symbol));
                 }
 
-                if let Some(idx) = data.mtl_set_symbols.chars().position(|symbol| { symbol == mtl_symbol }) {
+                if let Some(idx) = data.mtl_set.symbols.chars().position(|symbol| { symbol == mtl_symbol }) {
                     if data.mtl_set.material_properties[idx].portal.is_some() && data.geo_set.geometries[tile.geo_idx].is_none() {
-                        return Err(format!("Tile {}:{} : A portal must be associated to a non-empty geometric tile", line, col).into());
+                        error(Some([col, line]), "a portal must be associated to a non-empty geometric tile".to_string());
                     }
                     tile.mtl_idx = idx;
                 } else {
-                    return Err(format!("unknown material/portal symbol '{}' at tile {}:{}", mtl_symbol, line, col).into());
+                    error(Some([col, line]), format!("unknown material/portal symbol '{}'", mtl_symbol));
                 }
 
                 tiles[line].push(tile);
             }
         }
-        Ok((Self { height, width, tiles }, spawns))
+        if !valid.get() {
+            return None;
+        }
+        let mut map = Self { height, width, tiles, movers, primitives: vec![] };
+        for (idx, raw_primitive) in data.primitives.iter().enumerate() {
+            let added = PrimitiveGeometry::new_in_map(raw_primitive.clone())
+                .and_then(|primitive| map.add_primitive(primitive));
+            if let Err(err) = added {
+                error(None, format!("map primitive {}: {}", idx, err));
+            }
+        }
+        if !valid.get() {
+            return None;
+        }
+        Some((map, spawns))
     }
-}
-
-pub struct Level<'a> {
-    pub geo_set: GeometrySet,
-    pub mtl_set: MaterialSet,
-    pub spawns: Vec<Spawn>,
-    skybox: Option<Skybox<'a>>,
-    pub map: Map,
-}
 
-impl<'a> Level<'a> {
-    const MAX_PLAYERS: usize = 4;
-
-    pub fn new<P: AsRef<Path>>(level_name: P, tex_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Box<dyn Error>> {
-        let dirs = [&Path::new("maps"), level_name.as_ref()];
-
-        let geo_map_reader = read_assets_file(&dirs, "geometry_map")?;
-        let raw_geo_map: GeometryMap = serde_json::from_reader(geo_map_reader)?;
+    /// registers a primitive of the map in the tiles its bounds overlap
+    fn add_primitive(&mut self, primitive: PrimitiveGeometry) -> Result<(), Box<dyn Error>> {
+        let (min, max) = primitive.bounds();
+        // the hits on the borders of the tiles are kept by both tiles
+        let delta = 0.0001;
+        if max.x < -delta || max.y < -delta || min.x > self.width as f32 + delta || min.y > self.height as f32 + delta {
+            return Err("out of the map".into());
+        }
+        let tile_range = |min: f32, max: f32, size: usize| {
+            let first = (min - delta).max(0.0) as usize;
+            let last = ((max + delta).max(0.0) as usize).min(size - 1);
+            first..=last
+        };
+        let idx = self.primitives.len();
+        for tile_y in tile_range(min.y, max.y, self.height) {
+            for tile_x in tile_range(min.x, max.x, self.width) {
+                self.tiles[tile_y][tile_x].primitives.push(idx);
+            }
+        }
+        self.primitives.push(primitive);
+        Ok(())
+    }
 
-        let (geo_set, geo_set_symbols) = GeometrySet::new(&raw_geo_map.geometry_set)?;
-        let spawn_symbol_set = SpawnSymbolSet::new(&raw_geo_map.spawn_symbol_set)?;
+    /// the primitives of the map as written in the geometry map
+    pub fn raw_primitives(&self) -> Vec<parser::geometry::PrimitiveGeometry> {
+        self.primitives.iter().map(PrimitiveGeometry::to_raw).collect()
+    }
 
-        let mtl_map_reader = read_assets_file(&dirs, "material_map")?;
-        let raw_mtl_map: MaterialMap = serde_json::from_reader(mtl_map_reader)?;
+    /// The geometry map and the material map with the symbols of the sets, which `Map::new` reads back.
+    /// The spawns are written over the geometry of their tile,
+    /// and the tiles of the empty geometry at index 0 with the first geometry of the set without primitives.
+    fn to_symbols(&self, spawns: &[Spawn], geo_set: &GeometrySet, mtl_set: &MaterialSet, spawn_symbol_set: &SpawnSymbolSet) -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+        let geo_symbols: Vec<char> = geo_set.symbols.chars().collect();
+        let mtl_symbols: Vec<char> = mtl_set.symbols.chars().collect();
+        let spawn_symbols: Vec<char> = spawn_symbol_set.symbols.chars().collect();
+        let empty_symbol = (1..geo_set.geometries.len())
+            .find(|&idx| geo_set.geometries[idx].is_none() && geo_set.kinds[idx] == GeometryKind::Static)
+            .map(|idx| geo_symbols[idx - 1]);
+
+        let mut geo_map: Vec<Vec<char>> = Vec::with_capacity(self.height);
+        let mut mtl_map = Vec::with_capacity(self.height);
+        for (line, tiles) in self.tiles.iter().enumerate() {
+            let mut geo_line = Vec::with_capacity(self.width);
+            let mut mtl_line = String::with_capacity(self.width);
+            for (col, tile) in tiles.iter().enumerate() {
+                geo_line.push(match tile.geo_idx {
+                    0 => empty_symbol.ok_or_else(|| format!("tile {}:{}: the geometry set has no empty geometry", line, col))?,
+                    geo_idx => *geo_symbols.get(geo_idx - 1).ok_or_else(|| format!("tile {}:{}: unknown geometry {}", line, col, geo_idx))?,
+                });
+                mtl_line.push(*mtl_symbols.get(tile.mtl_idx).ok_or_else(|| format!("tile {}:{}: unknown material {}", line, col, tile.mtl_idx))?);
+            }
+            geo_map.push(geo_line);
+            mtl_map.push(mtl_line);
+        }
+        for spawn in spawns {
+            let (col, line) = (spawn.coordinates.x as usize, spawn.coordinates.y as usize);
+            let symbol = spawn_symbol_set.players.iter().position(|&player| player == spawn.player)
+                .map(|idx| spawn_symbols[idx])
+                .ok_or_else(|| format!("the spawn symbol set has no symbol for player {}", spawn.player))?;
+            match geo_map.get_mut(line).and_then(|geo_line| geo_line.get_mut(col)) {
+                Some(geo_symbol) => *geo_symbol = symbol,
+                None => return Err(format!("the spawn of player {} is out of the map", spawn.player).into()),
+            }
+        }
+        Ok((geo_map.into_iter().map(|geo_line| geo_line.into_iter().collect()).collect(), mtl_map))
+    }
+}
 
-        let (mtl_set, mtl_set_symbols) = MaterialSet::new(&raw_mtl_map)?;
+/// the primitive hit by a ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HitPrimitive {
+    /// index of the primitive in the geometry of the tile
+    Tile(usize),
+    /// index of the primitive in `Map.primitives`
+    Map(usize),
+    /// a primitive of a door or push wall, away from its tile
+    Mover,
+}
 
-        let (map, spawns) = Map::new(MapData {
-            geo_map: raw_geo_map.map,
-            mtl_map: raw_mtl_map.map,
-            geo_set: &geo_set,
-            geo_set_symbols,
-            mtl_set: &mtl_set,
-            mtl_set_symbols,
-            spawn_symbol_set,
-        })?;
-        let skybox = if raw_mtl_map.skybox.is_some() {
-                Some(Skybox::new(raw_mtl_map.skybox.unwrap(), tex_creator)?)
-            } else {
-                None
-            };
-        Ok(Self { geo_set, mtl_set, spawns, skybox, map })
-    }
+/// a wall hit by a ray
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    /// perpendicular distance to the wall
+    pub dist: f32,
+    pub tile_x: usize,
+    pub tile_y: usize,
+    pub point: Vec2,
+    /// unit normal of the wall, facing the ray
+    pub normal: Vec2,
+    /// horizontal texture coordinate of the hit
+    pub u: f32,
+    pub primitive: HitPrimitive,
+    pub elevation: Elevation,
+}
 
+impl Map {
     fn negative_step(coord: &mut usize) {
         *coord -= 1;
     }
@@ -185,7 +357,64 @@ impl<'a> Level<'a> {
         tile_y == map.height - 1
     }
 
-    fn get_wall_dist(&self, mut ray: Ray) -> Option<f32> {
+    fn in_tile(point: Vec2, tile_x: usize, tile_y: usize) -> bool {
+        let delta = 0.0001;
+        point.x >= tile_x as f32 - delta && point.x <= tile_x as f32 + 1.0 + delta
+            && point.y >= tile_y as f32 - delta && point.y <= tile_y as f32 + 1.0 + delta
+    }
+
+    /// Intersections between the ray and the primitives of the geometry `geo_idx` placed at `origin`, with their elevation.
+    /// When `clip` is set, only the hits located within the tile `clip` are kept.
+    fn geometry_intersections(&self, ray: Ray, geo_idx: usize, origin: Vec2, clip: Option<(usize, usize)>, geo_set: &GeometrySet, hits: &mut Vec<(PrimitiveHit, Elevation, HitPrimitive)>) {
+        for (idx, geometry) in geo_set.geometries[geo_idx].iter().flatten().enumerate() {
+            if let Some(hit) = geometry.ray_hit(ray, origin) {
+                if let Some((tile_x, tile_y)) = clip {
+                    if !Self::in_tile(ray.origin + ray.dir * hit.t, tile_x, tile_y) {
+                        continue;
+                    }
+                }
+                // the doors and push walls are clipped to the tile they move through
+                let primitive = if clip.is_some() { HitPrimitive::Mover } else { HitPrimitive::Tile(idx) };
+                hits.push((hit, geometry.elevation(), primitive));
+            }
+        }
+    }
+
+    /// intersections between the ray and the geometries located in the tile, nearest first,
+    /// including the moving doors and push walls
+    fn tile_intersections(&self, ray: Ray, tile_x: usize, tile_y: usize, geo_set: &GeometrySet) -> Vec<(PrimitiveHit, Elevation, HitPrimitive)> {
+        let map_tile = &self.tiles[tile_y][tile_x];
+        let tile = Vec2 { x: tile_x as f32, y: tile_y as f32 };
+        let mut hits = vec![];
+        match map_tile.mover {
+            Some(_) => {
+                let offset = self.tile_offset(tile_x, tile_y);
+                self.geometry_intersections(ray, map_tile.geo_idx, tile + offset, Some((tile_x, tile_y)), geo_set, &mut hits);
+            },
+            None => self.geometry_intersections(ray, map_tile.geo_idx, tile, None, geo_set, &mut hits),
+        }
+        if let Some(push_wall) = self.incoming_push_wall(tile_x, tile_y) {
+            let origin = Vec2 { x: push_wall.tile_x as f32, y: push_wall.tile_y as f32 } + push_wall.offset();
+            let geo_idx = self.tiles[push_wall.tile_y][push_wall.tile_x].geo_idx;
+            self.geometry_intersections(ray, geo_idx, origin, Some((tile_x, tile_y)), geo_set, &mut hits);
+        }
+        // A primitive of the map is tested in each tile it overlaps, but only its hits within the tile count:
+        // the tiles are crossed in the order of the ray, so its nearest hit is found in the tile holding it.
+        for &idx in &map_tile.primitives {
+            let primitive = &self.primitives[idx];
+            if let Some(hit) = primitive.ray_hit(ray, Vec2::default()) {
+                if Self::in_tile(ray.origin + ray.dir * hit.t, tile_x, tile_y) {
+                    hits.push((hit, primitive.elevation(), HitPrimitive::Map(idx)));
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+        hits
+    }
+
+    /// The walls hit by the ray before `max_dist`, nearest first, up to the first wall for which `stop` is true, which is the last one.
+    /// The tiles crossed by the ray are marked in `seen`, if it is set.
+    pub fn cast_ray<F: Fn(&RayHit) -> bool>(&self, mut ray: Ray, max_dist: f32, mut seen: Option<&mut Vec<Vec<bool>>>, geo_set: &GeometrySet, stop: F) -> Vec<RayHit> {
         let ray_len = ray.dir.length();
         ray.dir.normalize();
 
@@ -220,74 +449,511 @@ impl<'a> Level<'a> {
                 (Self::positive_on_y_border, Self::positive_step)
             };
         
-        // returns perpendicular wall dist or none if ray goes out of bounds
+        let mut hits = vec![];
         loop {
-            // check if ray has hit a wall
-            let tile_geometries = &self.geo_set.geometries[self.map.tiles[tile_y][tile_x].geo_idx];
-            if tile_geometries.is_some() {
-                let mut t_min: Option<f32> = None;
-                for geometry in tile_geometries.as_ref().unwrap() {
-                    if let Some(t) = geometry.ray_intersection(ray, tile_x, tile_y) {
-                        if t_min.is_none() || (t < t_min.unwrap()) {
-                            t_min.replace(t);
-                        }
-                    }
+            if let Some(ref mut seen) = seen {
+                seen[tile_y][tile_x] = true;
+            }
+            // the walls of the tile, until one of them stops the ray
+            for (hit, elevation, primitive) in self.tile_intersections(ray, tile_x, tile_y, geo_set) {
+                if hit.t / ray_len >= max_dist {
+                    return hits;
                 }
-                if t_min.is_some() {
-                    return Some(t_min.unwrap() / ray_len);
+                hits.push(RayHit {
+                    dist: hit.t / ray_len,
+                    tile_x,
+                    tile_y,
+                    point: ray.origin + ray.dir * hit.t,
+                    normal: hit.normal,
+                    u: hit.u,
+                    primitive,
+                    elevation,
+                });
+                if stop(&hits[hits.len() - 1]) {
+                    return hits;
                 }
             }
+            if side_dist.x.min(side_dist.y) / ray_len >= max_dist {
+                return hits;
+            }
 
             // jump to next tile, either in x-direction or in y-direction
-            if side_dist.x <= side_dist.y && !on_x_border(&self.map, tile_x) {
+            if side_dist.x <= side_dist.y && !on_x_border(self, tile_x) {
                 side_dist.x += delta_dist.x;
                 x_step(&mut tile_x);
-            } else if side_dist.y < side_dist.x && !on_y_border(&self.map, tile_y) {
+            } else if side_dist.y < side_dist.x && !on_y_border(self, tile_y) {
                 side_dist.y += delta_dist.y;
                 y_step(&mut tile_y);
             } else {
-                return None;
+                return hits;
             }
         }
     }
+}
+
+/// The map of a level with its geometry and material sets.
+/// It is loaded without a window, for the tools working on the asset files.
+pub struct LevelMap {
+    pub name: String,
+    pub geo_set: GeometrySet,
+    pub mtl_set: MaterialSet,
+    pub spawns: Vec<Spawn>,
+    pub map: Map,
+    /// file names of the skybox textures
+    pub skybox: Option<Vec<String>>,
+    pub fog: Option<Fog>,
+    pub lighting: Option<Lighting>,
+    pub spawn_symbol_set: SpawnSymbolSet,
+}
+
+impl LevelMap {
+    pub fn load<P: AsRef<Path>>(level_name: P) -> Result<Self, Box<dyn Error>> {
+        let files = LevelFiles::read(&level_name)?;
+        Self::from_files(level_name, &files)
+    }
+
+    /// builds the map from asset files that may not be written yet
+    pub fn from_files<P: AsRef<Path>>(level_name: P, files: &LevelFiles) -> Result<Self, Box<dyn Error>> {
+        let LevelFiles { geo_map: ref raw_geo_map, mtl_map: ref raw_mtl_map, .. } = *files;
+
+        let geo_set = GeometrySet::new(&raw_geo_map.geometry_set)?;
+        let spawn_symbol_set = SpawnSymbolSet::new(&raw_geo_map.spawn_symbol_set)?;
+        let mtl_set = MaterialSet::new(raw_mtl_map)?;
+        let fog = match raw_mtl_map.fog {
+            Some(ref raw_fog) => Some(Fog::new(raw_fog)?),
+            None => None,
+        };
+        let lighting = match raw_mtl_map.lighting {
+            Some(ref raw_lighting) => Some(Lighting::new(raw_lighting)?),
+            None => None,
+        };
+
+        let (geo_names, geo_symbols) = geometry::tile_names(&geo_set, &spawn_symbol_set);
+        let geo_map = raw_geo_map.map.to_symbols(&geo_names, &geo_symbols)
+            .map_err(|err| format!("geometry map: {}", err))?;
+        let mtl_symbols: Vec<char> = mtl_set.symbols.chars().collect();
+        let mtl_map = raw_mtl_map.map.to_symbols(&mtl_set.material_names, &mtl_symbols)
+            .map_err(|err| format!("material map: {}", err))?;
+
+        let (map, spawns) = Map::new(MapData {
+            geo_map,
+            mtl_map,
+            geo_set: &geo_set,
+            mtl_set: &mtl_set,
+            spawn_symbol_set: &spawn_symbol_set,
+            primitives: &raw_geo_map.primitives,
+        })?;
+        Ok(Self {
+            name: level_name.as_ref().to_string_lossy().into_owned(),
+            geo_set,
+            mtl_set,
+            spawns,
+            map,
+            skybox: raw_mtl_map.skybox.clone(),
+            fog,
+            lighting,
+            spawn_symbol_set,
+        })
+    }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>, pos: Vec2, dir: Vec2, fov: f32) -> Result<(), Box<dyn Error>> {
+    /// the asset files of the map, with symbols, which `from_files` reads back
+    pub fn to_files(&self, single_file: bool) -> Result<LevelFiles, Box<dyn Error>> {
+        let (geo_map, mtl_map) = self.map.to_symbols(&self.spawns, &self.geo_set, &self.mtl_set, &self.spawn_symbol_set)?;
+        Ok(LevelFiles {
+            geo_map: GeometryMap {
+                geometry_set: self.geo_set.name.clone(),
+                spawn_symbol_set: self.spawn_symbol_set.name.clone(),
+                primitives: self.map.raw_primitives(),
+                map: TileMap::Symbols(geo_map),
+            },
+            mtl_map: MaterialMap {
+                material_set: self.mtl_set.name.clone(),
+                portal_symbol_set: self.mtl_set.portal_symbol_set.clone(),
+                previous_level: self.mtl_set.portal_level("previous_level"),
+                next_level: self.mtl_set.portal_level("next_level"),
+                bonus_level: self.mtl_set.portal_level("bonus_level"),
+                skybox: self.skybox.clone(),
+                fog: self.fog.as_ref().map(Fog::to_raw),
+                lighting: self.lighting.as_ref().map(Lighting::to_raw),
+                map: TileMap::Symbols(mtl_map),
+            },
+            single_file,
+        })
+    }
+
+    /// The map and the spawns read back from the symbols of the map, with the checks of `Map::new`.
+    /// The doors and push walls of the new map are at rest.
+    pub fn rebuild_map(&self) -> Result<(Map, Vec<Spawn>), Box<dyn Error>> {
+        let (geo_map, mtl_map) = self.map.to_symbols(&self.spawns, &self.geo_set, &self.mtl_set, &self.spawn_symbol_set)?;
+        Map::new(MapData {
+            geo_map,
+            mtl_map,
+            geo_set: &self.geo_set,
+            mtl_set: &self.mtl_set,
+            spawn_symbol_set: &self.spawn_symbol_set,
+            primitives: &self.map.raw_primitives(),
+        })
+    }
+
+    /// the asset files used by the map besides the files of its level directory: its sets and textures
+    pub fn asset_files(&self) -> Vec<PathBuf> {
+        let set_file = |dir: &str, set_name: &str| assets_path(&[dir], format!("{}.json", set_name));
+        let mut files = vec![
+            set_file("geometry_sets", &self.geo_set.name),
+            set_file("spawn_symbol_sets", &self.spawn_symbol_set.name),
+            set_file("material_sets", &self.mtl_set.name),
+            set_file("portal_symbol_sets", &self.mtl_set.portal_symbol_set),
+        ];
+        for shading in &self.mtl_set.material_shadings {
+            if let Shading::Texture(ref file_name) = *shading {
+                let path = assets_path(&["material_sets", "textures"], file_name);
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        for file_name in self.skybox.iter().flatten() {
+            files.push(assets_path(&["skybox"], file_name));
+        }
+        files
+    }
+}
+
+pub struct Level<'a> {
+    pub name: String,
+    pub geo_set: GeometrySet,
+    pub mtl_set: MaterialSet,
+    pub spawns: Vec<Spawn>,
+    skybox: Option<Skybox<'a>>,
+    fog: Option<Fog>,
+    lighting: Option<Lighting>,
+    /// light of the lights of the level on its walls, none without lighting
+    lightmap: Option<Lightmap>,
+    /// lights added by the game, with the seconds they have left
+    dynamic_lights: Vec<(PointLight, f32)>,
+    pub map: Map,
+    pub triggers: Vec<Trigger>,
+    /// enemies spawned by the triggers
+    pub enemies: Vec<EnemyMarker>,
+    pub events: EventBus<'a, LevelEvent>,
+    script: Option<LevelScript>,
+    /// tiles of the players during the last update, used by the enter triggers
+    player_tiles: Vec<(usize, usize)>,
+    /// tiles crossed by the rays of the wall pass, shown by the automap
+    pub seen: Vec<Vec<bool>>,
+    watcher: FileWatcher,
+    /// top of the highest primitive: a wall from the floor up to it hides everything behind
+    wall_top: f32,
+}
+
+impl<'a> Level<'a> {
+    const MAX_PLAYERS: usize = 4;
+    /// maximum number of events processed by one update, which stops triggers from firing each other forever
+    const MAX_EVENTS_PER_UPDATE: usize = 256;
+    /// 0xRRGGBBAA
+    const WALL_COLOR: u32 = 0x247048ff;
+    /// direction of the light shading the sides of the walls: (2, 1) normalized
+    const LIGHT_DIR: Vec2 = Vec2 { x: 0.894_427_2, y: 0.447_213_6 };
+    /// brightness of the sides facing away from the light
+    const MIN_SHADE: f32 = 0.5;
+    /// 0xRRGGBBAA
+    const MUZZLE_FLASH_COLOR: u32 = 0xffd890c0;
+    const MUZZLE_FLASH_RADIUS: f32 = 4.0;
+    /// seconds
+    const MUZZLE_FLASH_TIME: f32 = 0.08;
+
+    /// names of the levels, which are the directories of `assets/maps/`
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = match std::fs::read_dir(assets_path(&["maps"], "")) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect(),
+            Err(_) => vec![],
+        };
+        names.sort();
+        names
+    }
+
+    pub fn new<P: AsRef<Path>>(level_name: P, tex_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Box<dyn Error>> {
+        let level_map = LevelMap::load(&level_name)?;
+        let dirs = [&Path::new("maps"), level_name.as_ref()];
+        let watcher = FileWatcher::new(assets_path(&dirs, ""), level_map.asset_files());
+        let LevelMap { name, geo_set, mtl_set, spawns, mut map, skybox, fog, lighting, .. } = level_map;
+        let skybox = match skybox {
+            Some(names) => Some(Skybox::new(names, tex_creator)?),
+            None => None,
+        };
+        let triggers = Trigger::load(&dirs, &map, &geo_set, &mtl_set)?;
+        let mut events = EventBus::new();
+        let mut script = LevelScript::load(&level_name, &map, &geo_set, &mtl_set, &mut events);
+        if let Some(ref mut script) = script {
+            script.start(&mut map, &mut events);
+        }
+        let seen = vec![vec![false; map.width]; map.height];
+        // the players start on the spawns, whose enter triggers do not fire
+        let player_tiles = spawns.iter().map(|spawn| (spawn.coordinates.x as usize, spawn.coordinates.y as usize)).collect();
+        let wall_top = geo_set.geometries.iter().flatten().flatten().chain(&map.primitives)
+            .map(|primitive| primitive.elevation().top())
+            .fold(1.0, f32::max);
+        let mut level = Self {
+            name,
+            geo_set,
+            mtl_set,
+            spawns,
+            skybox,
+            fog,
+            lighting,
+            lightmap: None,
+            dynamic_lights: vec![],
+            map,
+            triggers,
+            enemies: vec![],
+            events,
+            script,
+            player_tiles,
+            seen,
+            watcher,
+            wall_top,
+        };
+        level.bake_lights();
+        Ok(level)
+    }
+
+    /// bakes the lights of the level on the walls of its map, as they are now
+    pub fn bake_lights(&mut self) {
+        let lightmap = self.lighting.as_ref().map(|lighting| Lightmap::bake(&self.map, &self.geo_set, lighting));
+        self.lightmap = lightmap;
+    }
+
+    /// adds a light for `duration` seconds, on top of the baked light
+    pub fn add_light(&mut self, light: PointLight, duration: f32) {
+        self.dynamic_lights.push((light, duration));
+    }
+
+    /// animates the doors and push walls, fires the enter triggers and dispatches the pending events
+    pub fn update(&mut self, players: &[Vec2]) {
+        let delta_time = timer::get_delta_time();
+        self.map.update(delta_time, &self.geo_set, players);
+        for &mut (_, ref mut time_left) in &mut self.dynamic_lights {
+            *time_left -= delta_time;
+        }
+        self.dynamic_lights.retain(|&(_, time_left)| time_left > 0.0);
+        if let Some(ref mut script) = self.script {
+            script.on_update(&mut self.map, &mut self.events, delta_time);
+        }
+
+        let player_tiles: Vec<(usize, usize)> = players.iter().map(|pos| (pos.x as usize, pos.y as usize)).collect();
+        for (idx, &(tile_x, tile_y)) in player_tiles.iter().enumerate() {
+            if self.player_tiles.get(idx) != Some(&(tile_x, tile_y)) {
+                self.fire_triggers(tile_x, tile_y, TriggerCondition::Enter);
+            }
+        }
+        self.player_tiles = player_tiles;
+
+        let mut n_events = 0;
+        while let Some(event) = self.events.pop() {
+            if n_events == Self::MAX_EVENTS_PER_UPDATE {
+                // the remaining events are dropped
+                while self.events.pop().is_some() {}
+                break;
+            }
+            self.apply_event(&event);
+            self.events.notify(&event);
+            n_events += 1;
+        }
+    }
+
+    /// publishes the events of the triggers of the tile matching the condition, and calls the script of the level
+    pub fn fire_triggers(&mut self, tile_x: usize, tile_y: usize, condition: TriggerCondition) {
+        for trigger in &mut self.triggers {
+            if trigger.tile_x != tile_x || trigger.tile_y != tile_y || trigger.condition != condition {
+                continue;
+            }
+            if trigger.once && trigger.fired {
+                continue;
+            }
+            trigger.fired = true;
+            for event in &trigger.events {
+                self.events.publish(event.clone());
+            }
+        }
+        if let Some(ref mut script) = self.script {
+            script.on_trigger(&mut self.map, &mut self.events, condition, tile_x, tile_y);
+        }
+    }
+
+    /// the part of the events that affects the level itself
+    fn apply_event(&mut self, event: &LevelEvent) {
+        match *event {
+            LevelEvent::OpenDoor{ tile_x, tile_y } => {
+                if let Some(idx) = self.map.tiles[tile_y][tile_x].mover {
+                    if let Mover::Door(ref mut door) = self.map.movers[idx] {
+                        if door.state == mover::DoorState::Closed || door.state == mover::DoorState::Closing {
+                            door.activate();
+                        }
+                    }
+                }
+            },
+            LevelEvent::SpawnEnemies{ ref kind, count, tile_x, tile_y } => {
+                let position = Vec2 { x: tile_x as f32 + 0.5, y: tile_y as f32 + 0.5 };
+                self.enemies.extend((0..count).map(|_| EnemyMarker { kind: kind.clone(), position }));
+            },
+            LevelEvent::ActivatePortal{ tile_x, tile_y } => {
+                let mtl_idx = self.map.tiles[tile_y][tile_x].mtl_idx;
+                if let Some(ref portal) = self.mtl_set.material_properties[mtl_idx].portal {
+                    self.events.publish(LevelEvent::EnterPortal(portal.clone()));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// returns true if a file of the level, or a set or texture it uses, changed since the level was loaded or the last call
+    pub fn files_changed(&mut self) -> bool {
+        self.watcher.poll(timer::get_delta_time())
+    }
+
+    /// takes the files of the level as unchanged, after the game wrote them itself
+    pub fn acknowledge_file_changes(&mut self) {
+        self.watcher.acknowledge();
+    }
+
+    /// a player uses a tile: opens doors, pushes push walls, goes through portals and fires the use triggers.
+    /// `players` holds the positions of the players, which push walls do not move into.
+    pub fn use_tile(&mut self, tile_x: usize, tile_y: usize, dir: Vec2, players: &[Vec2]) {
+        if tile_x >= self.map.width || tile_y >= self.map.height {
+            return;
+        }
+        self.map.use_tile(tile_x, tile_y, dir, &self.geo_set, players);
+        if self.mtl_set.material_properties[self.map.tiles[tile_y][tile_x].mtl_idx].portal.is_some() {
+            self.events.publish(LevelEvent::ActivatePortal { tile_x, tile_y });
+        }
+        self.fire_triggers(tile_x, tile_y, TriggerCondition::Use);
+    }
+
+    /// a player shoots: fires the shoot triggers of the first wall hit at eye level
+    pub fn shoot(&mut self, ray: Ray) {
+        let muzzle_flash = PointLight { position: ray.origin, color: Self::MUZZLE_FLASH_COLOR, radius: Self::MUZZLE_FLASH_RADIUS };
+        self.add_light(muzzle_flash, Self::MUZZLE_FLASH_TIME);
+        if let Some(hit) = self.get_wall_dist(ray) {
+            self.fire_triggers(hit.tile_x, hit.tile_y, TriggerCondition::Shoot);
+        }
+    }
+
+    /// the first tile with a wall at eye level hit by the ray within `reach`, otherwise the tile at `reach` along the ray
+    pub fn aimed_tile(&self, ray: Ray, reach: f32) -> Option<(usize, usize)> {
+        let mut dir = ray.dir;
+        dir.normalize();
+        match self.get_wall_dist(Ray::new(ray.origin, dir)) {
+            Some(hit) if hit.dist <= reach => Some((hit.tile_x, hit.tile_y)),
+            _ => {
+                let end = ray.origin + dir * reach;
+                if end.x < 0.0 || end.y < 0.0 || end.x as usize >= self.map.width || end.y as usize >= self.map.height {
+                    return None;
+                }
+                Some((end.x as usize, end.y as usize))
+            },
+        }
+    }
+
+    /// the first wall at eye level hit by the ray, or none if the ray goes out of bounds
+    fn get_wall_dist(&self, ray: Ray) -> Option<RayHit> {
+        self.map.cast_ray(ray, f32::INFINITY, None, &self.geo_set, |hit| hit.elevation.at_eye_level()).pop()
+            .filter(|hit| hit.elevation.at_eye_level())
+    }
+
+    fn color_channels(color: u32) -> (u8, u8, u8) {
+        ((color >> 24) as u8, (color >> 16) as u8, (color >> 8) as u8)
+    }
+
+    /// the color of a wall, darker the more its side faces away from the light, and blended with the fog
+    fn wall_color(&self, hit: &RayHit) -> Color {
+        let (r, g, b) = Self::color_channels(Self::WALL_COLOR);
+        let shade = Self::MIN_SHADE + (1.0 - Self::MIN_SHADE) * hit.normal.dot(Self::LIGHT_DIR).abs();
+        let light = self.wall_light(hit);
+        let (fog_r, fog_g, fog_b, opacity) = match self.fog {
+            Some(ref fog) => {
+                let (fog_r, fog_g, fog_b) = Self::color_channels(fog.color);
+                (fog_r, fog_g, fog_b, fog.opacity(hit.dist))
+            },
+            None => (0, 0, 0, 0.0),
+        };
+        let blend = |wall: u8, light: f32, fog: u8| ((wall as f32 * shade * light).min(255.0) * (1.0 - opacity) + fog as f32 * opacity) as u8;
+        Color::RGB(blend(r, light[0], fog_r), blend(g, light[1], fog_g), blend(b, light[2], fog_b))
+    }
+
+    /// The light of a wall: the baked light, or full light for a level without lighting, and the dynamic lights.
+    /// The dynamic lights are not hidden by the walls.
+    fn wall_light(&self, hit: &RayHit) -> Rgb {
+        let mut light = match self.lightmap {
+            Some(ref lightmap) => lightmap.light(hit),
+            None => [1.0; 3],
+        };
+        for (dynamic_light, _) in &self.dynamic_lights {
+            let received = dynamic_light.light_at(hit.point, hit.normal, false);
+            for (channel, received) in light.iter_mut().zip(&received) {
+                *channel += received;
+            }
+        }
+        light
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, pos: Vec2, dir: Vec2, fov: f32) -> Result<(), Box<dyn Error>> {
         if let Some(ref skybox) = self.skybox.as_ref() {
             skybox.draw(canvas, dir, fov);
+            // the sky is beyond the end of the fog, so it is covered like a wall at the end of the fog, whatever the direction
+            if let Some(ref fog) = self.fog {
+                let (width, height) = canvas.window().drawable_size();
+                let (r, g, b) = Self::color_channels(fog.color);
+                canvas.set_blend_mode(BlendMode::Blend);
+                canvas.set_draw_color(Color::RGBA(r, g, b, (fog.opacity(fog.end) * 255.0).round() as u8));
+                let fill = canvas.fill_rect(Rect::new(0, 0, width, height / 2));
+                canvas.set_blend_mode(BlendMode::None);
+                fill?;
+            }
         }
         // half length of the camera plane: 
         let camera_plane_hlen = (fov / 2.0).to_radians().tan();
         
         let camera_plane_dir = dir.orthogonal(true) * camera_plane_hlen; // camera plane vector
         let (width, height) = canvas.window().drawable_size();
+        // seen is put back even if drawing fails
+        let mut seen = std::mem::take(&mut self.seen);
+        let mut result = Ok(());
 
-        for x in 0..width {
+        let wall_top = self.wall_top;
+        let center = height as f32 / 2.0;
+        'columns: for x in 0..width {
             let camera_x = 2.0 * (x as f32) / (width as f32) - 1.0; //x-coordinate in camera space
-            let wall_dist = self.get_wall_dist(Ray::new(pos, dir + camera_plane_dir * camera_x));
-            if wall_dist.is_none() {
-                continue;
-            }
-
-            // height of line to draw on screen
-            let line_height = ((height as f32 / wall_dist.unwrap()) * 1.3) as i32;
-
-            // calculate lowest and highest pixel to fill in current stripe
-            let draw_start = {
-                let start = (height as i32 - line_height) / 2;
-                if start < 0 { 0 } else { start }
-            };
-            let draw_end = {
-                let end = height as i32 / 2 + line_height / 2;
-                if end >= height as i32 { (height - 1) as i32 } else { end }
-            };
+            // the walls behind the low and raised ones are drawn first
+            let hits = self.map.cast_ray(Ray::new(pos, dir + camera_plane_dir * camera_x), f32::INFINITY, Some(&mut seen), &self.geo_set, |hit| {
+                hit.elevation.base <= 0.0 && hit.elevation.top() >= wall_top
+            });
+            for hit in hits.iter().rev() {
+                // height on screen of a wall going from the floor to the ceiling
+                let line_height = (height as f32 / hit.dist) * 1.3;
+                let screen_y = |z: f32| (center + (Elevation::EYE_LEVEL - z) * line_height) as i32;
+
+                // lowest and highest pixel of the stripe
+                let draw_start = screen_y(hit.elevation.top()).max(0);
+                let draw_end = screen_y(hit.elevation.base).min(height as i32 - 1);
+                if draw_start > draw_end {
+                    continue;
+                }
 
-            // draw the pixels of the stripe as a vertical line
-            let color = sdl2::pixels::Color::RGB(0x24, 0x70, 0x48);
-            let p1 = sdl2::rect::Point::new(x as i32, draw_start);
-            let p2 = sdl2::rect::Point::new(x as i32, draw_end);
-            canvas.set_draw_color(color);
-            canvas.draw_line(p1, p2)?;
+                // draw the pixels of the stripe as a vertical line
+                let p1 = sdl2::rect::Point::new(x as i32, draw_start);
+                let p2 = sdl2::rect::Point::new(x as i32, draw_end);
+                canvas.set_draw_color(self.wall_color(hit));
+                if let Err(err) = canvas.draw_line(p1, p2) {
+                    result = Err(err.into());
+                    break 'columns;
+                }
+            }
         }
-        Ok(())
+        self.seen = seen;
+        result
     }
 }
\ No newline at end of file
@@ -0,0 +1,423 @@
+use math_2d::{
+    Vec2,
+    PrimitiveGeometry,
+    clampf,
+};
+
+use std::ops::RangeInclusive;
+use std::error::Error;
+
+use super::{
+    Map,
+    GeometrySet,
+    geometry::GeometryKind,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    /// `timer` counts the seconds spent fully open
+    Open { timer: f32 },
+    Closing,
+}
+
+#[derive(Debug)]
+pub struct Door {
+    pub tile_x: usize,
+    pub tile_y: usize,
+    slide: Vec2,
+    open_time: f32,
+    close_delay: f32,
+    pub state: DoorState,
+    /// 0.0 when the door is closed, 1.0 when it is open
+    pub progress: f32,
+}
+
+impl Door {
+    /// a door can be walked through once it is open at least this much
+    const PASSABLE: f32 = 0.9;
+
+    pub fn activate(&mut self) {
+        self.state = match self.state {
+            DoorState::Closed | DoorState::Closing => DoorState::Opening,
+            DoorState::Opening | DoorState::Open{ .. } => DoorState::Closing,
+        };
+    }
+
+    /// `occupied` prevents the door from closing on someone standing in its tile
+    fn update(&mut self, delta_time: f32, occupied: bool) {
+        let step = if self.open_time > 0.0 { delta_time / self.open_time } else { 1.0 };
+        match self.state {
+            DoorState::Closed => {},
+            DoorState::Opening => {
+                self.progress += step;
+                if self.progress >= 1.0 {
+                    self.progress = 1.0;
+                    self.state = DoorState::Open { timer: 0.0 };
+                }
+            },
+            DoorState::Open{ timer } => {
+                // a close delay of 0 keeps the door open until it is used again
+                if !occupied && self.close_delay > 0.0 {
+                    let timer = timer + delta_time;
+                    self.state = if timer >= self.close_delay {
+                        DoorState::Closing
+                    } else {
+                        DoorState::Open { timer }
+                    };
+                }
+            },
+            DoorState::Closing => {
+                if occupied {
+                    self.state = DoorState::Opening;
+                    return;
+                }
+                self.progress -= step;
+                if self.progress <= 0.0 {
+                    self.progress = 0.0;
+                    self.state = DoorState::Closed;
+                }
+            },
+        }
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        self.slide * self.progress
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.progress < Self::PASSABLE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushWallState {
+    Idle,
+    Moving {
+        step_x: isize,
+        step_y: isize,
+        remaining: usize,
+    },
+    Done,
+}
+
+#[derive(Debug)]
+pub struct PushWall {
+    pub tile_x: usize,
+    pub tile_y: usize,
+    distance: usize,
+    move_time: f32,
+    pub state: PushWallState,
+    /// 0.0 to 1.0 between the current tile and the target tile
+    pub progress: f32,
+}
+
+impl PushWall {
+    /// the tile the push wall is currently moving into
+    pub fn target(&self) -> Option<(usize, usize)> {
+        match self.state {
+            PushWallState::Moving{ step_x, step_y, .. } => {
+                Some(((self.tile_x as isize + step_x) as usize, (self.tile_y as isize + step_y) as usize))
+            },
+            _ => None,
+        }
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        match self.state {
+            PushWallState::Moving{ step_x, step_y, .. } => {
+                Vec2 { x: step_x as f32, y: step_y as f32 } * self.progress
+            },
+            _ => Vec2::default(),
+        }
+    }
+
+    fn push(&mut self, step_x: isize, step_y: isize) -> bool {
+        if self.state != PushWallState::Idle || self.distance == 0 {
+            return false;
+        }
+        self.state = PushWallState::Moving { step_x, step_y, remaining: self.distance };
+        self.progress = 0.0;
+        true
+    }
+
+    /// returns true when the wall has fully entered its target tile
+    fn update(&mut self, delta_time: f32) -> bool {
+        if let PushWallState::Moving{ .. } = self.state {
+            self.progress += if self.move_time > 0.0 { delta_time / self.move_time } else { 1.0 };
+            return self.progress >= 1.0;
+        }
+        false
+    }
+
+    /// moves the wall to its target tile, then returns the next target if there is one
+    fn step(&mut self) -> Option<(usize, usize)> {
+        if let PushWallState::Moving{ step_x, step_y, remaining } = self.state {
+            self.tile_x = (self.tile_x as isize + step_x) as usize;
+            self.tile_y = (self.tile_y as isize + step_y) as usize;
+            self.progress = 0.0;
+            if remaining > 1 {
+                self.state = PushWallState::Moving { step_x, step_y, remaining: remaining - 1 };
+                return self.target();
+            }
+            self.state = PushWallState::Done;
+        }
+        None
+    }
+
+    fn stop(&mut self) {
+        self.state = PushWallState::Done;
+        self.progress = 0.0;
+    }
+}
+
+/// tile whose geometry changes over time
+#[derive(Debug)]
+pub enum Mover {
+    Door(Door),
+    PushWall(PushWall),
+}
+
+impl Mover {
+    pub fn new(kind: GeometryKind, tile_x: usize, tile_y: usize) -> Option<Self> {
+        match kind {
+            GeometryKind::Static => None,
+            GeometryKind::Door{ slide, open_time, close_delay } => {
+                Some(Mover::Door(Door {
+                    tile_x,
+                    tile_y,
+                    slide,
+                    open_time,
+                    close_delay,
+                    state: DoorState::Closed,
+                    progress: 0.0,
+                }))
+            },
+            GeometryKind::PushWall{ distance, move_time } => {
+                Some(Mover::PushWall(PushWall {
+                    tile_x,
+                    tile_y,
+                    distance,
+                    move_time,
+                    state: PushWallState::Idle,
+                    progress: 0.0,
+                }))
+            },
+        }
+    }
+
+    pub fn tile(&self) -> (usize, usize) {
+        match self {
+            Mover::Door(door) => (door.tile_x, door.tile_y),
+            Mover::PushWall(push_wall) => (push_wall.tile_x, push_wall.tile_y),
+        }
+    }
+
+    /// offset of the primitives of the tile from their resting place
+    pub fn offset(&self) -> Vec2 {
+        match self {
+            Mover::Door(door) => door.offset(),
+            Mover::PushWall(push_wall) => push_wall.offset(),
+        }
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        match self {
+            Mover::Door(door) => door.is_blocking(),
+            Mover::PushWall(_) => true,
+        }
+    }
+}
+
+impl Map {
+    /// Returns true if the tile blocks the movement of the players: a primitive at eye level stands in it,
+    /// but for the inward cylinders which are the walls of round rooms around it.
+    pub fn is_blocking(&self, tile_x: usize, tile_y: usize, geo_set: &GeometrySet) -> bool {
+        let tile = &self.tiles[tile_y][tile_x];
+        if let Some(idx) = tile.mover {
+            return self.movers[idx].is_blocking();
+        }
+        let is_solid = |primitive: &PrimitiveGeometry| match *primitive {
+            PrimitiveGeometry::Cylinder{ inward: true, .. } => false,
+            ref primitive => primitive.elevation().at_eye_level(),
+        };
+        geo_set.geometries[tile.geo_idx].iter().flatten().any(is_solid)
+            || tile.primitives.iter().any(|&idx| is_solid(&self.primitives[idx]))
+            || self.incoming_push_wall(tile_x, tile_y).is_some()
+    }
+
+    /// checks that a player can be moved to the position: inside the map, on a tile which does not block
+    pub fn check_position(&self, position: Vec2, geo_set: &GeometrySet) -> Result<(), Box<dyn Error>> {
+        if !position.x.is_finite() || !position.y.is_finite() {
+            return Err(format!("invalid position {}, {}", position.x, position.y).into());
+        }
+        if position.x < 0.0 || position.y < 0.0 || position.x >= self.width as f32 || position.y >= self.height as f32 {
+            return Err(format!("position {}, {} is out of the map", position.x, position.y).into());
+        }
+        let (tile_x, tile_y) = (position.x as usize, position.y as usize);
+        if self.is_blocking(tile_x, tile_y, geo_set) {
+            return Err(format!("tile {}:{} is blocking", tile_y, tile_x).into());
+        }
+        Ok(())
+    }
+
+    /// The primitives at eye level of the tiles in the given ranges, with the top left corner of their tile.
+    /// The doors and push walls are where they stand, and a primitive of the map comes once however many tiles it overlaps.
+    fn eye_level_primitives<'a>(&'a self, tiles_x: RangeInclusive<usize>, tiles_y: RangeInclusive<usize>, geo_set: &'a GeometrySet)
+        -> Vec<(&'a PrimitiveGeometry, Vec2)>
+    {
+        let mut primitives = vec![];
+        let mut map_primitives: Vec<usize> = vec![];
+        for tile_y in tiles_y {
+            for tile_x in tiles_x.clone() {
+                let tile = &self.tiles[tile_y][tile_x];
+                let origin = Vec2 { x: tile_x as f32, y: tile_y as f32 } + self.tile_offset(tile_x, tile_y);
+                primitives.extend(geo_set.geometries[tile.geo_idx].iter().flatten().map(|primitive| (primitive, origin)));
+                if let Some(push_wall) = self.incoming_push_wall(tile_x, tile_y) {
+                    let origin = Vec2 { x: push_wall.tile_x as f32, y: push_wall.tile_y as f32 } + push_wall.offset();
+                    let geo_idx = self.tiles[push_wall.tile_y][push_wall.tile_x].geo_idx;
+                    primitives.extend(geo_set.geometries[geo_idx].iter().flatten().map(|primitive| (primitive, origin)));
+                }
+                map_primitives.extend(&tile.primitives);
+            }
+        }
+        map_primitives.sort_unstable();
+        map_primitives.dedup();
+        primitives.extend(map_primitives.into_iter().map(|idx| (&self.primitives[idx], Vec2::default())));
+        primitives.retain(|(primitive, _)| primitive.elevation().at_eye_level());
+        primitives
+    }
+
+    /// The center of a disc of the given radius moved out of the primitives at eye level it overlaps, and kept in the map.
+    /// Each primitive pushes the disc in turn, which is repeated while a primitive moves it.
+    pub fn push_out(&self, mut center: Vec2, radius: f32, geo_set: &GeometrySet) -> Vec2 {
+        const MAX_PASSES: usize = 4;
+        let tile_range = |position: f32, size: usize| {
+            ((position - radius).max(0.0) as usize).min(size - 1)..=((position + radius).max(0.0) as usize).min(size - 1)
+        };
+        for _ in 0..MAX_PASSES {
+            let mut pushed = false;
+            let primitives = self.eye_level_primitives(tile_range(center.x, self.width), tile_range(center.y, self.height), geo_set);
+            for (primitive, origin) in primitives {
+                if let Some(push) = primitive.push_out(center, radius, origin) {
+                    center += push;
+                    pushed = true;
+                }
+            }
+            if !pushed {
+                break;
+            }
+        }
+        Vec2 {
+            x: clampf(center.x, radius, self.width as f32 - radius),
+            y: clampf(center.y, radius, self.height as f32 - radius),
+        }
+    }
+
+    /// offset of the primitives of a tile, which is only non zero for moving tiles
+    pub fn tile_offset(&self, tile_x: usize, tile_y: usize) -> Vec2 {
+        match self.tiles[tile_y][tile_x].mover {
+            Some(idx) => self.movers[idx].offset(),
+            None => Vec2::default(),
+        }
+    }
+
+    fn incoming_mover(&self, tile_x: usize, tile_y: usize) -> Option<usize> {
+        self.movers.iter().position(|mover| {
+            match mover {
+                Mover::PushWall(push_wall) => push_wall.target() == Some((tile_x, tile_y)),
+                _ => false,
+            }
+        })
+    }
+
+    /// the push wall moving into the given tile, if any
+    pub fn incoming_push_wall(&self, tile_x: usize, tile_y: usize) -> Option<&PushWall> {
+        match self.incoming_mover(tile_x, tile_y).map(|idx| &self.movers[idx]) {
+            Some(Mover::PushWall(push_wall)) => Some(push_wall),
+            _ => None,
+        }
+    }
+
+    /// returns true if a push wall can move into the tile.
+    /// `mover_idx` is the push wall itself, which may already target the tile, and `occupied` holds the positions of the players.
+    fn is_free(&self, tile_x: usize, tile_y: usize, geo_set: &GeometrySet, mover_idx: usize, occupied: &[Vec2]) -> bool {
+        if tile_x >= self.width || tile_y >= self.height {
+            return false;
+        }
+        let tile = &self.tiles[tile_y][tile_x];
+        tile.mover.is_none()
+            && geo_set.geometries[tile.geo_idx].is_none()
+            && self.incoming_mover(tile_x, tile_y).is_none_or(|idx| idx == mover_idx)
+            && !occupied.iter().any(|pos| pos.x as usize == tile_x && pos.y as usize == tile_y)
+    }
+
+    /// uses the tile: opens or closes a door, pushes a push wall along the main axis of `dir`.
+    /// `occupied` holds the positions of the players, push walls do not move into them.
+    /// Returns true if something happened.
+    pub fn use_tile(&mut self, tile_x: usize, tile_y: usize, dir: Vec2, geo_set: &GeometrySet, occupied: &[Vec2]) -> bool {
+        if tile_x >= self.width || tile_y >= self.height {
+            return false;
+        }
+        let idx = match self.tiles[tile_y][tile_x].mover {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let (step_x, step_y) = if dir.x.abs() >= dir.y.abs() {
+            (if dir.x < 0.0 { -1 } else { 1 }, 0)
+        } else {
+            (0, if dir.y < 0.0 { -1 } else { 1 })
+        };
+        let target_free = self.is_free(
+            (tile_x as isize + step_x) as usize,
+            (tile_y as isize + step_y) as usize,
+            geo_set,
+            idx,
+            occupied
+        );
+        match &mut self.movers[idx] {
+            Mover::Door(door) => {
+                door.activate();
+                true
+            },
+            Mover::PushWall(push_wall) => target_free && push_wall.push(step_x, step_y),
+        }
+    }
+
+    /// animates doors and push walls. `occupied` holds the positions of the players,
+    /// doors do not close on them and push walls stop before them.
+    pub fn update(&mut self, delta_time: f32, geo_set: &GeometrySet, occupied: &[Vec2]) {
+        for idx in 0..self.movers.len() {
+            let (tile_x, tile_y) = self.movers[idx].tile();
+            let entered = match &mut self.movers[idx] {
+                Mover::Door(door) => {
+                    let occupied = occupied.iter().any(|pos| {
+                        pos.x as usize == tile_x && pos.y as usize == tile_y
+                    });
+                    door.update(delta_time, occupied);
+                    false
+                },
+                Mover::PushWall(push_wall) => push_wall.update(delta_time),
+            };
+            if !entered {
+                continue;
+            }
+            if let Mover::PushWall(push_wall) = &mut self.movers[idx] {
+                let (from_x, from_y) = (push_wall.tile_x, push_wall.tile_y);
+                let (to_x, to_y) = push_wall.target().unwrap();
+                let next = push_wall.step();
+                let from = std::mem::take(&mut self.tiles[from_y][from_x]);
+                let to = std::mem::replace(&mut self.tiles[to_y][to_x], from);
+                self.tiles[from_y][from_x] = to;
+                if let Some((next_x, next_y)) = next {
+                    if !self.is_free(next_x, next_y, geo_set, idx, occupied) {
+                        if let Mover::PushWall(push_wall) = &mut self.movers[idx] {
+                            push_wall.stop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
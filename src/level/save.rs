@@ -0,0 +1,209 @@
+use super::*;
+
+use parser::save;
+
+use self::mover::{
+    DoorState,
+    PushWallState,
+};
+
+impl DoorState {
+    fn to_save(self) -> save::DoorState {
+        match self {
+            DoorState::Closed => save::DoorState::Closed,
+            DoorState::Opening => save::DoorState::Opening,
+            DoorState::Open{ timer } => save::DoorState::Open { timer },
+            DoorState::Closing => save::DoorState::Closing,
+        }
+    }
+
+    fn from_save(state: save::DoorState) -> Self {
+        match state {
+            save::DoorState::Closed => DoorState::Closed,
+            save::DoorState::Opening => DoorState::Opening,
+            save::DoorState::Open{ timer } => DoorState::Open { timer },
+            save::DoorState::Closing => DoorState::Closing,
+        }
+    }
+}
+
+impl PushWallState {
+    fn to_save(self) -> save::PushWallState {
+        match self {
+            PushWallState::Idle => save::PushWallState::Idle,
+            PushWallState::Moving{ step_x, step_y, remaining } => save::PushWallState::Moving { step_x, step_y, remaining },
+            PushWallState::Done => save::PushWallState::Done,
+        }
+    }
+
+    fn from_save(state: save::PushWallState) -> Self {
+        match state {
+            save::PushWallState::Idle => PushWallState::Idle,
+            save::PushWallState::Moving{ step_x, step_y, remaining } => PushWallState::Moving { step_x, step_y, remaining },
+            save::PushWallState::Done => PushWallState::Done,
+        }
+    }
+}
+
+/// the tiles of the saved doors and push walls
+fn saved_mover_tiles(movers: &[save::Mover]) -> Vec<[usize; 2]> {
+    movers.iter().map(|mover| match *mover {
+        save::Mover::Door{ tile, .. } | save::Mover::PushWall{ tile, .. } => tile,
+    }).collect()
+}
+
+/// The number of tiles, up to `remaining`, a push wall moving from `tile` by `step` can enter in the saved tiles:
+/// tiles inside the map, without geometry nor door or push wall.
+fn free_path(tiles: &[Vec<[usize; 2]>], mover_tiles: &[[usize; 2]], geo_set: &GeometrySet, tile: [usize; 2], step: (isize, isize), remaining: usize) -> usize {
+    (1..=remaining).take_while(|&distance| {
+        let x = tile[0] as isize + step.0 * distance as isize;
+        let y = tile[1] as isize + step.1 * distance as isize;
+        if x < 0 || y < 0 || y as usize >= tiles.len() || x as usize >= tiles[y as usize].len() {
+            return false;
+        }
+        let (x, y) = (x as usize, y as usize);
+        geo_set.geometries[tiles[y][x][0]].is_none() && !mover_tiles.contains(&[x, y])
+    }).count()
+}
+
+impl Map {
+    fn check_tile(&self, tile: [usize; 2]) -> Result<(), Box<dyn Error>> {
+        if tile[0] >= self.width || tile[1] >= self.height {
+            return Err(format!("tile {}:{} is out of the map", tile[1], tile[0]).into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the saved tiles, doors and push walls of a level match its map.
+    /// A moving push wall must be able to enter its next tile.
+    pub fn check_save(&self, state: &save::LevelSave, geo_set: &GeometrySet, mtl_set: &MaterialSet) -> Result<(), Box<dyn Error>> {
+        if state.tiles.len() != self.height || state.tiles.iter().any(|line| line.len() != self.width) {
+            return Err("the map dimensions do not match the level".into());
+        }
+        for (line, tiles) in state.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                if tile[0] >= geo_set.geometries.len() || tile[1] >= mtl_set.material_properties.len() {
+                    return Err(format!("tile {}:{}: unknown geometry or material", line, col).into());
+                }
+            }
+        }
+        if state.movers.len() != self.movers.len() {
+            return Err("the doors and push walls do not match the level".into());
+        }
+        let mover_tiles = saved_mover_tiles(&state.movers);
+        for (saved, mover) in state.movers.iter().zip(&self.movers) {
+            let progress = match (saved, mover) {
+                (save::Mover::Door{ tile, progress, .. }, Mover::Door(door)) => {
+                    if *tile != [door.tile_x, door.tile_y] {
+                        return Err(format!("no door at tile {}:{}", tile[1], tile[0]).into());
+                    }
+                    *progress
+                },
+                (save::Mover::PushWall{ tile, state: push_wall_state, progress }, Mover::PushWall(_)) => {
+                    self.check_tile(*tile)?;
+                    if let save::PushWallState::Moving{ step_x, step_y, remaining } = *push_wall_state {
+                        if !matches!((step_x, step_y), (-1, 0) | (1, 0) | (0, -1) | (0, 1)) {
+                            return Err(format!("push wall at tile {}:{}: invalid step {}:{}", tile[1], tile[0], step_y, step_x).into());
+                        }
+                        if free_path(&state.tiles, &mover_tiles, geo_set, *tile, (step_x, step_y), remaining.min(1)) == 0 {
+                            return Err(format!("push wall at tile {}:{} can not move", tile[1], tile[0]).into());
+                        }
+                    }
+                    *progress
+                },
+                _ => return Err("the doors and push walls do not match the level".into()),
+            };
+            if !(0.0..=1.0).contains(&progress) {
+                return Err(format!("invalid progress {} of a door or push wall", progress).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Level<'a> {
+    /// the state of the level that differs from its asset files
+    pub fn save_state(&self) -> Result<save::LevelSave, Box<dyn Error>> {
+        let tiles = self.map.tiles.iter().map(|line| {
+            line.iter().map(|tile| [tile.geo_idx, tile.mtl_idx]).collect()
+        }).collect();
+        let movers = self.map.movers.iter().map(|mover| {
+            match mover {
+                Mover::Door(door) => save::Mover::Door {
+                    tile: [door.tile_x, door.tile_y],
+                    state: door.state.to_save(),
+                    progress: door.progress,
+                },
+                Mover::PushWall(push_wall) => save::Mover::PushWall {
+                    tile: [push_wall.tile_x, push_wall.tile_y],
+                    state: push_wall.state.to_save(),
+                    progress: push_wall.progress,
+                },
+            }
+        }).collect();
+        let fired_triggers = self.triggers.iter().enumerate()
+            .filter(|(_, trigger)| trigger.fired)
+            .map(|(idx, _)| idx)
+            .collect();
+        let script = match self.script {
+            Some(ref script) => Some(script.save_this()?),
+            None => None,
+        };
+        Ok(save::LevelSave { tiles, movers, fired_triggers, script })
+    }
+
+    /// restores a state returned by save_state, with the players at the given positions.
+    /// The level is left untouched if the state does not match it.
+    pub fn restore_state(&mut self, state: save::LevelSave, players: &[Vec2]) -> Result<(), Box<dyn Error>> {
+        self.map.check_save(&state, &self.geo_set, &self.mtl_set)?;
+        if let Some(&idx) = state.fired_triggers.iter().find(|&&idx| idx >= self.triggers.len()) {
+            return Err(format!("unknown trigger {}", idx).into());
+        }
+        match (state.script, self.script.as_mut()) {
+            (Some(this), Some(script)) => script.restore_this(this)?,
+            (Some(_), None) => return Err("the level has no script".into()),
+            (None, _) => {},
+        }
+
+        let mover_tiles = saved_mover_tiles(&state.movers);
+        for (tiles, saved_tiles) in self.map.tiles.iter_mut().zip(&state.tiles) {
+            for (tile, saved_tile) in tiles.iter_mut().zip(saved_tiles) {
+                tile.geo_idx = saved_tile[0];
+                tile.mtl_idx = saved_tile[1];
+                tile.mover = None;
+            }
+        }
+        for (saved, mover) in state.movers.into_iter().zip(&mut self.map.movers) {
+            match (saved, mover) {
+                (save::Mover::Door{ state, progress, .. }, Mover::Door(door)) => {
+                    door.state = DoorState::from_save(state);
+                    door.progress = progress;
+                },
+                (save::Mover::PushWall{ tile, state: push_wall_state, progress }, Mover::PushWall(push_wall)) => {
+                    push_wall.tile_x = tile[0];
+                    push_wall.tile_y = tile[1];
+                    // the moves left end before the first tile the wall can not enter, where it would stop anyway
+                    push_wall.state = match PushWallState::from_save(push_wall_state) {
+                        PushWallState::Moving{ step_x, step_y, remaining } => {
+                            let remaining = free_path(&state.tiles, &mover_tiles, &self.geo_set, tile, (step_x, step_y), remaining);
+                            PushWallState::Moving { step_x, step_y, remaining }
+                        },
+                        push_wall_state => push_wall_state,
+                    };
+                    push_wall.progress = progress;
+                },
+                _ => unreachable!(),
+            }
+        }
+        for (idx, mover) in self.map.movers.iter().enumerate() {
+            let (tile_x, tile_y) = mover.tile();
+            self.map.tiles[tile_y][tile_x].mover = Some(idx);
+        }
+        for (idx, trigger) in self.triggers.iter_mut().enumerate() {
+            trigger.fired = state.fired_triggers.contains(&idx);
+        }
+        // the enter triggers of the tiles of the players do not fire again
+        self.player_tiles = players.iter().map(|pos| (pos.x as usize, pos.y as usize)).collect();
+        Ok(())
+    }
+}
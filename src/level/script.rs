@@ -0,0 +1,327 @@
+use super::*;
+
+use super::geometry::GeometryKind;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use rhai::{
+    Engine,
+    EvalAltResult,
+    Dynamic,
+    Scope,
+    CallFnOptions,
+    FuncArgs,
+    AST,
+    FLOAT,
+    INT,
+    module_resolvers::DummyModuleResolver,
+};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// The part of the level a script can see and modify.
+/// It is synchronized with the level before and after every call to the script.
+struct ScriptState {
+    width: usize,
+    height: usize,
+    /// (geo_idx, mtl_idx) of every tile, line by line
+    tiles: Vec<(usize, usize)>,
+    /// tiles holding a door or a push wall, which scripts can not modify
+    locked: Vec<bool>,
+    modified: bool,
+    geo_names: Vec<String>,
+    /// geometries without primitives
+    geo_empty: Vec<bool>,
+    /// doors and push walls, which can not be placed by scripts
+    geo_animated: Vec<bool>,
+    mtl_names: Vec<String>,
+    mtl_portals: Vec<bool>,
+    events: Vec<LevelEvent>,
+}
+
+impl ScriptState {
+    fn tile_idx(&self, x: INT, y: INT) -> ScriptResult<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return Err(format!("tile {}:{} is out of the map", y, x).into());
+        }
+        Ok(y as usize * self.width + x as usize)
+    }
+
+    fn set_geometry(&mut self, x: INT, y: INT, name: &str) -> ScriptResult<()> {
+        let idx = self.tile_idx(x, y)?;
+        let geo_idx = match self.geo_names.iter().position(|geo_name| geo_name == name) {
+            Some(geo_idx) => geo_idx,
+            None => return Err(format!("unknown geometry '{}'", name).into()),
+        };
+        if self.locked[idx] || self.geo_animated[geo_idx] {
+            return Err(format!("tile {}:{}: doors and push walls can not be modified by scripts", y, x).into());
+        }
+        if self.geo_empty[geo_idx] && self.mtl_portals[self.tiles[idx].1] {
+            return Err(format!("tile {}:{}: a portal must be associated to a non-empty geometric tile", y, x).into());
+        }
+        self.tiles[idx].0 = geo_idx;
+        self.modified = true;
+        Ok(())
+    }
+
+    fn set_material(&mut self, x: INT, y: INT, name: &str) -> ScriptResult<()> {
+        let idx = self.tile_idx(x, y)?;
+        let mtl_idx = match self.mtl_names.iter().position(|mtl_name| mtl_name == name) {
+            Some(mtl_idx) => mtl_idx,
+            None => return Err(format!("unknown material '{}'", name).into()),
+        };
+        if self.mtl_portals[mtl_idx] && self.geo_empty[self.tiles[idx].0] {
+            return Err(format!("tile {}:{}: a portal must be associated to a non-empty geometric tile", y, x).into());
+        }
+        self.tiles[idx].1 = mtl_idx;
+        self.modified = true;
+        Ok(())
+    }
+}
+
+/// Rhai script of a level, stored in the `script.rhai` file of the level directory.
+///
+/// The script may define the following functions, called by the level:
+/// `on_load()`, `on_update(delta_time)`, `on_enter(x, y)`, `on_use(x, y)` and `on_shoot(x, y)`.
+/// `this` is an object map kept between the calls, where scripts can store their own state.
+pub struct LevelScript {
+    level_name: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    state: Rc<RefCell<ScriptState>>,
+}
+
+impl LevelScript {
+    const FILE_NAME: &'static str = "script.rhai";
+    // sandbox limits
+    const MAX_OPERATIONS: u64 = 1_000_000;
+    const MAX_CALL_LEVELS: usize = 32;
+    const MAX_EXPR_DEPTH: usize = 64;
+    const MAX_STRING_SIZE: usize = 4096;
+    const MAX_ARRAY_SIZE: usize = 4096;
+
+    fn create_engine(state: &Rc<RefCell<ScriptState>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(Self::MAX_OPERATIONS)
+            .set_max_call_levels(Self::MAX_CALL_LEVELS)
+            .set_max_expr_depths(Self::MAX_EXPR_DEPTH, Self::MAX_EXPR_DEPTH)
+            .set_max_string_size(Self::MAX_STRING_SIZE)
+            .set_max_array_size(Self::MAX_ARRAY_SIZE)
+            .set_max_map_size(Self::MAX_ARRAY_SIZE)
+            .set_module_resolver(DummyModuleResolver::new())
+            .disable_symbol("eval");
+
+        let s = Rc::clone(state);
+        engine.on_print(move |text| s.borrow_mut().events.push(LevelEvent::Message(text.to_string())));
+        let s = Rc::clone(state);
+        engine.register_fn("message", move |text: &str| {
+            s.borrow_mut().events.push(LevelEvent::Message(text.to_string()));
+        });
+
+        let s = Rc::clone(state);
+        engine.register_fn("map_width", move || s.borrow().width as INT);
+        let s = Rc::clone(state);
+        engine.register_fn("map_height", move || s.borrow().height as INT);
+
+        let s = Rc::clone(state);
+        engine.register_fn("geometry", move |x: INT, y: INT| -> ScriptResult<String> {
+            let state = s.borrow();
+            let idx = state.tile_idx(x, y)?;
+            Ok(state.geo_names[state.tiles[idx].0].clone())
+        });
+        let s = Rc::clone(state);
+        engine.register_fn("set_geometry", move |x: INT, y: INT, name: &str| -> ScriptResult<()> {
+            s.borrow_mut().set_geometry(x, y, name)
+        });
+        let s = Rc::clone(state);
+        engine.register_fn("material", move |x: INT, y: INT| -> ScriptResult<String> {
+            let state = s.borrow();
+            let idx = state.tile_idx(x, y)?;
+            Ok(state.mtl_names[state.tiles[idx].1].clone())
+        });
+        let s = Rc::clone(state);
+        engine.register_fn("set_material", move |x: INT, y: INT, name: &str| -> ScriptResult<()> {
+            s.borrow_mut().set_material(x, y, name)
+        });
+
+        let s = Rc::clone(state);
+        engine.register_fn("open_door", move |x: INT, y: INT| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+            state.tile_idx(x, y)?;
+            state.events.push(LevelEvent::OpenDoor { tile_x: x as usize, tile_y: y as usize });
+            Ok(())
+        });
+
+        // positions can be given as integers or floats
+        let teleport = |state: &Rc<RefCell<ScriptState>>| {
+            let s = Rc::clone(state);
+            move |x: FLOAT, y: FLOAT| -> ScriptResult<()> {
+                let mut state = s.borrow_mut();
+                if !x.is_finite() || !y.is_finite() {
+                    return Err(format!("invalid position {}, {}", x, y).into());
+                }
+                state.tile_idx(x as INT, y as INT)?;
+                state.events.push(LevelEvent::Teleport { x: x as f32, y: y as f32 });
+                Ok(())
+            }
+        };
+        let teleport_float = teleport(state);
+        let teleport_int = teleport(state);
+        engine.register_fn("teleport", teleport_float);
+        engine.register_fn("teleport", move |x: INT, y: INT| teleport_int(x as FLOAT, y as FLOAT));
+        engine
+    }
+
+    /// describes the error with the level name and the line where it happened
+    fn describe_error(&self, err: &EvalAltResult) -> String {
+        // the position of an error inside a function is the one of the innermost call
+        let mut err = err;
+        while let EvalAltResult::ErrorInFunctionCall(_, _, ref inner, _) = *err {
+            err = inner;
+        }
+        let position = err.position();
+        match position.line() {
+            Some(line) => format!("level '{}', {} line {}: {}", self.level_name, Self::FILE_NAME, line, err),
+            None => format!("level '{}', {}: {}", self.level_name, Self::FILE_NAME, err),
+        }
+    }
+
+    /// loads the optional script of the level, and runs its top level statements.
+    /// Errors are published as `LevelEvent::ScriptError`, the level then runs without its script.
+    pub fn load<P: AsRef<Path>>(level_name: P, map: &Map, geo_set: &GeometrySet, mtl_set: &MaterialSet, events: &mut EventBus<LevelEvent>) -> Option<Self> {
+        let mut path = PathBuf::from("assets");
+        path.push("maps");
+        path.push(level_name.as_ref());
+        path.push(Self::FILE_NAME);
+        let level_name = level_name.as_ref().to_string_lossy().into_owned();
+
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                events.publish(LevelEvent::ScriptError(format!("level '{}', {}: {}", level_name, Self::FILE_NAME, err)));
+                return None;
+            },
+        };
+
+        let state = Rc::new(RefCell::new(ScriptState {
+            width: map.width,
+            height: map.height,
+            tiles: Vec::with_capacity(map.width * map.height),
+            locked: Vec::with_capacity(map.width * map.height),
+            modified: false,
+            geo_names: geo_set.names.clone(),
+            geo_empty: geo_set.geometries.iter().map(|geometry| geometry.is_none()).collect(),
+            geo_animated: geo_set.kinds.iter().map(|&kind| kind != GeometryKind::Static).collect(),
+            mtl_names: mtl_set.material_names.clone(),
+            mtl_portals: mtl_set.material_properties.iter().map(|properties| properties.portal.is_some()).collect(),
+            events: vec![],
+        }));
+        let engine = Self::create_engine(&state);
+        let mut script = Self {
+            level_name,
+            ast: AST::empty(),
+            engine,
+            scope: Scope::new(),
+            this: Dynamic::from_map(Default::default()),
+            state,
+        };
+        script.ast = match script.engine.compile(&source) {
+            Ok(ast) => ast,
+            Err(err) => {
+                let err: EvalAltResult = err.into();
+                events.publish(LevelEvent::ScriptError(script.describe_error(&err)));
+                return None;
+            },
+        };
+        Some(script)
+    }
+
+    fn has_function(&self, name: &str, n_params: usize) -> bool {
+        self.ast.iter_functions().any(|function| function.name == name && function.params.len() == n_params)
+    }
+
+    fn sync_from_map(&self, map: &Map) {
+        let mut state = self.state.borrow_mut();
+        state.tiles.clear();
+        state.locked.clear();
+        for tile in map.tiles.iter().flatten() {
+            state.tiles.push((tile.geo_idx, tile.mtl_idx));
+            state.locked.push(tile.mover.is_some());
+        }
+        state.modified = false;
+    }
+
+    fn sync_to_map(&self, map: &mut Map, events: &mut EventBus<LevelEvent>) {
+        let mut state = self.state.borrow_mut();
+        if state.modified {
+            for (tile, &(geo_idx, mtl_idx)) in map.tiles.iter_mut().flatten().zip(state.tiles.iter()) {
+                tile.geo_idx = geo_idx;
+                tile.mtl_idx = mtl_idx;
+            }
+        }
+        for event in state.events.drain(..) {
+            events.publish(event);
+        }
+    }
+
+    fn call(&mut self, map: &mut Map, events: &mut EventBus<LevelEvent>, eval_ast: bool, name: &str, args: impl FuncArgs) {
+        self.sync_from_map(map);
+        let options = CallFnOptions::new()
+            .eval_ast(eval_ast)
+            .bind_this_ptr(&mut self.this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args);
+        if let Err(err) = result {
+            events.publish(LevelEvent::ScriptError(self.describe_error(&err)));
+        }
+        self.sync_to_map(map, events);
+    }
+
+    /// the `this` object map of the script, written in the saves
+    pub fn save_this(&self) -> Result<serde_json::Value, Box<dyn Error>> {
+        serde_json::to_value(&self.this).map_err(|err| format!("script state: {}", err).into())
+    }
+
+    /// restores the `this` object map of a save, the script is left untouched if it is not an object map
+    pub fn restore_this(&mut self, value: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let this: Dynamic = serde_json::from_value(value).map_err(|err| format!("script state: {}", err))?;
+        if !this.is_map() {
+            return Err("script state: not an object map".into());
+        }
+        self.this = this;
+        Ok(())
+    }
+
+    /// runs the top level statements of the script, then its `on_load` function
+    pub fn start(&mut self, map: &mut Map, events: &mut EventBus<LevelEvent>) {
+        self.sync_from_map(map);
+        if let Err(err) = self.engine.run_ast_with_scope(&mut self.scope, &self.ast) {
+            events.publish(LevelEvent::ScriptError(self.describe_error(&err)));
+        }
+        self.sync_to_map(map, events);
+        if self.has_function("on_load", 0) {
+            self.call(map, events, false, "on_load", ());
+        }
+    }
+
+    pub fn on_update(&mut self, map: &mut Map, events: &mut EventBus<LevelEvent>, delta_time: f32) {
+        if self.has_function("on_update", 1) {
+            self.call(map, events, false, "on_update", (delta_time as FLOAT,));
+        }
+    }
+
+    /// calls `on_enter`, `on_use` or `on_shoot` depending on the condition
+    pub fn on_trigger(&mut self, map: &mut Map, events: &mut EventBus<LevelEvent>, condition: TriggerCondition, tile_x: usize, tile_y: usize) {
+        let name = match condition {
+            TriggerCondition::Enter => "on_enter",
+            TriggerCondition::Use => "on_use",
+            TriggerCondition::Shoot => "on_shoot",
+        };
+        if self.has_function(name, 2) {
+            self.call(map, events, false, name, (tile_x as INT, tile_y as INT));
+        }
+    }
+}
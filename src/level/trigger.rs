@@ -0,0 +1,124 @@
+use super::*;
+
+use parser::trigger::{
+    TriggerMap,
+    TriggerEvent,
+};
+
+pub use parser::trigger::TriggerCondition;
+
+use self::material::Portal;
+
+/// events published by the level and its triggers
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelEvent {
+    OpenDoor {
+        tile_x: usize,
+        tile_y: usize,
+    },
+    SpawnEnemies {
+        kind: String,
+        count: usize,
+        tile_x: usize,
+        tile_y: usize,
+    },
+    Message(String),
+    /// activates the portal of the material of the tile
+    ActivatePortal {
+        tile_x: usize,
+        tile_y: usize,
+    },
+    /// a player went through a portal
+    EnterPortal(Portal),
+    /// moves the player to the given position
+    Teleport {
+        x: f32,
+        y: f32,
+    },
+    /// an error raised by the script of the level, which keeps running
+    ScriptError(String),
+}
+
+/// an enemy spawned by a trigger, only shown on the automap until the enemies are implemented
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnemyMarker {
+    pub kind: String,
+    pub position: Vec2,
+}
+
+#[derive(Debug)]
+pub struct Trigger {
+    pub tile_x: usize,
+    pub tile_y: usize,
+    pub condition: TriggerCondition,
+    /// a trigger fired once is disabled
+    pub once: bool,
+    pub fired: bool,
+    pub events: Vec<LevelEvent>,
+}
+
+impl Trigger {
+    fn check_tile(map: &Map, tile: [usize; 2]) -> Result<(usize, usize), Box<dyn Error>> {
+        if tile[0] >= map.width || tile[1] >= map.height {
+            return Err(format!("tile {}:{} is out of the map", tile[1], tile[0]).into());
+        }
+        Ok((tile[0], tile[1]))
+    }
+
+    fn new(raw_trigger: parser::trigger::Trigger, map: &Map, geo_set: &GeometrySet, mtl_set: &MaterialSet) -> Result<Self, Box<dyn Error>> {
+        let (tile_x, tile_y) = Self::check_tile(map, raw_trigger.tile)?;
+        let mut events = Vec::with_capacity(raw_trigger.events.len());
+        for raw_event in raw_trigger.events {
+            let event = match raw_event {
+                TriggerEvent::OpenDoor(tile) => {
+                    let (tile_x, tile_y) = Self::check_tile(map, tile)?;
+                    match map.tiles[tile_y][tile_x].mover.map(|idx| &map.movers[idx]) {
+                        Some(Mover::Door(_)) => {},
+                        _ => return Err(format!("open_door: no door at tile {}:{}", tile_y, tile_x).into()),
+                    }
+                    LevelEvent::OpenDoor { tile_x, tile_y }
+                },
+                TriggerEvent::SpawnEnemies{ kind, count, tile } => {
+                    let (tile_x, tile_y) = Self::check_tile(map, tile)?;
+                    if map.is_blocking(tile_x, tile_y, geo_set) {
+                        return Err(format!("spawn_enemies: tile {}:{} is blocking", tile_y, tile_x).into());
+                    }
+                    LevelEvent::SpawnEnemies { kind, count, tile_x, tile_y }
+                },
+                TriggerEvent::Message(message) => LevelEvent::Message(message),
+                TriggerEvent::ActivatePortal(tile) => {
+                    let (tile_x, tile_y) = Self::check_tile(map, tile)?;
+                    if mtl_set.material_properties[map.tiles[tile_y][tile_x].mtl_idx].portal.is_none() {
+                        return Err(format!("activate_portal: no portal at tile {}:{}", tile_y, tile_x).into());
+                    }
+                    LevelEvent::ActivatePortal { tile_x, tile_y }
+                },
+            };
+            events.push(event);
+        }
+        Ok(Self {
+            tile_x,
+            tile_y,
+            condition: raw_trigger.on,
+            once: raw_trigger.once,
+            fired: false,
+            events,
+        })
+    }
+
+    /// loads the optional trigger map of the level
+    pub fn load<P: AsRef<Path>>(dirs: &[P], map: &Map, geo_set: &GeometrySet, mtl_set: &MaterialSet) -> Result<Vec<Self>, Box<dyn Error>> {
+        let reader = match read_optional_assets_file(dirs, "trigger_map")? {
+            Some(reader) => reader,
+            None => return Ok(vec![]),
+        };
+        let raw_trigger_map: TriggerMap = serde_json::from_reader(reader)?;
+        let mut triggers = Vec::with_capacity(raw_trigger_map.triggers.len());
+        for (idx, raw_trigger) in raw_trigger_map.triggers.into_iter().enumerate() {
+            let trigger = Self::new(raw_trigger, map, geo_set, mtl_set)
+                .map_err(|err| format!("trigger {}: {}", idx, err))?;
+            triggers.push(trigger);
+        }
+        Ok(triggers)
+    }
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+use std::time::SystemTime;
+
+/// The files a level is built from, polled for changes.
+/// The files of the level directory are listed again on each poll, so that added and removed files count as changes.
+pub struct FileWatcher {
+    level_dir: PathBuf,
+    /// sets and textures used by the level
+    asset_files: Vec<PathBuf>,
+    /// modification times of the files at the last poll, none for the missing files
+    times: Vec<(PathBuf, Option<SystemTime>)>,
+    /// seconds since the last poll
+    elapsed: f32,
+}
+
+impl FileWatcher {
+    /// seconds between two polls
+    const POLL_INTERVAL: f32 = 0.5;
+
+    pub fn new(level_dir: PathBuf, asset_files: Vec<PathBuf>) -> Self {
+        let mut watcher = Self {
+            level_dir,
+            asset_files,
+            times: vec![],
+            elapsed: 0.0,
+        };
+        watcher.times = watcher.modification_times();
+        watcher
+    }
+
+    fn modification_times(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut files: Vec<PathBuf> = match std::fs::read_dir(&self.level_dir) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+            Err(_) => vec![],
+        };
+        files.sort();
+        files.extend(self.asset_files.iter().cloned());
+        files.into_iter().map(|path| {
+            let time = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+            (path, time)
+        }).collect()
+    }
+
+    /// Returns true if a file changed since the last call, polling the files every `POLL_INTERVAL` seconds.
+    /// A change is reported once.
+    pub fn poll(&mut self, delta_time: f32) -> bool {
+        self.elapsed += delta_time;
+        if self.elapsed < Self::POLL_INTERVAL {
+            return false;
+        }
+        self.elapsed = 0.0;
+        let times = self.modification_times();
+        if times == self.times {
+            return false;
+        }
+        self.times = times;
+        true
+    }
+
+    /// takes the files as they are now as unchanged, after the game wrote them itself
+    pub fn acknowledge(&mut self) {
+        self.times = self.modification_times();
+    }
+}
@@ -3,8 +3,13 @@ pub extern crate bitflags;
 pub extern crate clap;
 pub extern crate serde;
 pub extern crate serde_json;
+pub extern crate rhai;
+pub extern crate roxmltree;
 
-pub use clap::App;
+pub use clap::{
+    App,
+    ArgMatches,
+};
 
 pub use std::time::Duration;
 
@@ -18,6 +23,9 @@ pub use math_2d::*;
 
 mod parser;
 
+mod events;
+pub use events::*;
+
 mod level;
 pub use level::*;
 
@@ -26,4 +34,28 @@ pub use player::*;
 
 pub mod timer;
 
+mod text;
+pub use text::*;
+
+mod cvars;
+pub use cvars::*;
+
+mod hud;
+pub use hud::*;
+
+mod console;
+pub use console::*;
+mod map_render;
+pub use map_render::*;
+mod automap;
+pub use automap::*;
+mod edit_mode;
+pub use edit_mode::*;
+mod menu;
+pub use menu::*;
+mod save;
+pub use save::*;
+mod game_state;
+pub use game_state::*;
+
 mod skybox;
\ No newline at end of file
@@ -1,10 +1,211 @@
 extern crate wolf3d_rs;
 use wolf3d_rs::*;
+use wolf3d_rs::sdl2::{
+    image::SaveSurface,
+    pixels::PixelFormatEnum,
+    render::{
+        Canvas,
+        TextureCreator,
+    },
+    surface::Surface,
+    video::{
+        Window,
+        WindowContext,
+    },
+};
+
+/// what the level asks the game to do with the player
+#[derive(Default)]
+struct LevelRequests {
+    messages: Vec<String>,
+    portal: Option<Portal>,
+    teleport: Option<Vec2>,
+}
+
+/// prints the errors of the level and records its requests
+fn subscribe_level_events(level: &mut Level, requests: &Rc<RefCell<LevelRequests>>) {
+    let requests = Rc::clone(requests);
+    level.events.subscribe(Box::new(move |event: &LevelEvent| {
+        match event {
+            LevelEvent::Message(message) => requests.borrow_mut().messages.push(message.clone()),
+            LevelEvent::ScriptError(err) => eprintln!("script error: {}", err),
+            LevelEvent::EnterPortal(portal) => requests.borrow_mut().portal = Some(portal.clone()),
+            LevelEvent::Teleport{ x, y } => requests.borrow_mut().teleport = Some(Vec2 { x: *x, y: *y }),
+            _ => {},
+        }
+    }));
+}
+
+/// loads a level with at least one spawn
+fn load_level<'a>(level_name: &str, texture_creator: &'a TextureCreator<WindowContext>, requests: &Rc<RefCell<LevelRequests>>) -> Result<Level<'a>, Box<dyn Error>> {
+    let mut level = Level::new(level_name, texture_creator)?;
+    if level.spawns.is_empty() {
+        return Err("no spawn available".into());
+    }
+    subscribe_level_events(&mut level, requests);
+    Ok(level)
+}
+
+/// loads the level of a save, and restores the level and the player
+fn load_game<'a>(slot: &str, texture_creator: &'a TextureCreator<WindowContext>, requests: &Rc<RefCell<LevelRequests>>, player: &mut Player) -> Result<Level<'a>, Box<dyn Error>> {
+    let save = SaveGame::read(slot)?;
+    let mut level = load_level(&save.level_name, texture_creator, requests)?;
+    save.restore(&mut level, player)?;
+    Ok(level)
+}
+
+/// saves the content of the canvas in the `captures/` directory, and returns the path of the file
+fn save_screenshot(canvas: &Canvas<Window>) -> Result<String, Box<dyn Error>> {
+    let (width, height) = canvas.output_size()?;
+    let format = PixelFormatEnum::ABGR8888;
+    let mut pixels = canvas.read_pixels(None, format)?;
+    let surface = Surface::from_data(&mut pixels, width, height, width * format.byte_size_per_pixel() as u32, format)?;
+    std::fs::create_dir_all("captures")?;
+    let since_epoch = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    let path = format!("captures/screenshot_{}.png", since_epoch.as_millis());
+    surface.save(&path)?;
+    Ok(path)
+}
+
+/// `render` subcommand: writes a picture of a level, without opening a window
+fn render_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let level_name = matches.value_of("level_name").unwrap();
+    let tile_size: u32 = match matches.value_of("tile_size").unwrap().parse() {
+        Ok(tile_size) if tile_size > 0 => tile_size,
+        _ => return Err("the tile size must be a positive integer".into()),
+    };
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => format!("{}.png", level_name),
+    };
+    let level_map = LevelMap::load(level_name)?;
+    MapRender::render(&level_map, tile_size)?.save(&output)?;
+    println!("{} written", output);
+    Ok(())
+}
+
+fn check_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let report = CheckReport::run(matches.value_of("level_name").unwrap());
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.to_text());
+    }
+    if report.error_count() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn convert_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let level_name = matches.value_of("level_name").unwrap();
+    let encoding = MapEncoding::from_name(matches.value_of("to").unwrap())?;
+    convert_level(level_name, encoding)?;
+    println!("maps of level '{}' converted", level_name);
+    Ok(())
+}
+
+fn migrate_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let level_name = matches.value_of("level_name").unwrap();
+    let single_file = !matches.is_present("split");
+    migrate_level(level_name, single_file)?;
+    if single_file {
+        println!("maps of level '{}' moved to level.json", level_name);
+    } else {
+        println!("maps of level '{}' moved to geometry_map.json and material_map.json", level_name);
+    }
+    Ok(())
+}
+
+fn import_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let level_name = matches.value_of("level_name").unwrap();
+    import_tiled(matches.value_of("tiled_file").unwrap(), level_name, matches.is_present("replace"))?;
+    println!("level '{}' imported", level_name);
+    Ok(())
+}
+
+fn import_wolf3d_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let level_names = import_wolf3d(
+        matches.value_of("data_dir").unwrap(),
+        matches.value_of("extension").unwrap(),
+        matches.value_of("prefix").unwrap(),
+        matches.is_present("replace"),
+    )?;
+    println!("{} levels imported: {}", level_names.len(), level_names.join(", "));
+    Ok(())
+}
+
+fn generate_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let seed: u64 = match matches.value_of("seed") {
+        Some(seed) => seed.parse().map_err(|_| "the seed must be a positive integer")?,
+        None => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs(),
+    };
+    let size = |name: &str| -> Result<usize, Box<dyn Error>> {
+        matches.value_of(name).unwrap().parse().map_err(|_| format!("the {} must be a positive integer", name).into())
+    };
+    let level_names = generate_levels(
+        matches.value_of("level_name").unwrap(),
+        GeneratorStyle::from_name(matches.value_of("style").unwrap())?,
+        seed,
+        size("width")?,
+        size("height")?,
+        size("count")?,
+        matches.is_present("replace"),
+    )?;
+    println!("{} levels generated with seed {}: {}", level_names.len(), seed, level_names.join(", "));
+    Ok(())
+}
+
+fn autotile_command(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let threshold: f32 = match matches.value_of("threshold").unwrap().parse() {
+        Ok(threshold) if (0.0..=1.0).contains(&threshold) => threshold,
+        _ => return Err("the threshold must be a number between 0 and 1".into()),
+    };
+    let json = autotile_geometry_map(
+        matches.value_of("input").unwrap(),
+        matches.value_of("geometry_set").unwrap(),
+        matches.value_of("spawn_symbol_set").unwrap(),
+        threshold,
+        MapEncoding::from_name(matches.value_of("to").unwrap())?,
+    )?;
+    match matches.value_of("output") {
+        Some(output) => {
+            std::fs::write(output, json)?;
+            println!("{} written", output);
+        },
+        None => print!("{}", json),
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
     
     let yml = clap::load_yaml!("clap.yml");
     let matches = App::from_yaml(yml).get_matches();
+    if let Some(matches) = matches.subcommand_matches("render") {
+        return render_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("check") {
+        return check_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        return convert_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("migrate") {
+        return migrate_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("import") {
+        return import_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("import-wolf3d") {
+        return import_wolf3d_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("generate") {
+        return generate_command(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("autotile") {
+        return autotile_command(matches);
+    }
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -17,7 +218,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     // let width = value_t!(matches.value_of("width"), u32).unwrap_or(1024);
     // let height = value_t!(matches.value_of("height"), u32).unwrap_or(768);
     // let fullscreen = matches.is_present("fullscreen");
-    let level_name = matches.value_of("level_name").unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
     canvas.set_draw_color(Color::RGB(70, 75, 90));
@@ -25,41 +225,283 @@ fn main() -> Result<(), Box<dyn Error>> {
     canvas.present();
 
     let texture_creator = canvas.texture_creator();
+    let ttf_context = sdl2::ttf::init()?;
+    let window_height = canvas.window().drawable_size().1;
+    let mut hud = Hud::new(&ttf_context, "DejaVuSans", window_height)?;
+    let mut console_font = Console::create_font(&ttf_context, "DejaVuSans", window_height)?;
+    let mut menu_font = Menu::create_font(&ttf_context, "DejaVuSans", window_height)?;
 
-    let level = Level::new(level_name, &texture_creator)?;
-    if level.spawns.len() == 0 {
-        return Err("no spawn available".into());
-    }
+    let requests = Rc::new(RefCell::new(LevelRequests::default()));
     let game_loop = Cell::new(true);
+    let pause = Cell::new(false);
+    let quicksave = Cell::new(false);
+    let quickload = Cell::new(false);
     let mut control_handler = ControlHandler::new();
     
-    let mut player = Player::new(level.spawns[0].coordinates, Vec2 { x: 0.0, y: 1.0 }, &mut control_handler)?;
-    let fov: f32 = 66.0;
+    let mut player = Player::new(Vec2::default(), Vec2 { x: 0.0, y: 1.0 }, &mut control_handler)?;
+    let mut console = Console::new(&mut control_handler)?;
+    let mut automap = Automap::new(&mut control_handler)?;
+    let mut edit_mode = EditMode::new(&mut control_handler)?;
+    let menu_input = MenuInput::new(&mut control_handler)?;
+    let fov = CVar::with_bounds(66.0f32, 30.0, 150.0);
+
+    let mut cvars = CVarRegistry::new();
+    cvars.register("fov", "field of view, in degrees", &fov)?;
+    player.register_cvars(&mut cvars)?;
+    hud.register_cvars(&mut cvars)?;
+    automap.register_cvars(&mut cvars)?;
+    let text_input = video_subsystem.text_input();
+    text_input.stop();
 
-    sdl_context.mouse().set_relative_mouse_mode(true);
     timer::init_timer(sdl_context.timer()?);
 
-    let event_quit = Event::KeyDown {
+    let event_pause = Event::KeyDown {
         timestamp: 0, window_id: 0, keycode: None, scancode: Some(Scancode::Escape), keymod: Mod::NOMOD, repeat: false
     };
-    let quit_action = Box::new(|_: Event| {
-        game_loop.set(false);
-        println!("Escape key pressed: Quit!");
-    });
-    let _quit_handle = control_handler
-        .add_control(ControlManagerType::Game, event_quit, quit_action)
+    let pause_action = Box::new(|_: Event| { pause.set(true); });
+    let _pause_handle = control_handler
+        .add_control(ControlManagerType::Game, event_pause, pause_action)
+        .unwrap();
+    let event_quicksave = Event::KeyDown {
+        timestamp: 0, window_id: 0, keycode: None, scancode: Some(Scancode::F5), keymod: Mod::NOMOD, repeat: false
+    };
+    let quicksave_action = Box::new(|_: Event| { quicksave.set(true); });
+    let _quicksave_handle = control_handler
+        .add_control(ControlManagerType::Game, event_quicksave, quicksave_action)
+        .unwrap();
+    let event_quickload = Event::KeyDown {
+        timestamp: 0, window_id: 0, keycode: None, scancode: Some(Scancode::F9), keymod: Mod::NOMOD, repeat: false
+    };
+    let quickload_action = Box::new(|_: Event| { quickload.set(true); });
+    let _quickload_handle = control_handler
+        .add_control(ControlManagerType::Game, event_quickload, quickload_action)
         .unwrap();
 
+    // the level given on the command line is played without going through the menus
+    let mut level = None;
+    let mut state = GameState::Title;
+    if let Some(level_name) = matches.value_of("level_name") {
+        let new_level = load_level(level_name, &texture_creator, &requests)?;
+        player.respawn(new_level.spawns[0].coordinates);
+        level = Some(new_level);
+        state = GameState::Playing;
+    }
+    let mut menu = state.menu();
+    control_handler.set_active_manager(state.control_manager());
+    sdl_context.mouse().set_relative_mouse_mode(state == GameState::Playing);
+
     let mut event_pump = sdl_context.event_pump().unwrap();
     while game_loop.get() {
         timer::update();
         canvas.set_draw_color(Color::RGB(70, 75, 90));
         canvas.clear();
-        level.draw(&mut canvas, player.position, player.direction, fov)?;
+        if let (true, Some(level)) = (state.shows_level(), &mut level) {
+            level.draw(&mut canvas, player.position, player.direction, fov.get())?;
+        }
         control_handler.call_loop(&mut event_pump);
-        player.update(&level.map, &level.geo_set);
+        let mut next_state = None;
+
+        let mut take_screenshot = false;
+        let was_open = console.is_open;
+        for request in console.update(&mut control_handler, &text_input, &cvars) {
+            match request {
+                ConsoleRequest::LoadLevel(level_name) => {
+                    match load_level(&level_name, &texture_creator, &requests) {
+                        Ok(new_level) => {
+                            player.position = new_level.spawns[0].coordinates;
+                            level = Some(new_level);
+                        },
+                        Err(err) => console.print(&format!("cannot load level '{}': {}", level_name, err)),
+                    }
+                },
+                ConsoleRequest::SaveGame(slot) => {
+                    match &level {
+                        Some(level) => match SaveGame::new(level, &player).and_then(|save| save.write(&slot)) {
+                            Ok(()) => console.print(&format!("game saved in '{}'", slot)),
+                            Err(err) => console.print(&format!("cannot save the game: {}", err)),
+                        },
+                        None => console.print("no game to save"),
+                    }
+                },
+                ConsoleRequest::LoadGame(slot) => {
+                    match load_game(&slot, &texture_creator, &requests, &mut player) {
+                        Ok(new_level) => {
+                            level = Some(new_level);
+                            console.print(&format!("game loaded from '{}'", slot));
+                        },
+                        Err(err) => console.print(&format!("cannot load the game: {}", err)),
+                    }
+                },
+                ConsoleRequest::Teleport(position) => {
+                    match level.as_ref().map(|level| level.map.check_position(position, &level.geo_set)) {
+                        Some(Ok(())) => player.position = position,
+                        Some(Err(err)) => console.print(&format!("cannot teleport: {}", err)),
+                        None => console.print("no level to teleport in"),
+                    }
+                },
+                ConsoleRequest::Screenshot => take_screenshot = true,
+            }
+        }
+        if console.is_open && !was_open {
+            player.release_actions();
+        }
+
+        match (&state, &mut level) {
+            (GameState::Playing, Some(level)) => {
+                if quicksave.replace(false) {
+                    match SaveGame::new(level, &player).and_then(|save| save.write(QUICKSAVE_SLOT)) {
+                        Ok(()) => hud.show_message("Game saved"),
+                        Err(err) => hud.show_message(&format!("Cannot save the game: {}", err)),
+                    }
+                }
+                if quickload.replace(false) {
+                    match load_game(QUICKSAVE_SLOT, &texture_creator, &requests, &mut player) {
+                        Ok(new_level) => {
+                            *level = new_level;
+                            hud.show_message("Game loaded");
+                        },
+                        Err(err) => hud.show_message(&format!("Cannot load the game: {}", err)),
+                    }
+                }
+                // the level is rebuilt from its files when they change, the player keeps its place when it is still on a free tile of the map
+                if level.files_changed() {
+                    let level_name = level.name.clone();
+                    match load_level(&level_name, &texture_creator, &requests) {
+                        Ok(new_level) => {
+                            let position = player.position;
+                            if position.x < 0.0 || position.y < 0.0
+                                || position.x as usize >= new_level.map.width || position.y as usize >= new_level.map.height
+                                || new_level.map.is_blocking(position.x as usize, position.y as usize, &new_level.geo_set) {
+                                player.position = new_level.spawns[0].coordinates;
+                            }
+                            *level = new_level;
+                            hud.show_message(&format!("Level '{}' reloaded", level_name));
+                            for message in edit_mode.reload(level) {
+                                hud.show_message(&message);
+                            }
+                        },
+                        Err(err) => hud.show_message(&format!("Cannot reload the level: {}", err)),
+                    }
+                }
+                for message in edit_mode.update(level, player.position, player.direction) {
+                    hud.show_message(&message);
+                }
+                // the level stands still in edit mode
+                let commands = player.update(&level.map, &level.geo_set);
+                if !edit_mode.is_open() {
+                    for command in commands {
+                        match command {
+                            PlayerCommand::Use{ tile_x, tile_y, dir } => level.use_tile(tile_x, tile_y, dir, &[player.position]),
+                            PlayerCommand::Shoot(ray) => level.shoot(ray),
+                        }
+                    }
+                    level.update(&[player.position]);
+                }
+                for message in requests.borrow_mut().messages.drain(..) {
+                    hud.show_message(&message);
+                }
+                if let Some(position) = requests.borrow_mut().teleport.take() {
+                    player.position = position;
+                }
+                let portal = requests.borrow_mut().portal.take();
+                match portal {
+                    Some(Portal::ToLevel(level_name)) => {
+                        next_state = Some(GameState::LevelComplete { next_level: Some(level_name) });
+                    },
+                    Some(Portal::ToEnd) => next_state = Some(GameState::LevelComplete { next_level: None }),
+                    None => {},
+                }
+                if player.health == 0 {
+                    next_state = Some(GameState::GameOver);
+                }
+                if pause.replace(false) {
+                    next_state = Some(GameState::Paused);
+                }
+            },
+            (_, level) => {
+                let menu = menu.as_mut().unwrap();
+                let mut load = None;
+                let transition = match menu.update(&menu_input.take()) {
+                    Some(choice) => state.choose(menu, choice),
+                    None => None,
+                };
+                match transition {
+                    Some(GameTransition::To(new_state)) => next_state = Some(new_state),
+                    Some(GameTransition::StartLevel(level_name)) => load = Some((level_name, true)),
+                    Some(GameTransition::NextLevel(level_name)) => load = Some((level_name, false)),
+                    Some(GameTransition::RestartLevel) => {
+                        load = level.as_ref().map(|level| (level.name.clone(), true));
+                    },
+                    Some(GameTransition::LoadGame(slot)) => {
+                        match load_game(&slot, &texture_creator, &requests, &mut player) {
+                            Ok(new_level) => {
+                                *level = Some(new_level);
+                                next_state = Some(GameState::Playing);
+                            },
+                            Err(err) => menu.message = Some(format!("cannot load the game: {}", err)),
+                        }
+                    },
+                    Some(GameTransition::Quit) => game_loop.set(false),
+                    None => {},
+                }
+                // the player keeps health and ammunition when going to the next level
+                if let Some((level_name, respawn)) = load {
+                    match load_level(&level_name, &texture_creator, &requests) {
+                        Ok(new_level) => {
+                            if respawn {
+                                player.respawn(new_level.spawns[0].coordinates);
+                            } else {
+                                player.position = new_level.spawns[0].coordinates;
+                            }
+                            *level = Some(new_level);
+                            next_state = Some(GameState::Playing);
+                        },
+                        Err(err) => menu.message = Some(format!("cannot load level '{}': {}", level_name, err)),
+                    }
+                }
+            },
+        }
+
+        if let (true, Some(level)) = (state.shows_level(), &level) {
+            if state == GameState::Playing {
+                automap.update();
+            }
+            automap.draw(&mut canvas, level, player.position, player.direction, fov.get())?;
+            hud.update();
+            let edit_status = edit_mode.status();
+            hud.draw(&mut canvas, &texture_creator, &HudStatus {
+                health: player.health,
+                ammo: player.ammo,
+                level_name: &level.name,
+                edit_status: edit_status.as_deref(),
+            })?;
+        }
+        if let Some(menu) = &menu {
+            menu.draw(&mut canvas, &texture_creator, &mut menu_font)?;
+        }
+        if take_screenshot {
+            // taken before the console is drawn
+            match save_screenshot(&canvas) {
+                Ok(path) => console.print(&format!("screenshot saved as {}", path)),
+                Err(err) => console.print(&format!("cannot save screenshot: {}", err)),
+            }
+        }
+        console.draw(&mut canvas, &texture_creator, &mut console_font)?;
         canvas.present();
+
+        if let Some(new_state) = next_state {
+            state = new_state;
+            menu = state.menu();
+            console.close(&text_input);
+            control_handler.set_active_manager(state.control_manager());
+            sdl_context.mouse().set_relative_mouse_mode(state == GameState::Playing);
+            player.release_actions();
+            menu_input.take();
+            pause.set(false);
+            quicksave.set(false);
+            quickload.set(false);
+        }
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,233 @@
+use sdl2::{
+    image::LoadSurface,
+    pixels::{
+        Color,
+        PixelFormatEnum,
+    },
+    rect::{
+        Point,
+        Rect,
+    },
+    render::{
+        Canvas,
+        RenderTarget,
+    },
+    surface::Surface,
+};
+
+use parser::{
+    Error,
+    assets_path,
+};
+
+use math_2d::{
+    Vec2,
+    PrimitiveGeometry,
+};
+
+use level::{
+    LevelMap,
+    material::{
+        MaterialSet,
+        Shading,
+    },
+};
+
+/// where a map is drawn, and how tiles are projected on it
+pub struct MapView {
+    pub rect: Rect,
+    /// world position drawn at the center of the rect
+    pub focus: Vec2,
+    /// pixels per tile
+    pub scale: f32,
+}
+
+impl MapView {
+    const CIRCLE_SEGMENTS: usize = 16;
+
+    pub fn to_screen(&self, pos: Vec2) -> Point {
+        let center = self.rect.center();
+        Point::new(
+            center.x() + ((pos.x - self.focus.x) * self.scale) as i32,
+            center.y() + ((pos.y - self.focus.y) * self.scale) as i32,
+        )
+    }
+
+    /// the screen rect of a tile
+    pub fn tile_rect(&self, tile_x: usize, tile_y: usize) -> Rect {
+        let corner = self.to_screen(Vec2 { x: tile_x as f32, y: tile_y as f32 });
+        let size = self.scale.ceil().max(1.0) as u32;
+        Rect::new(corner.x(), corner.y(), size, size)
+    }
+
+    /// draws a primitive of the tile whose top left corner is `origin`
+    pub fn draw_primitive<T: RenderTarget>(&self, canvas: &mut Canvas<T>, primitive: &PrimitiveGeometry, origin: Vec2) -> Result<(), Box<dyn Error>> {
+        match *primitive {
+            PrimitiveGeometry::Plane{ p1, p2, .. } => {
+                canvas.draw_line(self.to_screen(origin + p1), self.to_screen(origin + p2))?;
+            },
+            PrimitiveGeometry::Cylinder{ center, radius, .. } => {
+                self.draw_circle(canvas, origin + center, radius)?;
+            },
+            PrimitiveGeometry::Arc{ center, radius, start_angle, end_angle, .. } => {
+                let segments = ((end_angle - start_angle) / 360.0 * Self::CIRCLE_SEGMENTS as f32).ceil().max(1.0) as usize;
+                let points: Vec<Point> = (0..=segments).map(|idx| {
+                    let angle = (start_angle + (end_angle - start_angle) * idx as f32 / segments as f32).to_radians();
+                    self.to_screen(origin + center + Vec2 { x: angle.cos(), y: angle.sin() } * radius)
+                }).collect();
+                canvas.draw_lines(&points[..])?;
+            },
+            PrimitiveGeometry::Polygon{ ref points, .. } => {
+                let mut points: Vec<Point> = points.iter().map(|&point| self.to_screen(origin + point)).collect();
+                points.push(points[0]);
+                canvas.draw_lines(&points[..])?;
+            },
+        }
+        Ok(())
+    }
+
+    pub fn draw_circle<T: RenderTarget>(&self, canvas: &mut Canvas<T>, center: Vec2, radius: f32) -> Result<(), Box<dyn Error>> {
+        let points: Vec<Point> = (0..=Self::CIRCLE_SEGMENTS).map(|idx| {
+            let angle = idx as f32 / Self::CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            self.to_screen(center + Vec2 { x: angle.cos(), y: angle.sin() } * radius)
+        }).collect();
+        canvas.draw_lines(&points[..])?;
+        Ok(())
+    }
+
+    pub fn fill_circle<T: RenderTarget>(&self, canvas: &mut Canvas<T>, center: Vec2, radius: f32) -> Result<(), Box<dyn Error>> {
+        let center = self.to_screen(center);
+        let radius = (radius * self.scale) as i32;
+        for dy in -radius..=radius {
+            let dx = ((radius * radius - dy * dy) as f32).sqrt() as i32;
+            canvas.draw_line(Point::new(center.x() - dx, center.y() + dy), Point::new(center.x() + dx, center.y() + dy))?;
+        }
+        Ok(())
+    }
+}
+
+/// Top-down picture of a level, for the map authors.
+/// Tiles are colored by material, with their primitives, spawns and portals drawn over them.
+pub struct MapRender;
+
+impl MapRender {
+    const BACKGROUND_COLOR: Color = Color { r: 0x18, g: 0x18, b: 0x1c, a: 0xff };
+    const GRID_COLOR: Color = Color { r: 0x40, g: 0x40, b: 0x48, a: 0xff };
+    const WALL_COLOR: Color = Color { r: 0xf0, g: 0xf0, b: 0xf0, a: 0xff };
+    const MOVER_COLOR: Color = Color { r: 0xe0, g: 0xa0, b: 0x40, a: 0xff };
+    const PORTAL_COLOR: Color = Color { r: 0xc0, g: 0x60, b: 0xff, a: 0xff };
+    const SPAWN_COLOR: Color = Color { r: 0x40, g: 0xe0, b: 0x60, a: 0xff };
+    /// brightness of the floors, relative to their material color
+    const FLOOR_BRIGHTNESS: f32 = 0.4;
+
+    fn scale_color(color: Color, factor: f32) -> Color {
+        let scale = |channel: u8| (channel as f32 * factor).min(255.0) as u8;
+        Color::RGB(scale(color.r), scale(color.g), scale(color.b))
+    }
+
+    /// a color derived from the name, for the materials whose texture cannot be loaded
+    fn name_color(name: &str) -> Color {
+        let hash = name.bytes().fold(0x811c_9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193));
+        Color::RGB(0x40 | (hash >> 16) as u8, 0x40 | (hash >> 8) as u8, 0x40 | hash as u8)
+    }
+
+    /// average color of a texture of `assets/material_sets/textures/`
+    fn texture_color(file_name: &str) -> Result<Color, Box<dyn Error>> {
+        let texture = Surface::from_file(assets_path(&["material_sets", "textures"], file_name))?;
+        // converted to a known pixel format by blitting it
+        let mut surface = Surface::new(texture.width(), texture.height(), PixelFormatEnum::RGB888)?;
+        texture.blit(None, &mut surface, None)?;
+        let (width, height, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+        let sum = surface.with_lock(|pixels| {
+            let mut sum = [0u64; 3];
+            for y in 0..height {
+                for x in 0..width {
+                    // RGB888 is stored as a little endian u32: blue, green, red, unused
+                    let pixel = &pixels[y * pitch + x * 4..y * pitch + x * 4 + 3];
+                    sum[0] += pixel[2] as u64;
+                    sum[1] += pixel[1] as u64;
+                    sum[2] += pixel[0] as u64;
+                }
+            }
+            sum
+        });
+        let n = (width * height).max(1) as u64;
+        Ok(Color::RGB((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8))
+    }
+
+    /// the color of each material of the set
+    pub fn material_colors(mtl_set: &MaterialSet) -> Vec<Color> {
+        mtl_set.material_names.iter().enumerate().map(|(idx, name)| {
+            match mtl_set.material_shadings.get(idx) {
+                Some(Shading::Color(color)) => Color::RGB((color >> 24) as u8, (color >> 16) as u8, (color >> 8) as u8),
+                Some(Shading::Texture(file_name)) => Self::texture_color(file_name).unwrap_or_else(|_| Self::name_color(name)),
+                None => Self::name_color(name),
+            }
+        }).collect()
+    }
+
+    /// draws the level on a surface, with `tile_size` pixels per tile
+    pub fn render(level_map: &LevelMap, tile_size: u32) -> Result<Surface<'static>, Box<dyn Error>> {
+        let map = &level_map.map;
+        let margin = tile_size / 2;
+        let width = map.width as u32 * tile_size + margin * 2;
+        let height = map.height as u32 * tile_size + margin * 2;
+        let surface = Surface::new(width, height, PixelFormatEnum::RGB888)?;
+        let mut canvas = surface.into_canvas()?;
+        let view = MapView {
+            rect: Rect::new(0, 0, width, height),
+            focus: Vec2 { x: map.width as f32 / 2.0, y: map.height as f32 / 2.0 },
+            scale: tile_size as f32,
+        };
+        let colors = Self::material_colors(&level_map.mtl_set);
+
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.clear();
+
+        // materials, dimmed on the floors
+        for (tile_y, line) in map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                let color = colors[tile.mtl_idx];
+                let is_floor = level_map.geo_set.geometries[tile.geo_idx].is_none();
+                canvas.set_draw_color(if is_floor { Self::scale_color(color, Self::FLOOR_BRIGHTNESS) } else { color });
+                canvas.fill_rect(view.tile_rect(tile_x, tile_y))?;
+            }
+        }
+
+        canvas.set_draw_color(Self::GRID_COLOR);
+        for x in 0..=map.width {
+            canvas.draw_line(view.to_screen(Vec2 { x: x as f32, y: 0.0 }), view.to_screen(Vec2 { x: x as f32, y: map.height as f32 }))?;
+        }
+        for y in 0..=map.height {
+            canvas.draw_line(view.to_screen(Vec2 { x: 0.0, y: y as f32 }), view.to_screen(Vec2 { x: map.width as f32, y: y as f32 }))?;
+        }
+
+        for (tile_y, line) in map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                let origin = Vec2 { x: tile_x as f32, y: tile_y as f32 };
+                if level_map.mtl_set.material_properties[tile.mtl_idx].portal.is_some() {
+                    // a cross over the portal tiles
+                    let rect = view.tile_rect(tile_x, tile_y);
+                    canvas.set_draw_color(Self::PORTAL_COLOR);
+                    canvas.draw_rect(rect)?;
+                    canvas.draw_line(rect.top_left(), rect.bottom_right())?;
+                    canvas.draw_line(rect.top_right(), rect.bottom_left())?;
+                }
+                canvas.set_draw_color(if tile.mover.is_some() { Self::MOVER_COLOR } else { Self::WALL_COLOR });
+                for primitive in level_map.geo_set.geometries[tile.geo_idx].iter().flatten() {
+                    view.draw_primitive(&mut canvas, primitive, origin)?;
+                }
+            }
+        }
+        canvas.set_draw_color(Self::WALL_COLOR);
+        for primitive in &map.primitives {
+            view.draw_primitive(&mut canvas, primitive, Vec2::default())?;
+        }
+
+        canvas.set_draw_color(Self::SPAWN_COLOR);
+        for spawn in &level_map.spawns {
+            view.fill_circle(&mut canvas, spawn.coordinates, 0.25)?;
+        }
+        Ok(canvas.into_surface())
+    }
+}
@@ -6,49 +6,300 @@ use super::{
 
 use parser;
 
+use serde::{
+    Serialize,
+    Deserialize,
+};
+
+use std::error::Error;
+
+/// the intersection of a ray with a primitive
+#[derive(Debug, Clone, Copy)]
+pub struct PrimitiveHit {
+    /// distance along the ray, in lengths of its direction
+    pub t: f32,
+    /// unit normal of the surface at the hit, facing the ray
+    pub normal: Vec2,
+    /// horizontal texture coordinate of the hit, from 0 to 1 along the surface
+    pub u: f32,
+}
+
+/// The vertical extent of a primitive, in wall heights: a wall goes from the floor at 0 to the ceiling at 1.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Elevation {
+    #[serde(skip_serializing_if = "Elevation::is_on_floor")]
+    pub base: f32,
+    #[serde(skip_serializing_if = "Elevation::is_wall_height")]
+    pub height: f32,
+}
+
+impl Default for Elevation {
+    fn default() -> Self {
+        Self { base: 0.0, height: 1.0 }
+    }
+}
+
+impl Elevation {
+    /// height of the eyes of the players
+    pub const EYE_LEVEL: f32 = 0.5;
+
+    fn is_on_floor(base: &f32) -> bool {
+        *base == 0.0
+    }
+
+    fn is_wall_height(height: &f32) -> bool {
+        *height == 1.0
+    }
+
+    pub fn top(&self) -> f32 {
+        self.base + self.height
+    }
+
+    /// a primitive at eye level blocks the view, the shots and the hands of the players
+    pub fn at_eye_level(&self) -> bool {
+        self.base <= Self::EYE_LEVEL && self.top() >= Self::EYE_LEVEL
+    }
+}
+
+/// A primitive is only seen from the side its normals face, unless it is `two_sided`.
 #[derive(Debug)]
 pub enum PrimitiveGeometry {
     Plane {
         p1: Vec2,
         p2: Vec2,
         n: Vec2,
+        two_sided: bool,
+        elevation: Elevation,
     },
+    /// seen from the outside, or from the inside when it is `inward`
     Cylinder {
+        /// position in the tile
+        center: Vec2,
         radius: f32,
+        inward: bool,
+        two_sided: bool,
+        elevation: Elevation,
+    },
+    /// Part of a circle, seen from the outside or from the inside like a cylinder.
+    /// The angles are in degrees, from the x axis towards the y axis, and the arc goes from `start_angle` to `end_angle`.
+    Arc {
+        center: Vec2,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        inward: bool,
+        two_sided: bool,
+        elevation: Elevation,
+    },
+    /// Closed convex polygon, seen from the outside.
+    /// `normals[i]` is the outward normal of the edge from `points[i]` to the next point.
+    Polygon {
+        points: Vec<Vec2>,
+        normals: Vec<Vec2>,
+        two_sided: bool,
+        elevation: Elevation,
     },
 }
 
 impl PrimitiveGeometry {
+    #[inline]
+    fn clamp_point(point: Vec2) -> Vec2 {
+        Vec2 {
+            x: clampf(point.x, 0.0, 1.0),
+            y: clampf(point.y, 0.0, 1.0)
+        }
+    }
+
     #[inline]
     fn create_one_sided_plane(p1: Vec2, p2: Vec2) -> Self {
-        let p1 = Vec2 {
-            x: clampf(p1.x, 0.0, 1.0),
-            y: clampf(p1.y, 0.0, 1.0)
-        };
-        let p2 = Vec2 {
-            x: clampf(p2.x, 0.0, 1.0),
-            y: clampf(p2.y, 0.0, 1.0)
-        };
         let mut p1p2 = p2 - p1;
         p1p2.normalize();
-        Self::Plane { p1, p2, n: p1p2.orthogonal(false) }
+        Self::Plane { p1, p2, n: p1p2.orthogonal(false), two_sided: false, elevation: Elevation::default() }
+    }
+
+    /// the center is kept in the tile, and the radius keeps the cylinder in the tile, up to 0.5
+    pub fn cylinder(center: Vec2, radius: f32, inward: bool) -> Self {
+        let center = Self::clamp_point(center);
+        let max_radius = center.x.min(1.0 - center.x).min(center.y).min(1.0 - center.y);
+        Self::Cylinder { center, radius: clampf(radius, 0.0, max_radius), inward, two_sided: false, elevation: Elevation::default() }
+    }
+
+    /// the arc must stay within its tile, an arc of 360° or more is a whole circle
+    pub fn arc(center: Vec2, radius: f32, start_angle: f32, end_angle: f32, inward: bool) -> Result<Self, Box<dyn Error>> {
+        let arc = Self::create_arc(Self::clamp_point(center), radius.max(0.0), start_angle, end_angle, inward)?;
+        let (min, max) = arc.bounds();
+        let delta = 0.0001;
+        if min.x < -delta || min.y < -delta || max.x > 1.0 + delta || max.y > 1.0 + delta {
+            return Err(format!("arc: the arc of radius {} from {}° to {}° leaves its tile", radius, start_angle, end_angle).into());
+        }
+        Ok(arc)
+    }
+
+    fn create_arc(center: Vec2, radius: f32, start_angle: f32, end_angle: f32, inward: bool) -> Result<Self, Box<dyn Error>> {
+        if end_angle <= start_angle {
+            return Err(format!("arc: the end angle {} must be greater than the start angle {}", end_angle, start_angle).into());
+        }
+        Ok(Self::Arc {
+            center,
+            radius,
+            start_angle,
+            end_angle: end_angle.min(start_angle + 360.0),
+            inward,
+            two_sided: false,
+            elevation: Elevation::default(),
+        })
+    }
+
+    /// the points are kept in the tile, and may go around the polygon in either direction
+    pub fn polygon(points: Vec<Vec2>) -> Result<Self, Box<dyn Error>> {
+        Self::create_polygon(points.into_iter().map(Self::clamp_point).collect())
+    }
+
+    fn create_polygon(points: Vec<Vec2>) -> Result<Self, Box<dyn Error>> {
+        if points.len() < 3 {
+            return Err("polygon: at least 3 points are needed".into());
+        }
+        let n = points.len();
+        let edge = |idx: usize| points[(idx + 1) % n] - points[idx];
+        // twice the signed area, positive when the points go clockwise on the screen
+        let area = (0..n).map(|idx| points[idx].cross(points[(idx + 1) % n])).sum::<f32>();
+        if area.abs() < 1e-6 {
+            return Err("polygon: the points are aligned".into());
+        }
+        if (0..n).any(|idx| edge(idx).cross(edge((idx + 1) % n)) * area < 0.0) {
+            return Err("polygon: the polygon is not convex".into());
+        }
+        let normals = (0..n).map(|idx| {
+            let mut normal = edge(idx).orthogonal(area < 0.0);
+            normal.normalize();
+            normal
+        }).collect();
+        Ok(Self::Polygon { points, normals, two_sided: false, elevation: Elevation::default() })
     }
 
-    pub fn new(raw_primitive: parser::geometry::PrimitiveGeometry) -> Self {
-        match raw_primitive {
-            parser::geometry::PrimitiveGeometry::Plane{ p1, p2 } => {
-                Self::create_one_sided_plane(p1, p2)
+    /// the primitive seen from both sides, or only from the side its normals face
+    pub fn with_two_sides(mut self, two_sided: bool) -> Self {
+        match self {
+            Self::Plane{ two_sided: ref mut sides, .. }
+            | Self::Cylinder{ two_sided: ref mut sides, .. }
+            | Self::Arc{ two_sided: ref mut sides, .. }
+            | Self::Polygon{ two_sided: ref mut sides, .. } => *sides = two_sided,
+        }
+        self
+    }
+
+    pub fn with_elevation(mut self, elevation: Elevation) -> Self {
+        match self {
+            Self::Plane{ elevation: ref mut primitive_elevation, .. }
+            | Self::Cylinder{ elevation: ref mut primitive_elevation, .. }
+            | Self::Arc{ elevation: ref mut primitive_elevation, .. }
+            | Self::Polygon{ elevation: ref mut primitive_elevation, .. } => *primitive_elevation = elevation,
+        }
+        self
+    }
+
+    pub fn elevation(&self) -> Elevation {
+        match *self {
+            Self::Plane{ elevation, .. }
+            | Self::Cylinder{ elevation, .. }
+            | Self::Arc{ elevation, .. }
+            | Self::Polygon{ elevation, .. } => elevation,
+        }
+    }
+
+    fn raw_elevation(raw_primitive: &parser::geometry::PrimitiveGeometry) -> Result<Elevation, Box<dyn Error>> {
+        let elevation = raw_primitive.elevation();
+        if elevation.height <= 0.0 {
+            return Err(format!("the height {} must be positive", elevation.height).into());
+        }
+        Ok(elevation)
+    }
+
+    /// a primitive of a geometric tile, kept within the tile
+    pub fn new(raw_primitive: parser::geometry::PrimitiveGeometry) -> Result<Self, Box<dyn Error>> {
+        let elevation = Self::raw_elevation(&raw_primitive)?;
+        let primitive = match raw_primitive {
+            parser::geometry::PrimitiveGeometry::Plane{ p1, p2, two_sided, .. } => {
+                Self::create_one_sided_plane(Self::clamp_point(p1), Self::clamp_point(p2)).with_two_sides(two_sided)
+            },
+            parser::geometry::PrimitiveGeometry::Cylinder{ center, radius, inward, two_sided, .. } => {
+                Self::cylinder(center, radius, inward).with_two_sides(two_sided)
+            },
+            parser::geometry::PrimitiveGeometry::Arc{ center, radius, start_angle, end_angle, inward, two_sided, .. } => {
+                Self::arc(center, radius, start_angle, end_angle, inward)?.with_two_sides(two_sided)
+            },
+            parser::geometry::PrimitiveGeometry::Polygon{ points, two_sided, .. } => {
+                Self::polygon(points)?.with_two_sides(two_sided)
+            },
+        };
+        Ok(primitive.with_elevation(elevation))
+    }
+
+    /// a primitive of the map, in map coordinates, which may span several tiles
+    pub fn new_in_map(raw_primitive: parser::geometry::PrimitiveGeometry) -> Result<Self, Box<dyn Error>> {
+        let elevation = Self::raw_elevation(&raw_primitive)?;
+        let primitive = match raw_primitive {
+            parser::geometry::PrimitiveGeometry::Plane{ p1, p2, two_sided, .. } => {
+                Self::create_one_sided_plane(p1, p2).with_two_sides(two_sided)
+            },
+            parser::geometry::PrimitiveGeometry::Cylinder{ center, radius, inward, two_sided, .. } => {
+                Self::Cylinder { center, radius: radius.max(0.0), inward, two_sided, elevation }
+            },
+            parser::geometry::PrimitiveGeometry::Arc{ center, radius, start_angle, end_angle, inward, two_sided, .. } => {
+                Self::create_arc(center, radius.max(0.0), start_angle, end_angle, inward)?.with_two_sides(two_sided)
+            },
+            parser::geometry::PrimitiveGeometry::Polygon{ points, two_sided, .. } => {
+                Self::create_polygon(points)?.with_two_sides(two_sided)
+            },
+        };
+        Ok(primitive.with_elevation(elevation))
+    }
+
+    /// the top left and bottom right corners of a box holding the primitive
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let points_bounds = |points: &[Vec2]| points.iter().skip(1).fold((points[0], points[0]), |(min, max), point| (
+            Vec2 { x: min.x.min(point.x), y: min.y.min(point.y) },
+            Vec2 { x: max.x.max(point.x), y: max.y.max(point.y) },
+        ));
+        match *self {
+            Self::Plane{ p1, p2, .. } => points_bounds(&[p1, p2]),
+            Self::Cylinder{ center, radius, .. } => {
+                (center - Vec2 { x: radius, y: radius }, center + Vec2 { x: radius, y: radius })
+            },
+            Self::Arc{ center, radius, start_angle, end_angle, .. } => {
+                let point = |angle: f32| center + Vec2 { x: angle.to_radians().cos(), y: angle.to_radians().sin() } * radius;
+                // the ends of the arc, and the points of its circle on the axes which the arc goes through
+                let axes = (start_angle / 90.0).ceil() as i32..=(end_angle / 90.0).floor() as i32;
+                let mut points = vec![point(start_angle), point(end_angle)];
+                points.extend(axes.map(|axis| point(axis as f32 * 90.0)));
+                points_bounds(&points)
+            },
+            Self::Polygon{ ref points, .. } => points_bounds(points),
+        }
+    }
+
+    /// the primitive as written in a geometry set
+    pub fn to_raw(&self) -> parser::geometry::PrimitiveGeometry {
+        match *self {
+            Self::Plane{ p1, p2, two_sided, elevation, .. } => parser::geometry::PrimitiveGeometry::Plane { p1, p2, two_sided, elevation },
+            Self::Cylinder{ center, radius, inward, two_sided, elevation } => {
+                parser::geometry::PrimitiveGeometry::Cylinder { center, radius, inward, two_sided, elevation }
+            },
+            Self::Arc{ center, radius, start_angle, end_angle, inward, two_sided, elevation } => {
+                parser::geometry::PrimitiveGeometry::Arc { center, radius, start_angle, end_angle, inward, two_sided, elevation }
+            },
+            Self::Polygon{ ref points, two_sided, elevation, .. } => {
+                parser::geometry::PrimitiveGeometry::Polygon { points: points.clone(), two_sided, elevation }
             },
-            parser::geometry::PrimitiveGeometry::Cylinder{ radius } => {
-                Self::Cylinder { radius: clampf(radius, 0.0, 0.5) }
-            }
         }
     }
 
-    /// ray/one-sided segment intersection
-    fn ray_osseg_intersection(ray: Ray, p1: Vec2, p2: Vec2, n: Vec2) -> Option<f32> {
+    /// Ray/one-sided segment intersection, the back side is also hit when the segment is `two_sided`.
+    /// Returns the ray parameter t and the segment parameter u, from 0 at p1 to 1 at p2.
+    fn ray_osseg_intersection(ray: Ray, p1: Vec2, p2: Vec2, n: Vec2, two_sided: bool) -> Option<(f32, f32)> {
         // wrong side
-        if n.dot(ray.dir) >= 0.0 {
+        if !two_sided && n.dot(ray.dir) >= 0.0 {
             return None;
         }
 
@@ -77,13 +328,13 @@ impl PrimitiveGeometry {
 
         if rxs != 0.0 {
             // q - p
-            let qp = p1 - ray.origin; 
-            
+            let qp = p1 - ray.origin;
+
             let u = qp.cross(ray.dir) / rxs;
 
-            if u >= 0.0 && u <= 1.0 {
+            if (0.0..=1.0).contains(&u) {
                 let t = qp.cross(p2p1) / rxs;
-                return Some(t);
+                return Some((t, u));
             }
         }
         None
@@ -94,10 +345,10 @@ impl PrimitiveGeometry {
     /// * Some((t0, None)) : there is one intersection (the ray is tangent to the circle)
     /// * Some((t0, Some(t1))) : there are two intersections and t0 <= t1
     fn ray_circle_intersection(ray: Ray, center: Vec2, radius: f32) -> Option<(f32, Option<f32>)> {
-        
+
         let center_orig = ray.origin - center;
-        
-        // quadratic equation : at^2 + bt + c = 0 
+
+        // quadratic equation : at^2 + bt + c = 0
         let a = ray.dir.dot(ray.dir);
         let b = 2.0 * ray.dir.dot(center_orig);
         let c = center_orig.dot(center_orig) - radius * radius;
@@ -122,23 +373,234 @@ impl PrimitiveGeometry {
         }
     }
 
-    pub fn ray_intersection(&self, ray: Ray, tile_x: usize, tile_y: usize) -> Option<f32> {
-        let mut t: Option<f32> = None;
-        match self {
-            &Self::Plane{ p1, p2, n } => {
-                let tile = Vec2{ x: tile_x as f32, y: tile_y as f32 };
-                t = Self::ray_osseg_intersection(ray, p1 + tile, p2 + tile, n);
-            },
-            &Self::Cylinder{ radius } => {
-                let center = Vec2 { x: tile_x as f32 + 0.5, y: tile_y as f32 + 0.5 };
-                if let Some((t0, _)) = Self::ray_circle_intersection(ray, center, radius) {
-                    t = Some(t0)
+    /// angle of a point around a center, in degrees from 0 to 360
+    fn angle_around(point: Vec2, center: Vec2) -> f32 {
+        let to_point = point - center;
+        to_point.y.atan2(to_point.x).to_degrees().rem_euclid(360.0)
+    }
+
+    /// the position of an angle along an arc, from 0 at its start to 1 at its end, if the arc holds it
+    fn arc_position(angle: f32, start_angle: f32, end_angle: f32) -> Option<f32> {
+        let span = end_angle - start_angle;
+        let offset = (angle - start_angle).rem_euclid(360.0);
+        if offset <= span {
+            Some(offset / span)
+        } else {
+            None
+        }
+    }
+
+    /// the normal turned towards the origin of the ray
+    fn facing_ray(normal: Vec2, ray: Ray) -> Vec2 {
+        if normal.dot(ray.dir) > 0.0 { normal * -1.0 } else { normal }
+    }
+
+    /// The nearest hit of a ray with a circle, or with an arc of it.
+    /// The ray enters the circle at the near root and leaves it at the far root:
+    /// the outside faces the ray at the near root, the inside at the far one.
+    fn circle_hit(ray: Ray, center: Vec2, radius: f32, angles: Option<(f32, f32)>, inward: bool, two_sided: bool) -> Option<PrimitiveHit> {
+        let (t0, t1) = match Self::ray_circle_intersection(ray, center, radius)? {
+            (t0, Some(t1)) => (t0, t1),
+            (t0, None) => (t0, t0),
+        };
+        let roots = [(t0, !inward || two_sided), (t1, inward || two_sided)];
+        roots.iter()
+            .filter(|&&(t, seen)| seen && t >= 0.0)
+            .filter_map(|&(t, _)| {
+                let hit = ray.origin + ray.dir * t;
+                let angle = Self::angle_around(hit, center);
+                let u = match angles {
+                    Some((start_angle, end_angle)) => Self::arc_position(angle, start_angle, end_angle)?,
+                    None => angle / 360.0,
+                };
+                let mut normal = hit - center;
+                normal.normalize();
+                Some(PrimitiveHit { t, normal: Self::facing_ray(normal, ray), u })
+            })
+            .next()
+    }
+
+    /// `origin` is the position of the top left corner of the tile holding the primitive
+    pub fn ray_hit(&self, ray: Ray, origin: Vec2) -> Option<PrimitiveHit> {
+        let hit = match *self {
+            Self::Plane{ p1, p2, n, two_sided, .. } => {
+                Self::ray_osseg_intersection(ray, p1 + origin, p2 + origin, n, two_sided)
+                    .map(|(t, u)| PrimitiveHit { t, normal: Self::facing_ray(n, ray), u })
+            },
+            Self::Cylinder{ center, radius, inward, two_sided, .. } => {
+                Self::circle_hit(ray, origin + center, radius, None, inward, two_sided)
+            },
+            Self::Arc{ center, radius, start_angle, end_angle, inward, two_sided, .. } => {
+                Self::circle_hit(ray, origin + center, radius, Some((start_angle, end_angle)), inward, two_sided)
+            },
+            Self::Polygon{ ref points, ref normals, two_sided, .. } => {
+                let mut nearest: Option<PrimitiveHit> = None;
+                for (idx, &normal) in normals.iter().enumerate() {
+                    let p1 = origin + points[idx];
+                    let p2 = origin + points[(idx + 1) % points.len()];
+                    if let Some((t, u)) = Self::ray_osseg_intersection(ray, p1, p2, normal, two_sided) {
+                        if t >= 0.0 && nearest.is_none_or(|nearest| t < nearest.t) {
+                            // along the whole outline, from the first point
+                            let u = (Self::outline_length(&points[..=idx]) + u * (p2 - p1).length()) / self.length();
+                            nearest = Some(PrimitiveHit { t, normal: Self::facing_ray(normal, ray), u });
+                        }
+                    }
                 }
-            }
+                nearest
+            },
+        };
+        hit.filter(|hit| hit.t >= 0.0)
+    }
+
+    /// length of the segments between the points, without closing them
+    fn outline_length(points: &[Vec2]) -> f32 {
+        points.windows(2).map(|edge| (edge[1] - edge[0]).length()).sum()
+    }
+
+    /// length of the surface of the primitive, along which the texture coordinate `u` goes from 0 to 1
+    pub fn length(&self) -> f32 {
+        match *self {
+            Self::Plane{ p1, p2, .. } => (p2 - p1).length(),
+            Self::Cylinder{ radius, .. } => 2.0 * std::f32::consts::PI * radius,
+            Self::Arc{ radius, start_angle, end_angle, .. } => (end_angle - start_angle).to_radians() * radius,
+            Self::Polygon{ ref points, .. } => Self::outline_length(points) + (points[0] - points[points.len() - 1]).length(),
         }
-        if t.is_some() && t.unwrap() >= 0.0 {
-            return t;
+    }
+
+    pub fn is_two_sided(&self) -> bool {
+        match *self {
+            Self::Plane{ two_sided, .. }
+            | Self::Cylinder{ two_sided, .. }
+            | Self::Arc{ two_sided, .. }
+            | Self::Polygon{ two_sided, .. } => two_sided,
         }
-        None
     }
-}
\ No newline at end of file
+
+    /// The point of the surface at the texture coordinate `u`, with the normal of the side the primitive is seen from.
+    /// `origin` is the position of the top left corner of the tile holding the primitive.
+    pub fn surface_point(&self, u: f32, origin: Vec2) -> (Vec2, Vec2) {
+        let circle_point = |center: Vec2, radius: f32, angle: f32, inward: bool| {
+            let normal = Vec2 { x: angle.to_radians().cos(), y: angle.to_radians().sin() };
+            (origin + center + normal * radius, if inward { normal * -1.0 } else { normal })
+        };
+        match *self {
+            Self::Plane{ p1, p2, n, .. } => (origin + p1 + (p2 - p1) * u, n),
+            Self::Cylinder{ center, radius, inward, .. } => circle_point(center, radius, u * 360.0, inward),
+            Self::Arc{ center, radius, start_angle, end_angle, inward, .. } => {
+                circle_point(center, radius, start_angle + u * (end_angle - start_angle), inward)
+            },
+            Self::Polygon{ ref points, ref normals, .. } => {
+                let mut distance = u * self.length();
+                for (idx, &normal) in normals.iter().enumerate() {
+                    let (p1, p2) = (points[idx], points[(idx + 1) % points.len()]);
+                    let edge_length = (p2 - p1).length();
+                    if distance <= edge_length || idx == normals.len() - 1 {
+                        let along = if edge_length > 0.0 { (distance / edge_length).min(1.0) } else { 0.0 };
+                        return (origin + p1 + (p2 - p1) * along, normal);
+                    }
+                    distance -= edge_length;
+                }
+                unreachable!()
+            },
+        }
+    }
+
+    /// `origin` is the position of the top left corner of the tile holding the primitive
+    pub fn ray_intersection(&self, ray: Ray, origin: Vec2) -> Option<f32> {
+        self.ray_hit(ray, origin).map(|hit| hit.t)
+    }
+
+    /// nearest point of the segment p1p2 to a point
+    fn closest_point_on_segment(point: Vec2, p1: Vec2, p2: Vec2) -> Vec2 {
+        let p1p2 = p2 - p1;
+        let length2 = p1p2.dot(p1p2);
+        if length2 == 0.0 {
+            return p1;
+        }
+        p1 + p1p2 * clampf((point - p1).dot(p1p2) / length2, 0.0, 1.0)
+    }
+
+    /// the move which takes a disc away from a point it covers
+    fn push_from_point(center: Vec2, radius: f32, point: Vec2) -> Option<Vec2> {
+        let mut away = center - point;
+        let dist = away.length();
+        if dist >= radius {
+            return None;
+        }
+        if dist == 0.0 {
+            away = Vec2 { x: 1.0, y: 0.0 };
+        }
+        away.normalize();
+        Some(away * (radius - dist))
+    }
+
+    /// Collision of a disc with the primitive: the shortest move which takes the disc out of it, if they overlap.
+    /// Segments, arcs and inward cylinders push the disc back to the side of its center, cylinders and polygons push it outside.
+    /// `origin` is the position of the top left corner of the tile holding the primitive.
+    pub fn push_out(&self, center: Vec2, radius: f32, origin: Vec2) -> Option<Vec2> {
+        match *self {
+            Self::Plane{ p1, p2, .. } => {
+                let closest = Self::closest_point_on_segment(center, p1 + origin, p2 + origin);
+                Self::push_from_point(center, radius, closest)
+            },
+            Self::Cylinder{ center: cylinder_center, radius: cylinder_radius, inward: true, .. } => {
+                // the wall of a round room
+                let cylinder_center = origin + cylinder_center;
+                let mut to_center = center - cylinder_center;
+                if to_center.length() == 0.0 {
+                    to_center = Vec2 { x: 1.0, y: 0.0 };
+                }
+                to_center.normalize();
+                Self::push_from_point(center, radius, cylinder_center + to_center * cylinder_radius)
+            },
+            Self::Cylinder{ center: cylinder_center, radius: cylinder_radius, .. } => {
+                let cylinder_center = origin + cylinder_center;
+                let mut away = center - cylinder_center;
+                let dist = away.length();
+                if dist >= cylinder_radius + radius {
+                    return None;
+                }
+                if dist == 0.0 {
+                    away = Vec2 { x: 1.0, y: 0.0 };
+                }
+                away.normalize();
+                Some(away * (cylinder_radius + radius - dist))
+            },
+            Self::Arc{ center: arc_center, radius: arc_radius, start_angle, end_angle, .. } => {
+                let arc_center = origin + arc_center;
+                let angle = Self::angle_around(center, arc_center);
+                let closest = match Self::arc_position(angle, start_angle, end_angle) {
+                    Some(_) => {
+                        let mut to_center = center - arc_center;
+                        to_center.normalize();
+                        arc_center + to_center * arc_radius
+                    },
+                    None => {
+                        let end_point = |angle: f32| arc_center + Vec2 { x: angle.to_radians().cos(), y: angle.to_radians().sin() } * arc_radius;
+                        let (start, end) = (end_point(start_angle), end_point(end_angle));
+                        if (center - start).length() <= (center - end).length() { start } else { end }
+                    },
+                };
+                Self::push_from_point(center, radius, closest)
+            },
+            Self::Polygon{ ref points, ref normals, .. } => {
+                let n = points.len();
+                // signed distances of the center to the lines of the edges, positive outside
+                let dists: Vec<f32> = (0..n).map(|idx| (center - (origin + points[idx])).dot(normals[idx])).collect();
+                if dists.iter().all(|&dist| dist <= 0.0) {
+                    // the center is inside: out through the nearest edge
+                    let (idx, dist) = dists.iter().enumerate()
+                        .fold((0, f32::MIN), |(best, best_dist), (idx, &dist)| if dist > best_dist { (idx, dist) } else { (best, best_dist) });
+                    return Some(normals[idx] * (radius - dist));
+                }
+                (0..n)
+                    .map(|idx| Self::closest_point_on_segment(center, origin + points[idx], origin + points[(idx + 1) % n]))
+                    .fold(None, |nearest: Option<Vec2>, point| match nearest {
+                        Some(nearest) if (center - nearest).length() <= (center - point).length() => Some(nearest),
+                        _ => Some(point),
+                    })
+                    .and_then(|closest| Self::push_from_point(center, radius, closest))
+            },
+        }
+    }
+}
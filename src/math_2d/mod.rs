@@ -9,7 +9,11 @@ pub use self::ray::Ray;
 
 pub use self::mat2::Mat2;
 
-pub use self::geometry::PrimitiveGeometry;
+pub use self::geometry::{
+    PrimitiveGeometry,
+    PrimitiveHit,
+    Elevation,
+};
 
 pub fn clampf(value: f32, min: f32, max: f32) -> f32 {
     if value < min {
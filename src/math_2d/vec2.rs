@@ -1,4 +1,7 @@
-use serde::Deserialize;
+use serde::{
+    Serialize,
+    Deserialize,
+};
 
 use std::ops::{
     Add,
@@ -7,7 +10,7 @@ use std::ops::{
     Mul,
 };
 
-#[derive(Deserialize, Default, PartialEq, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy, Debug)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
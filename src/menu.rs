@@ -0,0 +1,162 @@
+use sdl2::{
+    pixels::Color,
+    ttf::Sdl2TtfContext,
+    rect::Rect,
+    render::{
+        Canvas,
+        TextureCreator,
+        BlendMode,
+    },
+    video::{
+        Window,
+        WindowContext,
+    },
+};
+
+use controls::controls::*;
+
+use text::ScaledFont;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+
+/// Keys of the menu control manager, shared by all the menus
+pub struct MenuInput<'a> {
+    keys: Rc<RefCell<Vec<MenuKey>>>,
+    _handles: Vec<ControlHandle<'a>>,
+}
+
+impl<'a> MenuInput<'a> {
+    pub fn new(control_handler: &mut ControlHandler<'a>) -> Result<Self, Box<dyn Error>> {
+        let keys = Rc::new(RefCell::new(vec![]));
+        let bindings = [
+            (Scancode::Up, MenuKey::Up),
+            (Scancode::W, MenuKey::Up),
+            (Scancode::Down, MenuKey::Down),
+            (Scancode::S, MenuKey::Down),
+            (Scancode::Return, MenuKey::Select),
+            (Scancode::KpEnter, MenuKey::Select),
+            (Scancode::Space, MenuKey::Select),
+            (Scancode::Escape, MenuKey::Back),
+        ];
+        let mut handles = Vec::with_capacity(bindings.len());
+        for &(scancode, key) in &bindings {
+            let clone_keys = Rc::downgrade(&keys);
+            let action = Box::new(move |_: Event| { clone_keys.upgrade().unwrap().borrow_mut().push(key); });
+            let event = Event::KeyDown {
+                timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+            };
+            handles.push(control_handler.add_control(ControlManagerType::Menu, event, action)?);
+        }
+        Ok(Self { keys, _handles: handles })
+    }
+
+    /// keys pressed since the last call
+    pub fn take(&self) -> Vec<MenuKey> {
+        std::mem::take(&mut *self.keys.borrow_mut())
+    }
+}
+
+/// what the player chose in a menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuChoice {
+    Item(usize),
+    Back,
+}
+
+/// Vertical list of items, drawn at the center of the screen
+pub struct Menu {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+    /// shown under the items, e.g. an error
+    pub message: Option<String>,
+    /// the menu is drawn over the level
+    pub overlay: bool,
+}
+
+impl Menu {
+    const TITLE_COLOR: Color = Color { r: 0xf0, g: 0xe6, b: 0x8c, a: 0xff };
+    const ITEM_COLOR: Color = Color { r: 0xa0, g: 0xa0, b: 0xa0, a: 0xff };
+    const SELECTED_COLOR: Color = Color { r: 0xff, g: 0xff, b: 0xff, a: 0xff };
+    const MESSAGE_COLOR: Color = Color { r: 0xe0, g: 0x50, b: 0x40, a: 0xff };
+    const BACKGROUND_COLOR: Color = Color { r: 0x20, g: 0x22, b: 0x2a, a: 0xff };
+    const OVERLAY_COLOR: Color = Color { r: 0x00, g: 0x00, b: 0x00, a: 0xa0 };
+    const FONT_SCALE: f32 = 1.0 / 16.0;
+
+    pub fn new(title: &str, items: Vec<String>) -> Self {
+        Self { title: title.to_string(), items, selected: 0, message: None, overlay: false }
+    }
+
+    pub fn overlay(mut self) -> Self {
+        self.overlay = true;
+        self
+    }
+
+    /// moves the selection, and returns the choice of the player if any
+    pub fn update(&mut self, keys: &[MenuKey]) -> Option<MenuChoice> {
+        for key in keys {
+            match key {
+                MenuKey::Up if !self.items.is_empty() => {
+                    self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+                },
+                MenuKey::Down if !self.items.is_empty() => {
+                    self.selected = (self.selected + 1) % self.items.len();
+                },
+                MenuKey::Select if !self.items.is_empty() => return Some(MenuChoice::Item(self.selected)),
+                MenuKey::Back => return Some(MenuChoice::Back),
+                _ => {},
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &mut ScaledFont) -> Result<(), Box<dyn Error>> {
+        font.fit(canvas)?;
+        let (width, height) = canvas.window().drawable_size();
+        if self.overlay {
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(Self::OVERLAY_COLOR);
+            canvas.fill_rect(Rect::new(0, 0, width, height))?;
+            canvas.set_blend_mode(BlendMode::None);
+        } else {
+            canvas.set_draw_color(Self::BACKGROUND_COLOR);
+            canvas.clear();
+        }
+
+        let line_height = font.line_height().max(1);
+        let mut lines: Vec<(String, Color)> = vec![(self.title.clone(), Self::TITLE_COLOR), (String::new(), Self::ITEM_COLOR)];
+        for (idx, item) in self.items.iter().enumerate() {
+            if idx == self.selected {
+                lines.push((format!("> {} <", item), Self::SELECTED_COLOR));
+            } else {
+                lines.push((item.clone(), Self::ITEM_COLOR));
+            }
+        }
+        if let Some(message) = &self.message {
+            lines.push((String::new(), Self::ITEM_COLOR));
+            lines.push((message.clone(), Self::MESSAGE_COLOR));
+        }
+
+        // long lists scroll to keep the selected item on screen
+        let max_lines = (height as i32 / line_height - 2).max(1) as usize;
+        let first = (self.selected + 2 + 1).saturating_sub(max_lines);
+        let mut y = (height as i32 - (lines.len().min(max_lines) as i32 * line_height)) / 2;
+        for (text, color) in lines.iter().skip(first).take(max_lines) {
+            let (text_width, _) = font.size_of(if text.is_empty() { " " } else { text })?;
+            font.draw(canvas, texture_creator, text, *color, (width as i32 - text_width as i32) / 2, y)?;
+            y += line_height;
+        }
+        Ok(())
+    }
+
+    /// the font used by the menus
+    pub fn create_font<'ttf>(ttf_context: &'ttf Sdl2TtfContext, font_name: &str, window_height: u32) -> Result<ScaledFont<'ttf>, Box<dyn Error>> {
+        ScaledFont::new(ttf_context, font_name, Self::FONT_SCALE, window_height)
+    }
+}
@@ -1,38 +1,147 @@
 use super::*;
 
-#[derive(Deserialize, Debug)]
+use serde::Serialize;
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SpawnSymbol {
     pub player: usize,
     pub symbol: char,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SpawnSymbolSet {
     pub spawn_symbol_set: Vec<SpawnSymbol>,
 }
 
-#[derive(Deserialize, Debug)]
+/// the points are written as [x, y], like in the hand-written sets
+pub fn serialize_point<S: serde::Serializer>(point: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+    [point.x, point.y].serialize(serializer)
+}
+
+fn serialize_points<S: serde::Serializer>(points: &[Vec2], serializer: S) -> Result<S::Ok, S::Error> {
+    points.iter().map(|point| [point.x, point.y]).collect::<Vec<_>>().serialize(serializer)
+}
+
+fn tile_center() -> Vec2 {
+    Vec2 { x: 0.5, y: 0.5 }
+}
+
+fn is_tile_center(point: &Vec2) -> bool {
+    *point == tile_center()
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Primitives are seen from one side, unless they are `two_sided`.
+/// Cylinders and arcs are seen from the outside, or from the inside when they are `inward`.
+/// The `base` and `height` of the elevation of a primitive are written next to its other fields.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum PrimitiveGeometry {
-    Plane { p1: Vec2, p2: Vec2 },
-    Cylinder { radius: f32 },
+    Plane {
+        #[serde(serialize_with = "serialize_point")]
+        p1: Vec2,
+        #[serde(serialize_with = "serialize_point")]
+        p2: Vec2,
+        #[serde(default, skip_serializing_if = "is_false")]
+        two_sided: bool,
+        #[serde(flatten)]
+        elevation: Elevation,
+    },
+    Cylinder {
+        /// position in the tile, its center by default
+        #[serde(default = "tile_center", skip_serializing_if = "is_tile_center", serialize_with = "serialize_point")]
+        center: Vec2,
+        radius: f32,
+        #[serde(default, skip_serializing_if = "is_false")]
+        inward: bool,
+        #[serde(default, skip_serializing_if = "is_false")]
+        two_sided: bool,
+        #[serde(flatten)]
+        elevation: Elevation,
+    },
+    /// angles in degrees, from the x axis towards the y axis
+    Arc {
+        #[serde(serialize_with = "serialize_point")]
+        center: Vec2,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        #[serde(default, skip_serializing_if = "is_false")]
+        inward: bool,
+        #[serde(default, skip_serializing_if = "is_false")]
+        two_sided: bool,
+        #[serde(flatten)]
+        elevation: Elevation,
+    },
+    /// closed convex polygon
+    Polygon {
+        #[serde(serialize_with = "serialize_points")]
+        points: Vec<Vec2>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        two_sided: bool,
+        #[serde(flatten)]
+        elevation: Elevation,
+    },
+}
+
+impl PrimitiveGeometry {
+    pub fn elevation(&self) -> Elevation {
+        match *self {
+            PrimitiveGeometry::Plane{ elevation, .. }
+            | PrimitiveGeometry::Cylinder{ elevation, .. }
+            | PrimitiveGeometry::Arc{ elevation, .. }
+            | PrimitiveGeometry::Polygon{ elevation, .. } => elevation,
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeometryKind {
+    #[default]
+    Static,
+    Door {
+        #[serde(serialize_with = "serialize_point")]
+        slide: Vec2,
+        open_time: f32,
+        close_delay: f32,
+    },
+    PushWall {
+        distance: usize,
+        move_time: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Geometry {
     pub name: String,
     pub symbol: char,
+    #[serde(default, skip_serializing_if = "GeometryKind::is_static")]
+    pub kind: GeometryKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub primitives: Option<Vec<PrimitiveGeometry>>,
 }
 
-#[derive(Deserialize, Debug)]
+impl GeometryKind {
+    pub fn is_static(&self) -> bool {
+        *self == GeometryKind::Static
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct GeometrySet {
     pub geometry_set: Vec<Geometry>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct GeometryMap {
     pub geometry_set: String,
     pub spawn_symbol_set: String,
-    pub map: Vec<String>,
+    /// primitives in map coordinates, which may span several tiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primitives: Vec<PrimitiveGeometry>,
+    pub map: TileMap,
 }
\ No newline at end of file
@@ -0,0 +1,194 @@
+use super::*;
+
+use serde::Serialize;
+
+use self::{
+    geometry::{
+        GeometryMap,
+        PrimitiveGeometry,
+    },
+    material::{
+        MaterialMap,
+        Fog,
+        Lighting,
+    },
+};
+
+/// The tiles of a single-file level.
+/// Each line is either a string alternating geometry and material symbols, or an array of [geometry, material] name pairs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LevelTiles {
+    Symbols(Vec<String>),
+    Names(Vec<Vec<[String; 2]>>),
+}
+
+/// a level whose geometry map, material map and metadata are held in a single `level.json` file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LevelFile {
+    pub geometry_set: String,
+    pub spawn_symbol_set: String,
+    pub material_set: String,
+    pub portal_symbol_set: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bonus_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skybox: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fog: Option<Fog>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<Lighting>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primitives: Vec<PrimitiveGeometry>,
+    pub map: LevelTiles,
+}
+
+impl LevelFile {
+    pub fn split(self) -> Result<(GeometryMap, MaterialMap), Box<dyn Error>> {
+        let (geo_map, mtl_map) = match self.map {
+            LevelTiles::Symbols(lines) => {
+                if let Some(line) = lines.iter().position(|line| line.chars().count() % 2 != 0) {
+                    return Err(format!("line {}: odd number of symbols", line).into());
+                }
+                let split_line = |line: &String, parity: usize| line.chars().skip(parity).step_by(2).collect();
+                (
+                    TileMap::Symbols(lines.iter().map(|line| split_line(line, 0)).collect()),
+                    TileMap::Symbols(lines.iter().map(|line| split_line(line, 1)).collect()),
+                )
+            },
+            LevelTiles::Names(lines) => (
+                TileMap::Names(lines.iter().map(|line| line.iter().map(|tile| tile[0].clone()).collect()).collect()),
+                TileMap::Names(lines.iter().map(|line| line.iter().map(|tile| tile[1].clone()).collect()).collect()),
+            ),
+        };
+        Ok((
+            GeometryMap {
+                geometry_set: self.geometry_set,
+                spawn_symbol_set: self.spawn_symbol_set,
+                primitives: self.primitives,
+                map: geo_map,
+            },
+            MaterialMap {
+                material_set: self.material_set,
+                portal_symbol_set: self.portal_symbol_set,
+                previous_level: self.previous_level,
+                next_level: self.next_level,
+                bonus_level: self.bonus_level,
+                skybox: self.skybox,
+                fog: self.fog,
+                lighting: self.lighting,
+                map: mtl_map,
+            },
+        ))
+    }
+
+    /// the two maps must have the same dimensions and the same encoding
+    pub fn join(geo_map: GeometryMap, mtl_map: MaterialMap) -> Result<Self, Box<dyn Error>> {
+        let map = match (geo_map.map, mtl_map.map) {
+            (TileMap::Symbols(geo_lines), TileMap::Symbols(mtl_lines)) => {
+                check_dimensions(&geo_lines, &mtl_lines, |line: &String| line.chars().count())?;
+                LevelTiles::Symbols(geo_lines.iter().zip(&mtl_lines).map(|(geo_line, mtl_line)| {
+                    geo_line.chars().zip(mtl_line.chars()).flat_map(|(geo_symbol, mtl_symbol)| vec![geo_symbol, mtl_symbol]).collect()
+                }).collect())
+            },
+            (TileMap::Names(geo_lines), TileMap::Names(mtl_lines)) => {
+                check_dimensions(&geo_lines, &mtl_lines, |line: &Vec<String>| line.len())?;
+                LevelTiles::Names(geo_lines.into_iter().zip(mtl_lines).map(|(geo_line, mtl_line)| {
+                    geo_line.into_iter().zip(mtl_line).map(|(geo_name, mtl_name)| [geo_name, mtl_name]).collect()
+                }).collect())
+            },
+            _ => return Err("the geometry map and the material map have different encodings".into()),
+        };
+        Ok(Self {
+            geometry_set: geo_map.geometry_set,
+            spawn_symbol_set: geo_map.spawn_symbol_set,
+            material_set: mtl_map.material_set,
+            portal_symbol_set: mtl_map.portal_symbol_set,
+            previous_level: mtl_map.previous_level,
+            next_level: mtl_map.next_level,
+            bonus_level: mtl_map.bonus_level,
+            skybox: mtl_map.skybox,
+            fog: mtl_map.fog,
+            lighting: mtl_map.lighting,
+            primitives: geo_map.primitives,
+            map,
+        })
+    }
+}
+
+fn check_dimensions<T, F: Fn(&T) -> usize>(geo_lines: &[T], mtl_lines: &[T], width: F) -> Result<(), Box<dyn Error>> {
+    if geo_lines.len() != mtl_lines.len() {
+        return Err("map dimensions mismatch: different number of lines".into());
+    }
+    if let Some(line) = (0..geo_lines.len()).find(|&line| width(&geo_lines[line]) != width(&mtl_lines[line])) {
+        return Err(format!("map dimensions mismatch: different lengths of line {}", line).into());
+    }
+    Ok(())
+}
+
+/// The asset files of the map of a level: `level.json`, or `geometry_map.json` and `material_map.json`.
+/// Both forms are read into a geometry map and a material map.
+#[derive(Debug)]
+pub struct LevelFiles {
+    pub geo_map: GeometryMap,
+    pub mtl_map: MaterialMap,
+    /// the maps are read from, and written to, `level.json`
+    pub single_file: bool,
+}
+
+impl LevelFiles {
+    pub const LEVEL_FILE: &'static str = "level";
+    pub const GEOMETRY_MAP_FILE: &'static str = "geometry_map";
+    pub const MATERIAL_MAP_FILE: &'static str = "material_map";
+
+    pub fn read<P: AsRef<Path>>(level_name: P) -> Result<Self, Box<dyn Error>> {
+        let dirs = [Path::new("maps"), level_name.as_ref()];
+
+        if let Some(reader) = read_optional_assets_file(&dirs, Self::LEVEL_FILE)? {
+            if assets_path(&dirs, format!("{}.json", Self::GEOMETRY_MAP_FILE)).exists() {
+                return Err(format!("{}.json and {}.json both hold the map", Self::LEVEL_FILE, Self::GEOMETRY_MAP_FILE).into());
+            }
+            let level_file: LevelFile = serde_json::from_reader(reader)
+                .map_err(|err| format!("{}: {}", Self::LEVEL_FILE, err))?;
+            let (geo_map, mtl_map) = level_file.split()
+                .map_err(|err| format!("{}: {}", Self::LEVEL_FILE, err))?;
+            return Ok(Self { geo_map, mtl_map, single_file: true });
+        }
+
+        let geo_map_reader = read_assets_file(&dirs, Self::GEOMETRY_MAP_FILE)?;
+        let geo_map: GeometryMap = serde_json::from_reader(geo_map_reader)
+            .map_err(|err| format!("{}: {}", Self::GEOMETRY_MAP_FILE, err))?;
+        let mtl_map_reader = read_assets_file(&dirs, Self::MATERIAL_MAP_FILE)?;
+        let mtl_map: MaterialMap = serde_json::from_reader(mtl_map_reader)
+            .map_err(|err| format!("{}: {}", Self::MATERIAL_MAP_FILE, err))?;
+        Ok(Self { geo_map, mtl_map, single_file: false })
+    }
+
+    /// writes the maps in the form given by `single_file`, and removes the files of the other form
+    pub fn write<P: AsRef<Path>>(self, level_name: P) -> Result<(), Box<dyn Error>> {
+        let dirs = [Path::new("maps"), level_name.as_ref()];
+        let remove = |file_name: &str| -> Result<(), Box<dyn Error>> {
+            let path = assets_path(&dirs, format!("{}.json", file_name));
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        };
+
+        if self.single_file {
+            let level_file = LevelFile::join(self.geo_map, self.mtl_map)?;
+            write_map_file(&dirs, Self::LEVEL_FILE, &level_file)?;
+            remove(Self::GEOMETRY_MAP_FILE)?;
+            remove(Self::MATERIAL_MAP_FILE)?;
+        } else {
+            write_map_file(&dirs, Self::GEOMETRY_MAP_FILE, &self.geo_map)?;
+            write_map_file(&dirs, Self::MATERIAL_MAP_FILE, &self.mtl_map)?;
+            remove(Self::LEVEL_FILE)?;
+        }
+        Ok(())
+    }
+}
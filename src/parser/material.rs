@@ -1,21 +1,27 @@
 use super::*;
 
-#[derive(Deserialize, Debug)]
+use serde::Serialize;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct PortalSymbolSet {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub previous_level_symbol: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_level_symbol: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub bonus_level_symbol: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub end_symbol: Option<char>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Shading {
     Texture(String),
     Color(String),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PhysicsProperties {
     Reflection,
@@ -24,12 +30,12 @@ pub enum PhysicsProperties {
     Absorption,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MaterialProperties {
     pub physics_properties: PhysicsProperties,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Material {
     pub name: String,
     pub symbol: char,
@@ -37,18 +43,54 @@ pub struct Material {
     pub shading: Shading,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MaterialSet {
     pub material_set: Vec<Material>,
 }
 
-#[derive(Deserialize, Debug)]
+/// distance fog, from no fog at `start` to the whole fog at `end`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fog {
+    /// "0xRRGGBBAA", the alpha is the opacity of the whole fog
+    pub color: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// a light whose color fades out at `radius`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Light {
+    #[serde(serialize_with = "geometry::serialize_point")]
+    pub position: Vec2,
+    /// "0xRRGGBBAA", the alpha is the intensity of the light
+    pub color: String,
+    pub radius: f32,
+}
+
+/// the lights of a level, baked on the walls when it is loaded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lighting {
+    /// "0xRRGGBBAA", the light of the walls that no light reaches
+    pub ambient: String,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MaterialMap {
     pub material_set: String,
     pub portal_symbol_set: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub previous_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub bonus_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub skybox: Option<Vec<String>>,
-    pub map: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fog: Option<Fog>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<Lighting>,
+    pub map: TileMap,
 }
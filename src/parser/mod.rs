@@ -9,14 +9,32 @@ pub use std::path::{
     PathBuf,
 };
 
-use math_2d::Vec2;
+use math_2d::{
+    Vec2,
+    Elevation,
+};
 
-use serde::Deserialize;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::ser::{
+    CompactFormatter,
+    Formatter,
+    PrettyFormatter,
+};
+use std::io;
 
 pub mod material;
 pub mod geometry;
+pub mod trigger;
+pub mod save;
+pub mod level;
+pub mod tiled;
+pub mod wolf3d;
 
-pub fn read_assets_file<P, S>(dirs: &[P], file_name: S) -> Result<BufReader<impl Read>, Box<dyn Error>>
+/// path of a file of the assets directory
+pub fn assets_path<P, S>(dirs: &[P], file_name: S) -> PathBuf
     where
         P: AsRef<Path>,
         S: AsRef<Path>
@@ -26,19 +44,210 @@ pub fn read_assets_file<P, S>(dirs: &[P], file_name: S) -> Result<BufReader<impl
         file.push(dir);
     }
     file.push(file_name);
+    file
+}
+
+pub fn read_assets_file<P, S>(dirs: &[P], file_name: S) -> Result<BufReader<impl Read>, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+        S: AsRef<Path>
+{
+    let mut file = assets_path(dirs, file_name);
     file.set_extension("json");
     let file = File::open(file)?;
     Ok(BufReader::new(file))
 }
 
-pub fn check_for_duplicate_symbols(symbols: &str) -> Option<char> {
-    let symbols: Vec<char> = symbols.chars().collect();
-    if symbols.len() < 2 {
-        return None;
+/// same as read_assets_file, but a missing file is not an error
+pub fn read_optional_assets_file<P, S>(dirs: &[P], file_name: S) -> Result<Option<BufReader<impl Read>>, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+        S: AsRef<Path>
+{
+    match read_assets_file(dirs, file_name) {
+        Ok(reader) => Ok(Some(reader)),
+        Err(err) => match err.downcast_ref::<std::io::Error>() {
+            Some(io_err) if io_err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            _ => Err(err),
+        },
+    }
+}
+
+/// all the symbols appearing more than once, in order of first appearance
+pub fn duplicate_symbols(symbols: &str) -> Vec<char> {
+    let mut seen = Vec::new();
+    let mut duplicates = Vec::new();
+    for symbol in symbols.chars() {
+        if seen.contains(&symbol) {
+            if !duplicates.contains(&symbol) {
+                duplicates.push(symbol);
+            }
+        } else {
+            seen.push(symbol);
+        }
+    }
+    duplicates
+}
+
+/// The tiles of a geometry or material map.
+/// Each line of the map is either a string of symbols, or an array of tile names such as "nw_corner".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TileMap {
+    Symbols(Vec<String>),
+    Names(Vec<Vec<String>>),
+}
+
+impl TileMap {
+    /// the lines of symbols of the map, `names[idx]` being the name of the tile of symbol `symbols[idx]`
+    pub fn to_symbols(&self, names: &[String], symbols: &[char]) -> Result<Vec<String>, Box<dyn Error>> {
+        match self {
+            TileMap::Symbols(lines) => Ok(lines.clone()),
+            TileMap::Names(lines) => {
+                let mut unknown = vec![];
+                let symbol_lines = lines.iter().enumerate().map(|(line, tile_names)| {
+                    tile_names.iter().enumerate().map(|(col, name)| {
+                        match names.iter().position(|tile_name| tile_name == name) {
+                            Some(idx) if names.iter().rposition(|tile_name| tile_name == name) == Some(idx) => symbols[idx],
+                            Some(_) => {
+                                unknown.push(format!("ambiguous tile name '{}' at tile {}:{}", name, line, col));
+                                char::REPLACEMENT_CHARACTER
+                            },
+                            None => {
+                                unknown.push(format!("unknown tile name '{}' at tile {}:{}", name, line, col));
+                                char::REPLACEMENT_CHARACTER
+                            },
+                        }
+                    }).collect()
+                }).collect();
+                if unknown.is_empty() {
+                    Ok(symbol_lines)
+                } else {
+                    Err(unknown.join(", ").into())
+                }
+            },
+        }
+    }
+
+    /// the lines of tile names of the map, `names[idx]` being the name of the tile of symbol `symbols[idx]`
+    pub fn to_names(&self, names: &[String], symbols: &[char]) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+        match self {
+            TileMap::Names(lines) => Ok(lines.clone()),
+            TileMap::Symbols(lines) => {
+                lines.iter().enumerate().map(|(line, symbol_line)| {
+                    symbol_line.chars().enumerate().map(|(col, symbol)| {
+                        match symbols.iter().position(|&tile_symbol| tile_symbol == symbol) {
+                            Some(idx) => Ok(names[idx].clone()),
+                            None => Err(format!("unknown symbol '{}' at tile {}:{}", symbol, line, col).into()),
+                        }
+                    }).collect()
+                }).collect()
+            },
+        }
     }
-    if let Some(idx) = (1..symbols.len()).find(|&i| symbols[i..].contains(&symbols[i - 1])) {
-        Some(symbols[idx - 1])
-    } else {
-        None
+}
+
+/// A pretty json formatter that writes an array held by another array on a single line,
+/// so that a map file has one line of the file per line of the map.
+struct MapFileFormatter {
+    pretty: PrettyFormatter<'static>,
+    /// for each open array or object, whether it is an array
+    containers: Vec<bool>,
+    /// number of the innermost open containers which are written on a single line
+    compact: usize,
+}
+
+/// calls a method of the compact formatter within the containers written on a single line, of the pretty one otherwise
+macro_rules! map_file_format {
+    ($formatter:expr, $method:ident($($arg:expr),*)) => {
+        if $formatter.compact > 0 {
+            CompactFormatter.$method($($arg),*)
+        } else {
+            $formatter.pretty.$method($($arg),*)
+        }
+    };
+}
+
+impl MapFileFormatter {
+    fn new() -> Self {
+        Self { pretty: PrettyFormatter::new(), containers: vec![], compact: 0 }
     }
-}
\ No newline at end of file
+
+    fn begin(&mut self, is_array: bool) {
+        if self.compact > 0 || (is_array && self.containers.last() == Some(&true)) {
+            self.compact += 1;
+        }
+        self.containers.push(is_array);
+    }
+
+    fn end(&mut self) {
+        self.containers.pop();
+        self.compact = self.compact.saturating_sub(1);
+    }
+}
+
+impl Formatter for MapFileFormatter {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(true);
+        map_file_format!(self, begin_array(writer))
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let result = map_file_format!(self, end_array(writer));
+        self.end();
+        result
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        map_file_format!(self, begin_array_value(writer, first))
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        map_file_format!(self, end_array_value(writer))
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(false);
+        map_file_format!(self, begin_object(writer))
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let result = map_file_format!(self, end_object(writer));
+        self.end();
+        result
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        map_file_format!(self, begin_object_key(writer, first))
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        map_file_format!(self, begin_object_value(writer))
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        map_file_format!(self, end_object_value(writer))
+    }
+}
+
+/// the json text of a map file, with one line of the file per line of the map
+pub fn map_file_json<T: Serialize>(map_file: &T) -> Result<String, Box<dyn Error>> {
+    let mut json = vec![];
+    map_file.serialize(&mut serde_json::Serializer::with_formatter(&mut json, MapFileFormatter::new()))?;
+    json.push(b'\n');
+    Ok(String::from_utf8(json)?)
+}
+
+/// writes a map file of the assets, see `map_file_json`
+pub fn write_map_file<T, P, S>(dirs: &[P], file_name: S, map_file: &T) -> Result<(), Box<dyn Error>>
+    where
+        T: Serialize,
+        P: AsRef<Path>,
+        S: AsRef<Path>
+{
+    let json = map_file_json(map_file)?;
+    let mut path = assets_path(dirs, file_name);
+    path.set_extension("json");
+    std::fs::write(path, json)?;
+    Ok(())
+}
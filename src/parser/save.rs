@@ -0,0 +1,94 @@
+use super::*;
+
+use serde::Serialize;
+
+/// version written in the saves, incremented when the format changes
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open { timer: f32 },
+    Closing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushWallState {
+    Idle,
+    Moving {
+        step_x: isize,
+        step_y: isize,
+        remaining: usize,
+    },
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Mover {
+    Door {
+        tile: [usize; 2],
+        state: DoorState,
+        progress: f32,
+    },
+    PushWall {
+        tile: [usize; 2],
+        state: PushWallState,
+        progress: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlayerSave {
+    pub position: Vec2,
+    pub direction: Vec2,
+    pub health: u32,
+    pub ammo: u32,
+}
+
+/// dynamic state of a level, on top of what its asset files describe
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LevelSave {
+    /// `[geo_idx, mtl_idx]` of every tile, changed by push walls and scripts
+    pub tiles: Vec<Vec<[usize; 2]>>,
+    pub movers: Vec<Mover>,
+    /// indices of the triggers that fired
+    pub fired_triggers: Vec<usize>,
+    /// the `this` object map of the script of the level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SaveGame {
+    pub version: u32,
+    pub level_name: String,
+    pub player: PlayerSave,
+    pub level: LevelSave,
+}
+
+/// brings a save written by an older version of the game to the current format
+fn migrate(save: serde_json::Value) -> Result<serde_json::Value, Box<dyn Error>> {
+    let version = match save.get("version").and_then(|version| version.as_u64()) {
+        Some(version) => version as u32,
+        None => return Err("not a save file: missing version".into()),
+    };
+    // the conversions from older versions go here, one version at a time
+    match version {
+        SAVE_VERSION => Ok(save),
+        version if version > SAVE_VERSION => {
+            Err(format!("save version {} is newer than the supported version {}", version, SAVE_VERSION).into())
+        },
+        version => Err(format!("save version {} is not supported anymore", version).into()),
+    }
+}
+
+impl SaveGame {
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let save: serde_json::Value = serde_json::from_reader(reader)?;
+        Ok(serde_json::from_value(migrate(save)?)?)
+    }
+}
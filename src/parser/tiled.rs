@@ -0,0 +1,446 @@
+use super::*;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use roxmltree::{
+    Document,
+    Node,
+};
+
+use self::{
+    geometry::GeometryMap,
+    material::MaterialMap,
+    level::LevelFiles,
+};
+
+/// tile layer of the geometries
+pub const GEOMETRY_LAYER: &str = "geometry";
+/// tile layer of the materials and portals
+pub const MATERIAL_LAYER: &str = "material";
+/// object layer of the spawns
+pub const SPAWN_LAYER: &str = "spawns";
+/// tile property holding the name of the geometry or material of a tile
+const NAME_PROPERTY: &str = "name";
+/// spawn object property holding the player number
+const PLAYER_PROPERTY: &str = "player";
+/// flip and rotation flags stored in the high bits of the tile ids of the layers
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+struct Tileset {
+    first_gid: u32,
+    /// tile names, by local tile id
+    names: HashMap<u32, String>,
+}
+
+struct TileLayer {
+    name: String,
+    gids: Vec<u32>,
+}
+
+struct SpawnObject {
+    /// center of the object, in pixels
+    x: f32,
+    y: f32,
+    player: usize,
+}
+
+/// A map of the Tiled editor, read from a TMX or JSON file.
+/// The tiles of its "geometry" and "material" layers are named by their "name" property,
+/// and the objects of its "spawns" layer have a "player" property.
+pub struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f32,
+    tile_height: f32,
+    properties: HashMap<String, String>,
+    tilesets: Vec<Tileset>,
+    layers: Vec<TileLayer>,
+    spawns: Vec<SpawnObject>,
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    #[serde(default)]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<JsonTile>,
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    data: Option<Value>,
+    encoding: Option<String>,
+    compression: Option<String>,
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    orientation: Option<String>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+    tilesets: Vec<JsonTileset>,
+    layers: Vec<JsonLayer>,
+}
+
+fn json_value_string(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn json_properties(properties: &[JsonProperty]) -> HashMap<String, String> {
+    properties.iter().map(|property| (property.name.clone(), json_value_string(&property.value))).collect()
+}
+
+fn xml_attribute<T: FromStr>(node: Node, name: &str) -> Result<T, Box<dyn Error>> {
+    node.attribute(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("missing or invalid attribute '{}' of <{}>", name, node.tag_name().name()).into())
+}
+
+fn xml_child<'a, 'input>(node: Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.tag_name().name() == tag)
+}
+
+fn xml_properties(node: Node) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    if let Some(properties_node) = xml_child(node, "properties") {
+        for property in properties_node.children().filter(|child| child.tag_name().name() == "property") {
+            // multiline string values are stored in the text of the element
+            let value = property.attribute("value").or_else(|| property.text()).unwrap_or("");
+            if let Some(name) = property.attribute("name") {
+                properties.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+    properties
+}
+
+fn xml_tile_names(tileset: Node) -> Result<HashMap<u32, String>, Box<dyn Error>> {
+    let mut names = HashMap::new();
+    for tile in tileset.children().filter(|child| child.tag_name().name() == "tile") {
+        if let Some(name) = xml_properties(tile).remove(NAME_PROPERTY) {
+            names.insert(xml_attribute(tile, "id")?, name);
+        }
+    }
+    Ok(names)
+}
+
+/// tile names of an external tileset file, TSX or JSON
+fn external_tile_names(path: &Path) -> Result<HashMap<u32, String>, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("tileset '{}': {}", path.display(), err))?;
+    if path.extension().is_some_and(|extension| extension == "tsx") {
+        let document = Document::parse(&text)?;
+        xml_tile_names(document.root_element())
+    } else {
+        let tileset: JsonTileset = serde_json::from_str(&text)?;
+        Ok(json_tile_names(&tileset))
+    }
+}
+
+fn json_tile_names(tileset: &JsonTileset) -> HashMap<u32, String> {
+    tileset.tiles.iter().filter_map(|tile| {
+        json_properties(&tile.properties).remove(NAME_PROPERTY).map(|name| (tile.id, name))
+    }).collect()
+}
+
+fn parse_csv(data: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    data.split(',')
+        .map(|gid| gid.trim())
+        .filter(|gid| !gid.is_empty())
+        .map(|gid| gid.parse().map_err(|_| format!("invalid tile id '{}'", gid).into()))
+        .collect()
+}
+
+/// little endian tile ids of an uncompressed base64 layer
+fn parse_base64(data: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data.bytes().filter(|byte| !byte.is_ascii_whitespace() && *byte != b'=') {
+        let value = ALPHABET.iter().position(|&symbol| symbol == byte)
+            .ok_or_else(|| format!("invalid base64 character '{}'", byte as char))?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    if bytes.len() % 4 != 0 {
+        return Err("the base64 layer data is not a list of 32 bits tile ids".into());
+    }
+    Ok(bytes.chunks(4).map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]])).collect())
+}
+
+fn check_compression(compression: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match compression {
+        None | Some("") => Ok(()),
+        Some(compression) => Err(format!("{} compressed layers are not supported, save the map with CSV layers", compression).into()),
+    }
+}
+
+impl TiledMap {
+    /// reads a `.tmx` file, or a `.tmj` or `.json` file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") => Self::from_tmx(&text, dir),
+            Some("tmj") | Some("json") => Self::from_json(&text, dir),
+            _ => Err(format!("'{}': expected a .tmx, .tmj or .json Tiled map", path.display()).into()),
+        }
+    }
+
+    /// `dir` is the directory of the external tilesets
+    fn from_tmx(text: &str, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let document = Document::parse(text)?;
+        let root = document.root_element();
+        if root.tag_name().name() != "map" {
+            return Err("not a Tiled map".into());
+        }
+        if root.attribute("infinite") == Some("1") {
+            return Err("infinite maps are not supported".into());
+        }
+        if root.attribute("orientation").is_some_and(|orientation| orientation != "orthogonal") {
+            return Err("only orthogonal maps are supported".into());
+        }
+
+        let mut map = Self {
+            width: xml_attribute(root, "width")?,
+            height: xml_attribute(root, "height")?,
+            tile_width: xml_attribute(root, "tilewidth")?,
+            tile_height: xml_attribute(root, "tileheight")?,
+            properties: xml_properties(root),
+            tilesets: vec![],
+            layers: vec![],
+            spawns: vec![],
+        };
+        map.check_size()?;
+        for node in root.children().filter(|child| child.is_element()) {
+            match node.tag_name().name() {
+                "tileset" => {
+                    let names = match node.attribute("source") {
+                        Some(source) => external_tile_names(&dir.join(source))?,
+                        None => xml_tile_names(node)?,
+                    };
+                    map.tilesets.push(Tileset { first_gid: xml_attribute(node, "firstgid")?, names });
+                },
+                "layer" => {
+                    let name = node.attribute("name").unwrap_or("").to_string();
+                    let data = xml_child(node, "data").ok_or_else(|| format!("layer '{}' without data", name))?;
+                    let text = data.text().unwrap_or("");
+                    let gids = match data.attribute("encoding") {
+                        Some("csv") => parse_csv(text)?,
+                        Some("base64") => {
+                            check_compression(data.attribute("compression"))?;
+                            parse_base64(text)?
+                        },
+                        None => data.children()
+                            .filter(|child| child.tag_name().name() == "tile")
+                            .map(|tile| tile.attribute("gid").unwrap_or("0").parse().map_err(|_| "invalid tile id".into()))
+                            .collect::<Result<_, Box<dyn Error>>>()?,
+                        Some(encoding) => return Err(format!("layer '{}': unknown encoding '{}'", name, encoding).into()),
+                    };
+                    map.layers.push(TileLayer { name, gids });
+                },
+                "objectgroup" if node.attribute("name") == Some(SPAWN_LAYER) => {
+                    for object in node.children().filter(|child| child.tag_name().name() == "object") {
+                        let x: f32 = xml_attribute(object, "x")?;
+                        let y: f32 = xml_attribute(object, "y")?;
+                        let width = object.attribute("width").and_then(|width| width.parse().ok()).unwrap_or(0.0);
+                        let height = object.attribute("height").and_then(|height| height.parse().ok()).unwrap_or(0.0);
+                        let player = xml_properties(object).get(PLAYER_PROPERTY).and_then(|player| player.parse().ok())
+                            .ok_or_else(|| format!("spawn object at {},{} without an integer '{}' property", x, y, PLAYER_PROPERTY))?;
+                        map.spawns.push(SpawnObject { x: x + width / 2.0, y: y + height / 2.0, player });
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(map)
+    }
+
+    /// `dir` is the directory of the external tilesets
+    fn from_json(text: &str, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let raw_map: JsonMap = serde_json::from_str(text)?;
+        if raw_map.infinite {
+            return Err("infinite maps are not supported".into());
+        }
+        if raw_map.orientation.as_ref().is_some_and(|orientation| orientation != "orthogonal") {
+            return Err("only orthogonal maps are supported".into());
+        }
+
+        let mut map = Self {
+            width: raw_map.width,
+            height: raw_map.height,
+            tile_width: raw_map.tilewidth,
+            tile_height: raw_map.tileheight,
+            properties: json_properties(&raw_map.properties),
+            tilesets: vec![],
+            layers: vec![],
+            spawns: vec![],
+        };
+        map.check_size()?;
+        for tileset in &raw_map.tilesets {
+            let names = match tileset.source {
+                Some(ref source) => external_tile_names(&dir.join(source))?,
+                None => json_tile_names(tileset),
+            };
+            map.tilesets.push(Tileset { first_gid: tileset.firstgid, names });
+        }
+        for layer in raw_map.layers {
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    let gids = match (layer.data, layer.encoding.as_deref()) {
+                        (Some(Value::Array(gids)), _) => gids.iter()
+                            .map(|gid| gid.as_u64().map(|gid| gid as u32).ok_or_else(|| format!("invalid tile id {}", gid).into()))
+                            .collect::<Result<_, Box<dyn Error>>>()?,
+                        (Some(Value::String(data)), Some("base64")) => {
+                            check_compression(layer.compression.as_deref())?;
+                            parse_base64(&data)?
+                        },
+                        _ => return Err(format!("layer '{}': unsupported data", layer.name).into()),
+                    };
+                    map.layers.push(TileLayer { name: layer.name, gids });
+                },
+                "objectgroup" if layer.name == SPAWN_LAYER => {
+                    for object in layer.objects {
+                        let player = json_properties(&object.properties).get(PLAYER_PROPERTY).and_then(|player| player.parse().ok())
+                            .ok_or_else(|| format!("spawn object at {},{} without an integer '{}' property", object.x, object.y, PLAYER_PROPERTY))?;
+                        map.spawns.push(SpawnObject { x: object.x + object.width / 2.0, y: object.y + object.height / 2.0, player });
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(map)
+    }
+
+    fn check_size(&self) -> Result<(), Box<dyn Error>> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("invalid map size {}x{}", self.width, self.height).into());
+        }
+        Ok(())
+    }
+
+    fn property(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        self.optional_property(name).ok_or_else(|| format!("missing map property '{}'", name).into())
+    }
+
+    fn optional_property(&self, name: &str) -> Option<String> {
+        self.properties.get(name).filter(|value| !value.is_empty()).cloned()
+    }
+
+    /// the tile names of a tile layer, `empty_name` being the name of the cells without a tile
+    fn layer_names(&self, layer_name: &str, empty_name: &str) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+        let layer = self.layers.iter().find(|layer| layer.name == layer_name)
+            .ok_or_else(|| format!("no tile layer named '{}'", layer_name))?;
+        if layer.gids.len() != self.width * self.height {
+            return Err(format!("layer '{}': {} tiles instead of {}", layer_name, layer.gids.len(), self.width * self.height).into());
+        }
+        layer.gids.chunks(self.width).enumerate().map(|(line, gids)| {
+            gids.iter().enumerate().map(|(col, &gid)| {
+                let gid = gid & !FLIP_FLAGS;
+                if gid == 0 {
+                    return Ok(empty_name.to_string());
+                }
+                self.tilesets.iter()
+                    .filter(|tileset| tileset.first_gid <= gid)
+                    .max_by_key(|tileset| tileset.first_gid)
+                    .and_then(|tileset| tileset.names.get(&(gid - tileset.first_gid)))
+                    .cloned()
+                    .ok_or_else(|| format!("layer '{}', tile {}:{}: no '{}' property", layer_name, line, col, NAME_PROPERTY).into())
+            }).collect()
+        }).collect()
+    }
+
+    /// The maps of a single-file level, with tile names.
+    /// The sets and the portal targets are the map properties of the same names.
+    pub fn into_level_files(self) -> Result<LevelFiles, Box<dyn Error>> {
+        let empty_geometry = self.optional_property("empty_geometry").unwrap_or_else(|| "empty".to_string());
+        let empty_material = self.optional_property("empty_material").unwrap_or_else(|| "default".to_string());
+        let mut geo_names = self.layer_names(GEOMETRY_LAYER, &empty_geometry)?;
+        let mtl_names = self.layer_names(MATERIAL_LAYER, &empty_material)?;
+
+        for spawn in &self.spawns {
+            let (tile_x, tile_y) = ((spawn.x / self.tile_width).floor(), (spawn.y / self.tile_height).floor());
+            if tile_x < 0.0 || tile_y < 0.0 || tile_x as usize >= self.width || tile_y as usize >= self.height {
+                return Err(format!("the spawn of player {} is out of the map", spawn.player).into());
+            }
+            let tile = &mut geo_names[tile_y as usize][tile_x as usize];
+            if *tile != empty_geometry {
+                return Err(format!("the spawn of player {} is on the non-empty tile {}:{}", spawn.player, tile_y, tile_x).into());
+            }
+            // the tile name of the spawns in the maps
+            *tile = format!("spawn_{}", spawn.player);
+        }
+
+        let skybox = self.optional_property("skybox")
+            .map(|skybox| skybox.split(',').map(|texture| texture.trim().to_string()).collect());
+        Ok(LevelFiles {
+            geo_map: GeometryMap {
+                geometry_set: self.property("geometry_set")?,
+                spawn_symbol_set: self.property("spawn_symbol_set")?,
+                primitives: vec![],
+                map: TileMap::Names(geo_names),
+            },
+            mtl_map: MaterialMap {
+                material_set: self.property("material_set")?,
+                portal_symbol_set: self.property("portal_symbol_set")?,
+                previous_level: self.optional_property("previous_level"),
+                next_level: self.optional_property("next_level"),
+                bonus_level: self.optional_property("bonus_level"),
+                skybox,
+                fog: None,
+                lighting: None,
+                map: TileMap::Names(mtl_names),
+            },
+            single_file: true,
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use super::*;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerCondition {
+    Enter,
+    Use,
+    Shoot,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerEvent {
+    OpenDoor([usize; 2]),
+    SpawnEnemies {
+        kind: String,
+        count: usize,
+        tile: [usize; 2],
+    },
+    Message(String),
+    ActivatePortal([usize; 2]),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Trigger {
+    pub tile: [usize; 2],
+    pub on: TriggerCondition,
+    #[serde(default)]
+    pub once: bool,
+    pub events: Vec<TriggerEvent>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TriggerMap {
+    pub triggers: Vec<Trigger>,
+}
@@ -0,0 +1,319 @@
+use super::*;
+
+use self::{
+    geometry::GeometryMap,
+    material::MaterialMap,
+    level::LevelFiles,
+};
+
+/// number of map offsets in MAPHEAD
+const MAX_MAPS: usize = 100;
+/// size of a map header in GAMEMAPS: 3 plane offsets, 3 plane lengths, width, height and a 16 bytes name
+const MAP_HEADER_SIZE: usize = 3 * 4 + 3 * 2 + 2 + 2 + 16;
+/// Carmack near and far pointer tags, in the high byte of a word
+const CARMACK_NEAR: u16 = 0xa7;
+const CARMACK_FAR: u16 = 0xa8;
+
+/// wall plane values
+const LAST_WALL: u16 = 89;
+const ELEVATOR_WALL: u16 = 21;
+const FIRST_DOOR: u16 = 90;
+const FIRST_ELEVATOR_DOOR: u16 = 100;
+const LAST_DOOR: u16 = 101;
+/// floor area of the secret elevators
+const SECRET_ELEVATOR_FLOOR: u16 = 107;
+
+/// object plane values
+const FIRST_PLAYER_START: u16 = 19;
+const LAST_PLAYER_START: u16 = 22;
+const PUSH_WALL: u16 = 98;
+const EXIT: u16 = 99;
+
+/// map index of the secret level of each episode
+const SECRET_MAP: usize = 9;
+/// map index of the boss of each episode, left through an exit
+const BOSS_MAP: usize = 8;
+/// map index reached from the secret level of each episode
+const ELEVATOR_BACK_TO: [usize; 6] = [1, 1, 7, 3, 5, 3];
+
+/// sets used by the imported levels
+pub const GEOMETRY_SET: &str = "default_geo_set";
+pub const SPAWN_SYMBOL_SET: &str = "default_spawn_symbols";
+pub const MATERIAL_SET: &str = "wolf3d_mtl_set";
+pub const PORTAL_SYMBOL_SET: &str = "default_portal_symbols";
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16, Box<dyn Error>> {
+    data.get(pos..pos + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| "unexpected end of data".into())
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, Box<dyn Error>> {
+    data.get(pos..pos + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| "unexpected end of data".into())
+}
+
+/// Expands Carmack compressed data, which starts with its expanded size in bytes.
+/// The words are either literal, or copies of earlier words: near pointers count back from the end of the output, far pointers from its start.
+fn carmack_expand(data: &[u8]) -> Result<Vec<u16>, Box<dyn Error>> {
+    let length = read_u16(data, 0)? as usize / 2;
+    let mut words: Vec<u16> = Vec::with_capacity(length);
+    let mut pos = 2;
+    while words.len() < length {
+        let word = read_u16(data, pos)?;
+        pos += 2;
+        let (tag, count) = (word >> 8, (word & 0xff) as usize);
+        if (tag == CARMACK_NEAR || tag == CARMACK_FAR) && count == 0 {
+            // a literal word whose high byte is a tag: its low byte follows
+            let low = *data.get(pos).ok_or("unexpected end of data")?;
+            pos += 1;
+            words.push(word | low as u16);
+        } else if tag == CARMACK_NEAR || tag == CARMACK_FAR {
+            let start = if tag == CARMACK_NEAR {
+                let offset = *data.get(pos).ok_or("unexpected end of data")? as usize;
+                pos += 1;
+                words.len().checked_sub(offset)
+            } else {
+                let offset = read_u16(data, pos)? as usize;
+                pos += 2;
+                Some(offset)
+            };
+            let start = start.filter(|&start| start < words.len()).ok_or("invalid Carmack pointer")?;
+            // the copied words may overlap the copy
+            for idx in start..start + count {
+                let word = *words.get(idx).ok_or("invalid Carmack pointer")?;
+                words.push(word);
+            }
+        } else {
+            words.push(word);
+        }
+    }
+    words.truncate(length);
+    Ok(words)
+}
+
+/// Expands RLEW compressed words, which start with their expanded size in bytes.
+/// A `tag` word is followed by a count and a value repeated count times.
+fn rlew_expand(words: &[u16], tag: u16) -> Result<Vec<u16>, Box<dyn Error>> {
+    let length = *words.first().ok_or("unexpected end of data")? as usize / 2;
+    let mut expanded = Vec::with_capacity(length);
+    let mut iter = words[1..].iter();
+    while expanded.len() < length {
+        let word = *iter.next().ok_or("unexpected end of data")?;
+        if word == tag {
+            let count = *iter.next().ok_or("unexpected end of data")? as usize;
+            let value = *iter.next().ok_or("unexpected end of data")?;
+            expanded.extend(std::iter::repeat_n(value, count));
+        } else {
+            expanded.push(word);
+        }
+    }
+    expanded.truncate(length);
+    Ok(expanded)
+}
+
+/// the file of `dir` named `name`, whatever the case of its name
+fn find_file(dir: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.file_name().and_then(|file_name| file_name.to_str()).is_some_and(|file_name| file_name.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| format!("no {} file in '{}'", name, dir.display()).into())
+}
+
+/// a map of the original game, with its wall plane and its object plane
+pub struct Wolf3dMap {
+    /// index of the map in MAPHEAD, 10 maps per episode
+    pub index: usize,
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub walls: Vec<u16>,
+    pub objects: Vec<u16>,
+}
+
+/// the maps of the MAPHEAD and GAMEMAPS files of the original game
+pub struct Wolf3dMaps {
+    rlew_tag: u16,
+    offsets: Vec<usize>,
+    gamemaps: Vec<u8>,
+}
+
+impl Wolf3dMaps {
+    /// reads MAPHEAD.<extension> and GAMEMAPS.<extension> from `dir`, with an extension such as WL6 or WL1
+    pub fn read<P: AsRef<Path>>(dir: P, extension: &str) -> Result<Self, Box<dyn Error>> {
+        let maphead = std::fs::read(find_file(dir.as_ref(), &format!("MAPHEAD.{}", extension))?)?;
+        let gamemaps = std::fs::read(find_file(dir.as_ref(), &format!("GAMEMAPS.{}", extension))?)?;
+        let rlew_tag = read_u16(&maphead, 0).map_err(|_| "MAPHEAD: empty file")?;
+        // missing maps have a 0 or -1 offset
+        let offsets = (0..MAX_MAPS)
+            .map_while(|idx| read_u32(&maphead, 2 + idx * 4).ok())
+            .map(|offset| if offset == u32::MAX { 0 } else { offset as usize })
+            .collect();
+        Ok(Self { rlew_tag, offsets, gamemaps })
+    }
+
+    /// the indices of the maps present in the files
+    pub fn indices(&self) -> Vec<usize> {
+        (0..self.offsets.len()).filter(|&idx| self.offsets[idx] != 0).collect()
+    }
+
+    fn plane(&self, offset: usize, length: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+        let data = self.gamemaps.get(offset..offset + length).ok_or("plane out of GAMEMAPS")?;
+        rlew_expand(&carmack_expand(data)?, self.rlew_tag)
+    }
+
+    pub fn map(&self, index: usize) -> Result<Wolf3dMap, Box<dyn Error>> {
+        let offset = match self.offsets.get(index) {
+            Some(&offset) if offset != 0 => offset,
+            _ => return Err(format!("no map {}", index).into()),
+        };
+        let header = self.gamemaps.get(offset..offset + MAP_HEADER_SIZE).ok_or("map header out of GAMEMAPS")?;
+        let width = read_u16(header, 18)? as usize;
+        let height = read_u16(header, 20)? as usize;
+        let name = header[22..].iter().take_while(|&&byte| byte != 0).map(|&byte| byte as char).collect();
+        let plane = |plane: usize| -> Result<Vec<u16>, Box<dyn Error>> {
+            let words = self.plane(read_u32(header, plane * 4)? as usize, read_u16(header, 12 + plane * 2)? as usize)?;
+            if words.len() != width * height {
+                return Err(format!("map {}: plane {} has {} tiles instead of {}", index, plane, words.len(), width * height).into());
+            }
+            Ok(words)
+        };
+        Ok(Wolf3dMap {
+            index,
+            name,
+            width,
+            height,
+            walls: plane(0)?,
+            objects: plane(1)?,
+        })
+    }
+}
+
+impl Wolf3dMap {
+    fn wall(&self, tile_x: usize, tile_y: usize) -> u16 {
+        self.walls[tile_y * self.width + tile_x]
+    }
+
+    fn object(&self, tile_x: usize, tile_y: usize) -> u16 {
+        self.objects[tile_y * self.width + tile_x]
+    }
+
+    /// the level names of the maps, such as "wolf3d_e1m1" for the index 0 with the "wolf3d_" prefix
+    pub fn level_name(prefix: &str, index: usize) -> String {
+        format!("{}e{}m{}", prefix, index / 10 + 1, index % 10 + 1)
+    }
+
+    /// elevator switches next to a secret elevator floor lead to the secret level
+    fn is_secret_elevator(&self, tile_x: usize, tile_y: usize) -> bool {
+        [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|&(dx, dy): &(isize, isize)| {
+            let (x, y) = (tile_x as isize + dx, tile_y as isize + dy);
+            x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+                && self.wall(x as usize, y as usize) == SECRET_ELEVATOR_FLOOR
+        })
+    }
+
+    /// The maps of a single-file level, with tile names.
+    /// Walls become cells with a material per wall value, doors and push walls keep their kind,
+    /// the elevator switches are portals to the next or secret level and the exits are portals to the end.
+    pub fn into_level_files(self, prefix: &str) -> Result<LevelFiles, Box<dyn Error>> {
+        let mut geo_names = Vec::with_capacity(self.height);
+        let mut mtl_names = Vec::with_capacity(self.height);
+        let (mut next_level, mut bonus_level, mut spawn) = (false, false, false);
+
+        for tile_y in 0..self.height {
+            let mut geo_line = Vec::with_capacity(self.width);
+            let mut mtl_line = Vec::with_capacity(self.width);
+            for tile_x in 0..self.width {
+                let (wall, object) = (self.wall(tile_x, tile_y), self.object(tile_x, tile_y));
+                let (geometry, material) = match wall {
+                    ELEVATOR_WALL if self.is_secret_elevator(tile_x, tile_y) => {
+                        bonus_level = true;
+                        ("cell", String::from("bonus_level"))
+                    },
+                    ELEVATOR_WALL => {
+                        next_level = true;
+                        ("cell", String::from("next_level"))
+                    },
+                    1..=LAST_WALL if object == PUSH_WALL => ("push_wall", format!("wall_{}", wall)),
+                    1..=LAST_WALL => ("cell", format!("wall_{}", wall)),
+                    FIRST_DOOR..=LAST_DOOR => {
+                        // the even doors are vertical on the map, they are crossed from east to west
+                        let geometry = if wall % 2 == 0 { "ew_door" } else { "ns_door" };
+                        (geometry, String::from(if wall >= FIRST_ELEVATOR_DOOR { "elevator_door" } else { "door" }))
+                    },
+                    _ if object == EXIT => ("cell", String::from("end")),
+                    _ if (FIRST_PLAYER_START..=LAST_PLAYER_START).contains(&object) && !spawn => {
+                        spawn = true;
+                        ("spawn_1", String::from("default"))
+                    },
+                    _ => ("empty", String::from("default")),
+                };
+                geo_line.push(geometry.to_string());
+                mtl_line.push(material);
+            }
+            geo_names.push(geo_line);
+            mtl_names.push(mtl_line);
+        }
+        if !spawn {
+            return Err(format!("map {} '{}': no player start", self.index, self.name).into());
+        }
+
+        let (episode, map) = (self.index / 10, self.index % 10);
+        let next_map = match map {
+            SECRET_MAP => ELEVATOR_BACK_TO.get(episode).cloned(),
+            BOSS_MAP => None,
+            _ => Some(map + 1),
+        };
+        Ok(LevelFiles {
+            geo_map: GeometryMap {
+                geometry_set: GEOMETRY_SET.to_string(),
+                spawn_symbol_set: SPAWN_SYMBOL_SET.to_string(),
+                primitives: vec![],
+                map: TileMap::Names(geo_names),
+            },
+            mtl_map: MaterialMap {
+                material_set: MATERIAL_SET.to_string(),
+                portal_symbol_set: PORTAL_SYMBOL_SET.to_string(),
+                previous_level: None,
+                next_level: next_map.filter(|_| next_level).map(|next_map| Self::level_name(prefix, episode * 10 + next_map)),
+                bonus_level: Some(Self::level_name(prefix, episode * 10 + SECRET_MAP)).filter(|_| bonus_level),
+                skybox: None,
+                fog: None,
+                lighting: None,
+                map: TileMap::Names(mtl_names),
+            },
+            single_file: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_carmack_expand() {
+        let data = [
+            16, 0, // 8 words
+            0x01, 0x00, 0x02, 0x00,
+            0x02, 0xa7, 0x02, // 2 words from the 2nd word before the end
+            0x03, 0xa8, 0x01, 0x00, // 3 words from the word 1
+            0x00, 0xa7, 0x05, // the literal word 0xa705
+        ];
+        assert_eq!(carmack_expand(&data).unwrap(), vec![1, 2, 1, 2, 2, 1, 2, 0xa705]);
+        // a near copy overlapping itself repeats the last word
+        assert_eq!(carmack_expand(&[8, 0, 0x07, 0x00, 0x03, 0xa7, 0x01]).unwrap(), vec![7, 7, 7, 7]);
+        assert!(carmack_expand(&[4, 0, 0x07, 0x00, 0x01, 0xa7, 0x02]).is_err());
+        assert!(carmack_expand(&[4, 0, 0x07, 0x00, 0x01, 0xa8, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_rlew_expand() {
+        const TAG: u16 = 0xabcd;
+        assert_eq!(rlew_expand(&[12, 7, TAG, 4, 9, 8], TAG).unwrap(), vec![7, 9, 9, 9, 9, 8]);
+        assert_eq!(rlew_expand(&[4, TAG, 0, 1, 2, TAG, 1, TAG], TAG).unwrap(), vec![2, TAG]);
+        assert!(rlew_expand(&[6, 7, TAG, 2], TAG).is_err());
+    }
+}
@@ -3,6 +3,7 @@ use timer;
 use math_2d::{
     Vec2,
     Mat2,
+    Ray,
 };
 
 use level::{
@@ -10,6 +11,11 @@ use level::{
     GeometrySet,
 };
 
+use cvars::{
+    CVar,
+    CVarRegistry,
+};
+
 mod input {
 
     pub use controls::controls::*;
@@ -19,7 +25,9 @@ mod input {
             _press: ControlHandle<'a>,
             _release: ControlHandle<'a>,
         },
-        _OneTouch(ControlHandle<'a>),
+        OneTouch {
+            _press: ControlHandle<'a>,
+        },
     }
 
     pub struct Key<'a> {
@@ -49,8 +57,41 @@ mod input {
             let _release = set_action(event, false)?;
             Ok(Self { active, _handle: KeyHandle::Hold{ _press, _release } })
         }
+
+        /// the key stays active after being pressed, until its owner resets it
+        pub fn one_touch(control_handler: &mut ControlHandler<'a>, scancode: Scancode) -> Result<Self, Box<dyn Error>> {
+            let active = Rc::new(Cell::new(false));
+
+            let clone_active = Rc::downgrade(&active);
+            let action = Box::new(move |_: Event| { clone_active.upgrade().unwrap().set(true); });
+            let event = Event::KeyDown {
+                timestamp: 0, window_id: 0, keycode: None, scancode: Some(scancode), keymod: Mod::NOMOD, repeat: false
+            };
+            let _press = control_handler.add_control(ControlManagerType::Game, event, action)?;
+            Ok(Self { active, _handle: KeyHandle::OneTouch{ _press } })
+        }
     }
     
+    pub struct Button<'a> {
+        pub active: Rc<Cell<bool>>,
+        _handle: ControlHandle<'a>,
+    }
+
+    impl<'a> Button<'a> {
+        /// the button stays active after being pressed, until its owner resets it
+        pub fn one_touch(control_handler: &mut ControlHandler<'a>, mouse_btn: MouseButton) -> Result<Self, Box<dyn Error>> {
+            let active = Rc::new(Cell::new(false));
+
+            let clone_active = Rc::downgrade(&active);
+            let action = Box::new(move |_: Event| { clone_active.upgrade().unwrap().set(true); });
+            let event = Event::MouseButtonDown {
+                timestamp: 0, window_id: 0, which: 0, mouse_btn, clicks: 1, x: 0, y: 0
+            };
+            let _handle = control_handler.add_control(ControlManagerType::Game, event, action)?;
+            Ok(Self { active, _handle })
+        }
+    }
+
     pub struct MouseMotion<'a> {
         pub xrel: Rc<Cell<i32>>,
         pub yrel: Rc<Cell<i32>>,
@@ -93,6 +134,8 @@ pub struct PlayerActions<'a> {
     left: Key<'a>,
     backward: Key<'a>,
     right: Key<'a>,
+    interact: Key<'a>,
+    shoot: Button<'a>,
     turn: MouseMotion<'a>,
 }
 
@@ -103,37 +146,129 @@ impl<'a> PlayerActions<'a> {
             left: Key::new(control_handler, Scancode::A)?,
             backward: Key::new(control_handler, Scancode::S)?,
             right: Key::new(control_handler, Scancode::D)?,
+            interact: Key::one_touch(control_handler, Scancode::E)?,
+            shoot: Button::one_touch(control_handler, MouseButton::Left)?,
             turn: MouseMotion::new(control_handler)?,
         })
     }
+
+    /// releases the held keys, whose key up events are lost when another control manager is active
+    pub fn release(&self) {
+        for key in &[&self.forward, &self.left, &self.backward, &self.right] {
+            key.active.set(false);
+        }
+        self.interact.active.set(false);
+        self.shoot.active.set(false);
+        self.turn.xrel.set(0);
+        self.turn.yrel.set(0);
+    }
+}
+
+/// what a player asks the level to do
+#[derive(Debug, Clone, Copy)]
+pub enum PlayerCommand {
+    Use {
+        tile_x: usize,
+        tile_y: usize,
+        dir: Vec2,
+    },
+    Shoot(Ray),
 }
 
 pub struct Player<'a> {
     pub position: Vec2,
     pub direction: Vec2,
-    move_speed: f32,
-    turn_speed: f32,
+    pub health: u32,
+    pub ammo: u32,
+    /// tiles per second
+    pub move_speed: CVar<f32>,
+    /// radians per second
+    pub turn_speed: CVar<f32>,
+    /// the player walks through walls
+    pub noclip: CVar<bool>,
+    /// the player takes no damage
+    pub god: CVar<bool>,
     actions: PlayerActions<'a>,
 }
 
 impl<'a> Player<'a> {
+    /// how far from the player a tile can be used, in tiles
+    const USE_RANGE: f32 = 1.0;
+    /// the player is a disc of this radius for the collisions with the walls
+    const RADIUS: f32 = 0.2;
+    const MAX_HEALTH: u32 = 100;
+    const START_AMMO: u32 = 8;
+
     pub fn new(spawn_pos: Vec2, direction: Vec2, control_handler: &mut ControlHandler<'a>) -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             position: spawn_pos,
             direction,
-            move_speed: 1.5, // tiles per second
-            turn_speed: 0.524, // radians per second
+            health: Self::MAX_HEALTH,
+            ammo: Self::START_AMMO,
+            move_speed: CVar::with_bounds(1.5, 0.0, 20.0),
+            turn_speed: CVar::with_bounds(0.524, 0.0, 10.0),
+            noclip: CVar::new(false),
+            god: CVar::new(false),
             actions: PlayerActions::new(control_handler)?,
         })
     }
 
-    pub fn update(&mut self, map: &Map, geo_set: &GeometrySet) {
+    /// puts the player back on a spawn, with full health and ammunition
+    pub fn respawn(&mut self, position: Vec2) {
+        self.position = position;
+        self.direction = Vec2 { x: 0.0, y: 1.0 };
+        self.health = Self::MAX_HEALTH;
+        self.ammo = Self::START_AMMO;
+    }
+
+    pub fn register_cvars(&self, cvars: &mut CVarRegistry) -> Result<(), Box<dyn Error>> {
+        cvars.register("move_speed", "player speed, in tiles per second", &self.move_speed)?;
+        cvars.register("turn_speed", "mouse sensitivity, in radians per second", &self.turn_speed)?;
+        cvars.register("noclip", "lets the player walk through walls", &self.noclip)?;
+        cvars.register("god", "makes the player invulnerable", &self.god)
+    }
+
+    /// releases the held keys, when the game loses the input focus
+    pub fn release_actions(&self) {
+        self.actions.release();
+    }
+
+    /// removes health, unless god mode is on
+    pub fn damage(&mut self, amount: u32) {
+        if !self.god.get() {
+            self.health = self.health.saturating_sub(amount);
+        }
+    }
+
+    /// returns the commands the level has to execute for the player
+    pub fn update(&mut self, map: &Map, geo_set: &GeometrySet) -> Vec<PlayerCommand> {
         self.update_position(map, geo_set);
         self.update_direction();
+        self.update_commands()
+    }
+
+    fn update_commands(&mut self) -> Vec<PlayerCommand> {
+        let mut commands = vec![];
+        if self.actions.interact.active.replace(false) {
+            // the tile in front of the player
+            let target = self.position + self.direction * Self::USE_RANGE;
+            if target.x >= 0.0 && target.y >= 0.0 {
+                commands.push(PlayerCommand::Use {
+                    tile_x: target.x as usize,
+                    tile_y: target.y as usize,
+                    dir: self.direction,
+                });
+            }
+        }
+        if self.actions.shoot.active.replace(false) && self.ammo > 0 {
+            self.ammo -= 1;
+            commands.push(PlayerCommand::Shoot(Ray::new(self.position, self.direction)));
+        }
+        commands
     }
 
     fn update_direction(&mut self) {
-        let angle = self.actions.turn.xrel.get() as f32 * self.turn_speed * timer::get_delta_time();
+        let angle = self.actions.turn.xrel.get() as f32 * self.turn_speed.get() * timer::get_delta_time();
         let rot_matrix = Mat2::rotation_matrix(angle);
         
         // rotation matrix:
@@ -162,64 +297,18 @@ impl<'a> Player<'a> {
         }
         move_direction.normalize();
 
-        let old_x = self.position.x as isize;
-        let old_y = self.position.y as isize;
-        self.position += move_direction * self.move_speed * timer::get_delta_time();
-        let new_x = self.position.x as isize;
-        let new_y = self.position.y as isize;
-        
-        // temporary
-        // collision solution with tiles (not with geometry primitives)
-        let delta = 0.001f32;
-        if move_direction.x < 0.0 {
-            if new_x < 0 || (new_x == old_x && old_x == 0) {
-                if self.position.x < delta {
-                    self.position.x = delta;
-                }
-            } else if (new_x != old_x
-                    && geo_set.geometries[map.tiles[old_y as usize][new_x as usize].geo_idx].is_some())
-                || (new_x == old_x
-                    && geo_set.geometries[map.tiles[old_y as usize][(old_x - 1) as usize].geo_idx].is_some() 
-                    && self.position.x < old_x as f32 + delta) {
-                self.position.x = old_x as f32 + delta;
-            }
-        } else {
-            if new_x >= map.width as isize || (new_x == old_x && old_x == (map.width - 1) as isize) {
-                if self.position.x > map.width as f32 - delta {
-                    self.position.x = map.width as f32 - delta;
-                }
-            } else if (new_x != old_x
-                    && geo_set.geometries[map.tiles[old_y as usize][new_x as usize].geo_idx].is_some())
-                || (new_x == old_x
-                    && geo_set.geometries[map.tiles[old_y as usize][(old_x + 1) as usize].geo_idx].is_some() 
-                    && self.position.x > old_x as f32 + 1.0 - delta) {
-                self.position.x = old_x as f32 + 1.0 - delta;
-            }
+        let step = move_direction * self.move_speed.get() * timer::get_delta_time();
+        if self.noclip.get() {
+            // stays inside the map
+            self.position += step;
+            self.position.x = self.position.x.max(0.0).min(map.width as f32 - 0.001);
+            self.position.y = self.position.y.max(0.0).min(map.height as f32 - 0.001);
+            return;
         }
-        if move_direction.y < 0.0 {
-            if new_y < 0 || (new_y == old_y && old_y == 0) {
-                if self.position.y < delta {
-                    self.position.y = delta;
-                }
-            } else if (new_y != old_y
-                    && geo_set.geometries[map.tiles[new_y as usize][old_x as usize].geo_idx].is_some())
-                || (new_y == old_y
-                    && geo_set.geometries[map.tiles[(old_y - 1) as usize][old_x as usize].geo_idx].is_some() 
-                    && self.position.y < old_y as f32 + delta) {
-                self.position.y = old_y as f32 + delta;
-            }
-        } else {
-            if new_y >= map.height as isize || (new_y == old_y && old_y == (map.height - 1) as isize) {
-                if self.position.y > map.height as f32 - delta {
-                    self.position.y = map.height as f32 - delta;
-                }
-            } else if (new_y != old_y
-                    && geo_set.geometries[map.tiles[new_y as usize][old_x as usize].geo_idx].is_some())
-                || (new_y == old_y
-                    && geo_set.geometries[map.tiles[(old_y + 1) as usize][old_x as usize].geo_idx].is_some() 
-                    && self.position.y > old_y as f32 + 1.0 - delta) {
-                self.position.y = old_y as f32 + 1.0 - delta;
-            }
+        // the move is cut into steps shorter than the radius of the player, so that it does not go through thin walls
+        let steps = (step.length() * 2.0 / Self::RADIUS).ceil().max(1.0);
+        for _ in 0..steps as usize {
+            self.position = map.push_out(self.position + step * (1.0 / steps), Self::RADIUS, geo_set);
         }
     }
 }
\ No newline at end of file
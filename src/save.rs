@@ -0,0 +1,103 @@
+use parser::{
+    Error,
+    File,
+    BufReader,
+    PathBuf,
+    save::{
+        SAVE_VERSION,
+        PlayerSave,
+    },
+};
+
+pub use parser::save::SaveGame;
+
+use level::Level;
+
+use player::Player;
+
+/// directory of the save files
+const SAVES_DIR: &str = "saves";
+
+/// slot of the quicksave and quickload keys
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+
+/// path of the file of a save slot.
+/// Slot names are restricted to letters, digits, '-' and '_' so that they stay inside the saves directory.
+pub fn save_path(slot: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if slot.is_empty() || !slot.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("invalid save name '{}'", slot).into());
+    }
+    let mut path = PathBuf::from(SAVES_DIR);
+    path.push(slot);
+    path.set_extension("json");
+    Ok(path)
+}
+
+/// names of the save slots, sorted
+pub fn save_slots() -> Vec<String> {
+    let mut slots: Vec<String> = match std::fs::read_dir(SAVES_DIR) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(|stem| stem.to_string()))
+            .collect(),
+        Err(_) => vec![],
+    };
+    slots.sort();
+    slots
+}
+
+impl SaveGame {
+    pub fn new(level: &Level, player: &Player) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            version: SAVE_VERSION,
+            level_name: level.name.clone(),
+            player: PlayerSave {
+                position: player.position,
+                direction: player.direction,
+                health: player.health,
+                ammo: player.ammo,
+            },
+            level: level.save_state()?,
+        })
+    }
+
+    pub fn write(&self, slot: &str) -> Result<(), Box<dyn Error>> {
+        let path = save_path(slot)?;
+        std::fs::create_dir_all(SAVES_DIR)?;
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn read(slot: &str) -> Result<Self, Box<dyn Error>> {
+        let path = save_path(slot)?;
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(format!("no save named '{}'", slot).into());
+            },
+            Err(err) => return Err(err.into()),
+        };
+        Self::from_reader(BufReader::new(file))
+            .map_err(|err| format!("save '{}': {}", slot, err).into())
+    }
+
+    /// restores the player and the level, which must be the level of the save
+    pub fn restore(self, level: &mut Level, player: &mut Player) -> Result<(), Box<dyn Error>> {
+        if level.name != self.level_name {
+            return Err(format!("the save is for level '{}', not '{}'", self.level_name, level.name).into());
+        }
+        let position = self.player.position;
+        if position.x < 0.0 || position.y < 0.0 || position.x >= level.map.width as f32 || position.y >= level.map.height as f32 {
+            return Err(format!("player position {}:{} is out of the map", position.y, position.x).into());
+        }
+        level.restore_state(self.level, &[position])?;
+        player.position = self.player.position;
+        player.direction = self.player.direction;
+        player.health = self.player.health;
+        player.ammo = self.player.ammo;
+        Ok(())
+    }
+}
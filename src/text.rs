@@ -0,0 +1,84 @@
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{
+        Canvas,
+        TextureCreator,
+    },
+    ttf::{
+        Font,
+        Sdl2TtfContext,
+    },
+    video::{
+        Window,
+        WindowContext,
+    },
+};
+
+use parser::{
+    Error,
+    assets_path,
+};
+
+/// Font whose size is a fraction of the window height.
+/// It is reloaded when the window height changes.
+pub struct ScaledFont<'ttf> {
+    ttf_context: &'ttf Sdl2TtfContext,
+    font_name: String,
+    scale: f32,
+    font: Font<'ttf, 'static>,
+    /// window height the font was loaded for
+    window_height: u32,
+}
+
+impl<'ttf> ScaledFont<'ttf> {
+    fn load(ttf_context: &'ttf Sdl2TtfContext, font_name: &str, scale: f32, window_height: u32) -> Result<Font<'ttf, 'static>, Box<dyn Error>> {
+        let mut path = assets_path(&["fonts"], font_name);
+        path.set_extension("ttf");
+        let point_size = ((window_height as f32 * scale) as u16).max(8);
+        Ok(ttf_context.load_font(path, point_size)?)
+    }
+
+    /// `font_name` is the name of a ttf file of the `assets/fonts/` directory
+    pub fn new(ttf_context: &'ttf Sdl2TtfContext, font_name: &str, scale: f32, window_height: u32) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            ttf_context,
+            font_name: font_name.to_string(),
+            scale,
+            font: Self::load(ttf_context, font_name, scale, window_height)?,
+            window_height,
+        })
+    }
+
+    /// reloads the font if the window height changed
+    pub fn fit(&mut self, canvas: &Canvas<Window>) -> Result<(), Box<dyn Error>> {
+        let (_, height) = canvas.window().drawable_size();
+        if height != self.window_height {
+            self.font = Self::load(self.ttf_context, &self.font_name, self.scale, height)?;
+            self.window_height = height;
+        }
+        Ok(())
+    }
+
+    /// height of a line of text, in pixels
+    pub fn line_height(&self) -> i32 {
+        self.font.recommended_line_spacing()
+    }
+
+    /// size of the text, in pixels
+    pub fn size_of(&self, text: &str) -> Result<(u32, u32), Box<dyn Error>> {
+        Ok(self.font.size_of(text)?)
+    }
+
+    /// draws the text with its top left corner at x, y
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>,
+            text: &str, color: Color, x: i32, y: i32) -> Result<(), Box<dyn Error>> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let surface = self.font.render(text).blended(color)?;
+        let texture = texture_creator.create_texture_from_surface(&surface)?;
+        canvas.copy(&texture, None, Rect::new(x, y, surface.width(), surface.height()))?;
+        Ok(())
+    }
+}
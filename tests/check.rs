@@ -0,0 +1,52 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::generator::LevelGenerator;
+use wolf3d_rs::check::CheckReport;
+
+use std::error::Error;
+
+fn errors(report: &CheckReport) -> Vec<(Option<[usize; 2]>, &str)> {
+    report.diagnostics.iter()
+        .filter(|diagnostic| diagnostic.severity == check::Severity::Error)
+        .map(|diagnostic| (diagnostic.tile, diagnostic.message.as_str()))
+        .collect()
+}
+
+#[test]
+fn test_check_border() -> Result<(), Box<dyn Error>> {
+    // the corners of the room on the border of the map close it
+    let files = LevelGenerator::new(5, 12, 10)?.generate(GeneratorStyle::Rooms, "42")?;
+    assert_eq!(errors(&CheckReport::run_files("generated", &files)), vec![]);
+
+    // a pillar does not
+    let mut level_map = LevelMap::from_files("generated", &files)?;
+    let tile_y = level_map.map.height - 1;
+    let tile_x = (0..level_map.map.width)
+        .find(|&tile_x| level_map.geo_set.names[level_map.map.tiles[tile_y][tile_x].geo_idx] == "s_wall")
+        .unwrap();
+    level_map.map.tiles[tile_y][tile_x].geo_idx = level_map.geo_set.names.iter().position(|name| name == "small_square_column").unwrap();
+    let report = CheckReport::run_files("generated", &level_map.to_files(false)?);
+    let errors = errors(&report);
+    assert!(errors.contains(&(Some([tile_x, tile_y]), "the border of the map is open")));
+    assert!(errors.iter().all(|&(_, message)| message == "the border of the map is open"));
+    Ok(())
+}
+
+#[test]
+fn test_check_reports_all_map_errors() -> Result<(), Box<dyn Error>> {
+    let files = LevelGenerator::new(5, 12, 10)?.generate(GeneratorStyle::Rooms, "42")?;
+    let mut level_map = LevelMap::from_files("generated", &files)?;
+    let portal = level_map.mtl_set.material_properties.iter().position(|properties| properties.portal.is_some()).unwrap();
+    level_map.map.tiles[3][3].mtl_idx = portal;
+    level_map.map.tiles[5][6].mtl_idx = portal;
+    level_map.map.tiles[3][3].geo_idx = 0;
+    level_map.map.tiles[5][6].geo_idx = 0;
+    let files = level_map.to_files(false)?;
+    let report = CheckReport::run_files("generated", &files);
+    let portal_error = "a portal must be associated to a non-empty geometric tile";
+    assert_eq!(errors(&report), vec![(Some([3, 3]), portal_error), (Some([6, 5]), portal_error)]);
+    // the map is not built with the first of them
+    let err = LevelMap::from_files("generated", &files).err().unwrap();
+    assert_eq!(err.to_string(), format!("tile 3:3: {}", portal_error));
+    Ok(())
+}
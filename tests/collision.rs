@@ -0,0 +1,71 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+
+mod common;
+use common::{floor_row, with_primitives};
+
+use std::error::Error;
+
+const RADIUS: f32 = 0.2;
+const EPSILON: f32 = 1e-3;
+
+#[test]
+fn test_collision_with_map_primitives() -> Result<(), Box<dyn Error>> {
+    // a wall across the three tiles, with a low one which does not block under it
+    let (level_map, (tile_x, tile_y)) = with_primitives(|x, y| format!(r#"[
+        {{ "plane": {{ "p1": [{x}, {wall_y}], "p2": [{end_x}, {wall_y}], "two_sided": true }} }},
+        {{ "plane": {{ "p1": [{x}, {low_y}], "p2": [{end_x}, {low_y}], "two_sided": true, "height": 0.2 }} }}
+    ]"#, x = x, end_x = x + 3.0, wall_y = y + 0.5, low_y = y + 0.8))?;
+    let (map, geo_set) = (&level_map.map, &level_map.geo_set);
+    let wall_y = tile_y as f32 + 0.5;
+    // the wall is tested in the last of its tiles
+    let center = Vec2 { x: tile_x as f32 + 2.5, y: wall_y - 0.1 };
+    let pushed = map.push_out(center, RADIUS, geo_set);
+    assert!((pushed.y - (wall_y - RADIUS)).abs() < EPSILON, "{:?}", pushed);
+    assert!((pushed.x - center.x).abs() < EPSILON);
+    // and from both sides
+    let pushed = map.push_out(Vec2 { x: center.x, y: wall_y + 0.1 }, RADIUS, geo_set);
+    assert!((pushed.y - (wall_y + RADIUS)).abs() < EPSILON, "{:?}", pushed);
+
+    let center = Vec2 { x: center.x, y: tile_y as f32 + 0.8 };
+    let pushed = map.push_out(center, RADIUS, geo_set);
+    assert!((pushed - center).length() < EPSILON);
+    assert!(map.is_blocking(tile_x + 2, tile_y, geo_set));
+    Ok(())
+}
+
+#[test]
+fn test_collision_in_round_room() -> Result<(), Box<dyn Error>> {
+    let (level_map, (tile_x, tile_y)) = with_primitives(|x, y| format!(r#"[
+        {{ "cylinder": {{ "center": [{x}, {y}], "radius": 0.45, "inward": true }} }}
+    ]"#, x = x + 1.5, y = y + 0.5))?;
+    let (map, geo_set) = (&level_map.map, &level_map.geo_set);
+    let room_center = Vec2 { x: tile_x as f32 + 1.5, y: tile_y as f32 + 0.5 };
+    // the room is not solid, its wall keeps the player inside
+    assert!(!map.is_blocking(tile_x + 1, tile_y, geo_set));
+    assert!((map.push_out(room_center, RADIUS, geo_set) - room_center).length() < EPSILON);
+    let pushed = map.push_out(room_center + Vec2 { x: 0.4, y: 0.0 }, RADIUS, geo_set);
+    assert!(((pushed - room_center).length() - (0.45 - RADIUS)).abs() < EPSILON, "{:?}", pushed);
+    Ok(())
+}
+
+#[test]
+fn test_collision_with_tiles() -> Result<(), Box<dyn Error>> {
+    let (mut level_map, (tile_x, tile_y)) = floor_row()?;
+    let cylinder_idx = level_map.geo_set.names.iter().position(|name| name == "cylinder").unwrap();
+    level_map.map.tiles[tile_y][tile_x + 1].geo_idx = cylinder_idx;
+    let (map, _) = level_map.rebuild_map()?;
+    let geo_set = &level_map.geo_set;
+    let pillar = Vec2 { x: tile_x as f32 + 1.5, y: tile_y as f32 + 0.5 };
+    // walking east into the pillar from the tile before it
+    let pushed = map.push_out(pillar - Vec2 { x: 0.6, y: 0.0 }, RADIUS, geo_set);
+    assert!(((pillar - pushed).length() - (0.45 + RADIUS)).abs() < EPSILON, "{:?}", pushed);
+    assert!(map.is_blocking(tile_x + 1, tile_y, geo_set));
+
+    assert!(map.check_position(pillar - Vec2 { x: 1.0, y: 0.0 }, geo_set).is_ok());
+    assert!(map.check_position(pillar, geo_set).is_err());
+    assert!(map.check_position(Vec2 { x: f32::NAN, y: 1.5 }, geo_set).is_err());
+    assert!(map.check_position(Vec2 { x: -0.5, y: 1.5 }, geo_set).is_err());
+    assert!(map.check_position(Vec2 { x: map.width as f32, y: 1.5 }, geo_set).is_err());
+    Ok(())
+}
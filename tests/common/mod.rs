@@ -0,0 +1,39 @@
+// each test file uses a part of the fixtures
+#![allow(dead_code)]
+
+use wolf3d_rs::*;
+use wolf3d_rs::generator::LevelGenerator;
+
+use std::error::Error;
+
+/// A generated level, and its first floor tile without spawn which is followed by two such tiles to the east.
+pub fn floor_row() -> Result<(LevelMap, (usize, usize)), Box<dyn Error>> {
+    let files = LevelGenerator::new(2, 16, 12)?.generate(GeneratorStyle::Rooms, "next")?;
+    let level_map = LevelMap::from_files("generated", &files)?;
+    let is_floor = |tile_x: usize, tile_y: usize| {
+        level_map.geo_set.geometries[level_map.map.tiles[tile_y][tile_x].geo_idx].is_none()
+            && level_map.spawns.iter().all(|spawn| (spawn.coordinates.x as usize, spawn.coordinates.y as usize) != (tile_x, tile_y))
+    };
+    let tile = (0..level_map.map.height)
+        .flat_map(|tile_y| (0..level_map.map.width - 2).map(move |tile_x| (tile_x, tile_y)))
+        .find(|&(tile_x, tile_y)| (0..3).all(|step| is_floor(tile_x + step, tile_y)))
+        .unwrap();
+    Ok((level_map, tile))
+}
+
+/// the level of `floor_row` with the geometry `geo_name` on the tile, whose doors and push walls are rebuilt
+pub fn with_geometry(geo_name: &str) -> Result<(LevelMap, (usize, usize)), Box<dyn Error>> {
+    let (mut level_map, (tile_x, tile_y)) = floor_row()?;
+    level_map.map.tiles[tile_y][tile_x].geo_idx = level_map.geo_set.names.iter().position(|name| name == geo_name).unwrap();
+    let (map, _) = level_map.rebuild_map()?;
+    level_map.map = map;
+    Ok((level_map, (tile_x, tile_y)))
+}
+
+/// the level of `floor_row` whose map holds the primitives given in json by `primitives` from the tile
+pub fn with_primitives<F: Fn(f32, f32) -> String>(primitives: F) -> Result<(LevelMap, (usize, usize)), Box<dyn Error>> {
+    let (level_map, (tile_x, tile_y)) = floor_row()?;
+    let mut files = level_map.to_files(false)?;
+    files.geo_map.primitives = serde_json::from_str(&primitives(tile_x as f32, tile_y as f32))?;
+    Ok((LevelMap::from_files("generated", &files)?, (tile_x, tile_y)))
+}
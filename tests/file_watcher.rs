@@ -0,0 +1,34 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_file_watcher() -> Result<(), Box<dyn Error>> {
+    let level_dir = std::env::temp_dir().join(format!("wolf3d_rs_watch_{}", std::process::id()));
+    fs::create_dir_all(&level_dir)?;
+    let asset = level_dir.with_extension("json");
+    fs::write(level_dir.join("level.json"), "{}")?;
+    fs::write(&asset, "{}")?;
+
+    let mut watcher = FileWatcher::new(level_dir.clone(), vec![asset.clone()]);
+    assert!(!watcher.poll(1.0));
+
+    // the files are only polled every half second
+    fs::write(level_dir.join("script.rhai"), "")?;
+    assert!(!watcher.poll(0.1));
+    assert!(watcher.poll(1.0));
+    assert!(!watcher.poll(1.0));
+
+    File::options().write(true).open(&asset)?.set_modified(SystemTime::now() + Duration::from_secs(10))?;
+    assert!(watcher.poll(1.0));
+
+    fs::remove_file(&asset)?;
+    watcher.acknowledge();
+    assert!(!watcher.poll(1.0));
+
+    fs::remove_dir_all(&level_dir)?;
+    Ok(())
+}
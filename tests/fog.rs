@@ -0,0 +1,21 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+
+use std::error::Error;
+
+fn fog(json: &str) -> Result<Fog, Box<dyn Error>> {
+    Fog::new(&serde_json::from_str(json)?)
+}
+
+#[test]
+fn test_fog() -> Result<(), Box<dyn Error>> {
+    let fog = fog(r#"{ "color": "0x80808080", "start": 2, "end": 10 }"#)?;
+    assert_eq!(fog.opacity(1.0), 0.0);
+    assert!((fog.opacity(6.0) - 0.5 * 128.0 / 255.0).abs() < 1e-4);
+    assert!((fog.opacity(20.0) - 128.0 / 255.0).abs() < 1e-4);
+    assert_eq!(Fog::new(&fog.to_raw())?, fog);
+
+    assert!(self::fog(r#"{ "color": "0x80808080", "start": 10, "end": 2 }"#).is_err());
+    assert!(self::fog(r#"{ "color": "0x808080", "start": 2, "end": 10 }"#).is_err());
+    Ok(())
+}
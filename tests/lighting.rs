@@ -0,0 +1,79 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::generator::LevelGenerator;
+
+mod common;
+use common::with_primitives;
+
+use std::error::Error;
+
+/// the first hit of the ray on the primitive `idx` of the map
+fn map_hit(level_map: &LevelMap, ray: Ray, idx: usize) -> RayHit {
+    level_map.map.cast_ray(ray, f32::INFINITY, None, &level_map.geo_set, |hit| hit.primitive == HitPrimitive::Map(idx))
+        .pop()
+        .filter(|hit| hit.primitive == HitPrimitive::Map(idx))
+        .unwrap()
+}
+
+#[test]
+fn test_lighting() -> Result<(), Box<dyn Error>> {
+    let mut files = LevelGenerator::new(3, 16, 12)?.generate(GeneratorStyle::Rooms, "next")?;
+    files.mtl_map.lighting = serde_json::from_str(r#"{
+        "ambient": "0xffffff40",
+        "lights": [{ "position": [2.5, 2.5], "color": "0xff8000ff", "radius": 4 }]
+    }"#)?;
+    let level_map = LevelMap::from_files("generated", &files)?;
+    let lighting = level_map.lighting.clone().unwrap();
+    assert_eq!(lighting.ambient, 0xffffff40);
+    let light = lighting.lights[0];
+    // a wall facing the light, half its radius away
+    let lit = light.light_at(Vec2 { x: 4.5, y: 2.5 }, Vec2 { x: -1.0, y: 0.0 }, false);
+    assert!((lit[0] - 0.25).abs() < 1e-4 && (lit[1] - 0.25 * 128.0 / 255.0).abs() < 1e-4 && lit[2] == 0.0);
+    assert_eq!(light.light_at(Vec2 { x: 4.5, y: 2.5 }, Vec2 { x: 1.0, y: 0.0 }, false), [0.0; 3]);
+    assert_eq!(light.light_at(Vec2 { x: 4.5, y: 2.5 }, Vec2 { x: 1.0, y: 0.0 }, true), lit);
+    assert_eq!(light.light_at(Vec2 { x: 7.5, y: 2.5 }, Vec2 { x: -1.0, y: 0.0 }, false), [0.0; 3]);
+    let reloaded = LevelMap::from_files("generated", &level_map.to_files(false)?)?;
+    assert_eq!(reloaded.lighting, level_map.lighting);
+
+    files.mtl_map.lighting = serde_json::from_str(r#"{ "ambient": "0xffffff40", "lights": [{ "position": [2, 2], "color": "0xffffffff", "radius": 0 }] }"#)?;
+    assert!(LevelMap::from_files("generated", &files).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_lightmap() -> Result<(), Box<dyn Error>> {
+    // a light in the first tile, a wall south of it, and a wall in the third tile behind an occluder in the second
+    let (level_map, (tile_x, tile_y)) = with_primitives(|x, y| format!(r#"[
+        {{ "plane": {{ "p1": [{x1}, {y}], "p2": [{x1}, {y1}], "two_sided": true }} }},
+        {{ "plane": {{ "p1": [{x2}, {y_start}], "p2": [{x2}, {y_end}], "two_sided": true }} }},
+        {{ "plane": {{ "p1": [{x_start}, {y_south}], "p2": [{x_end}, {y_south}], "two_sided": true }} }}
+    ]"#, x1 = x + 1.5, x2 = x + 2.8, y = y, y1 = y + 1.0, y_start = y + 0.1, y_end = y + 0.9,
+        x_start = x + 0.1, x_end = x + 0.9, y_south = y + 0.9))?;
+    let (map, geo_set) = (&level_map.map, &level_map.geo_set);
+    let position = Vec2 { x: tile_x as f32 + 0.5, y: tile_y as f32 + 0.5 };
+    let lighting = Lighting {
+        ambient: 0xffffff40,
+        lights: vec![PointLight { position, color: 0xffffffff, radius: 4.0 }],
+    };
+
+    let east = Vec2 { x: 1.0, y: 0.0 };
+    let south = Vec2 { x: 0.0, y: 1.0 };
+    // the occluder is 1 tile east of the light, the wall 2.3 tiles
+    assert!(Lightmap::hidden(map, geo_set, position, east, 2.3, None));
+    assert!(!Lightmap::hidden(map, geo_set, position, east, 0.9, None));
+    // the south wall is 0.4 tiles away, in the tile of the light
+    assert!(Lightmap::hidden(map, geo_set, position, south, 0.5, None));
+    assert!(!Lightmap::hidden(map, geo_set, position, south, 0.3, None));
+    assert!(!Lightmap::hidden(map, geo_set, position, south, 0.5, Some((tile_x, tile_y))));
+
+    let lightmap = Lightmap::bake(map, geo_set, &lighting);
+    let ambient = lightmap.ambient();
+    assert!((ambient[0] - 64.0 / 255.0).abs() < 1e-4);
+    let behind = map_hit(&level_map, Ray::new(position, east), 1);
+    assert_eq!(lightmap.light(&behind), ambient);
+    let occluder = map_hit(&level_map, Ray::new(position, east), 0);
+    assert!(lightmap.light(&occluder)[0] > ambient[0] + 0.1);
+    let wall = map_hit(&level_map, Ray::new(position, south), 2);
+    assert!(lightmap.light(&wall)[0] > ambient[0] + 0.1);
+    Ok(())
+}
@@ -0,0 +1,93 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::generator::LevelGenerator;
+
+use std::error::Error;
+
+fn generated_editor(seed: u64) -> Result<MapEditor, Box<dyn Error>> {
+    let files = LevelGenerator::new(seed, 16, 12)?.generate(GeneratorStyle::Rooms, "next")?;
+    Ok(MapEditor::new(LevelMap::from_files("generated", &files)?))
+}
+
+fn tile(editor: &MapEditor, (tile_x, tile_y): (usize, usize)) -> (usize, usize) {
+    let tile = &editor.level_map.map.tiles[tile_y][tile_x];
+    (tile.geo_idx, tile.mtl_idx)
+}
+
+/// the first tile of empty geometry without a spawn
+fn floor_tile(editor: &MapEditor) -> (usize, usize) {
+    let level_map = &editor.level_map;
+    let spawn = &level_map.spawns[0].coordinates;
+    (0..level_map.map.height).flat_map(|tile_y| (0..level_map.map.width).map(move |tile_x| (tile_x, tile_y)))
+        .find(|&(tile_x, tile_y)| {
+            level_map.geo_set.geometries[level_map.map.tiles[tile_y][tile_x].geo_idx].is_none()
+                && (spawn.x as usize, spawn.y as usize) != (tile_x, tile_y)
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_editor_undo_redo() -> Result<(), Box<dyn Error>> {
+    let mut editor = generated_editor(1)?;
+    let before = tile(&editor, (0, 0));
+    editor.cycle_geometry(0, 0, 1)?;
+    editor.cycle_material(0, 0, -1)?;
+    let after = tile(&editor, (0, 0));
+    assert_ne!(before, after);
+    assert!(editor.modified);
+
+    assert!(editor.undo() && editor.undo());
+    assert!(!editor.undo());
+    assert_eq!(tile(&editor, (0, 0)), before);
+    assert!(editor.redo() && editor.redo());
+    assert!(!editor.redo());
+    assert_eq!(tile(&editor, (0, 0)), after);
+    Ok(())
+}
+
+#[test]
+fn test_editor_validation() -> Result<(), Box<dyn Error>> {
+    let mut editor = generated_editor(2)?;
+    // a portal needs a non-empty geometry
+    let floor = floor_tile(&editor);
+    let before = tile(&editor, floor);
+    assert!(editor.cycle_portal(floor.0, floor.1).is_err());
+    assert_eq!(tile(&editor, floor), before);
+    editor.cycle_portal(0, 0)?;
+    let mtl_idx = tile(&editor, (0, 0)).1;
+    assert!(editor.level_map.mtl_set.material_properties[mtl_idx].portal.is_some());
+
+    // the geometry of a portal is never cycled to an empty one
+    for _ in 0..editor.level_map.geo_set.geometries.len() {
+        editor.cycle_geometry(0, 0, 1)?;
+        assert!(editor.level_map.geo_set.geometries[tile(&editor, (0, 0)).0].is_some());
+    }
+    assert!(editor.cycle_geometry(editor.level_map.map.width, 0, 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_editor_spawns() -> Result<(), Box<dyn Error>> {
+    let mut editor = generated_editor(3)?;
+    let player = editor.level_map.spawns[0].player;
+    let floor = floor_tile(&editor);
+    editor.cycle_spawn(floor.0, floor.1)?;
+    assert_eq!(editor.level_map.spawns.len(), 1);
+    let spawn = &editor.level_map.spawns[0];
+    assert_eq!((spawn.player, spawn.coordinates.x as usize, spawn.coordinates.y as usize), (player, floor.0, floor.1));
+    assert!(editor.cycle_geometry(floor.0, floor.1, 1).is_err());
+
+    // the map read back holds the spawn at its new place
+    let (_, spawns) = editor.level_map.rebuild_map()?;
+    assert_eq!((spawns[0].coordinates.x as usize, spawns[0].coordinates.y as usize), floor);
+
+    // the spawn cycles through the players, but the last spawn of the map is not removed
+    let players = editor.level_map.spawn_symbol_set.players.clone();
+    for _ in 1..players.len() {
+        editor.cycle_spawn(floor.0, floor.1)?;
+    }
+    assert_eq!(editor.level_map.spawns[0].player, *players.last().unwrap());
+    assert!(editor.cycle_spawn(floor.0, floor.1).is_err());
+    assert_eq!(editor.level_map.spawns.len(), 1);
+    Ok(())
+}
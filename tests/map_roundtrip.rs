@@ -0,0 +1,61 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::generator::LevelGenerator;
+
+use std::error::Error;
+
+fn tiles(level_map: &LevelMap) -> Vec<Vec<(usize, usize)>> {
+    level_map.map.tiles.iter().map(|line| line.iter().map(|tile| (tile.geo_idx, tile.mtl_idx)).collect()).collect()
+}
+
+fn spawns(level_map: &LevelMap) -> Vec<(usize, f32, f32)> {
+    level_map.spawns.iter().map(|spawn| (spawn.player, spawn.coordinates.x, spawn.coordinates.y)).collect()
+}
+
+/// the files of the level, and its sets, as json texts
+fn files_json(level_map: &LevelMap) -> Result<Vec<String>, Box<dyn Error>> {
+    let files = level_map.to_files(false)?;
+    Ok(vec![
+        serde_json::to_string(&files.geo_map)?,
+        serde_json::to_string(&files.mtl_map)?,
+        serde_json::to_string(&level_map.geo_set)?,
+        serde_json::to_string(&level_map.mtl_set)?,
+        serde_json::to_string(&level_map.spawn_symbol_set)?,
+        serde_json::to_string(&level_map.mtl_set.portal_symbols)?,
+    ])
+}
+
+/// load -> save -> load gives the same level, and saving it again gives the same files
+fn assert_roundtrip(level_map: &LevelMap) -> Result<(), Box<dyn Error>> {
+    let reloaded = LevelMap::from_files(&level_map.name, &level_map.to_files(false)?)?;
+    assert_eq!(tiles(level_map), tiles(&reloaded), "tiles of level '{}'", level_map.name);
+    assert_eq!(spawns(level_map), spawns(&reloaded), "spawns of level '{}'", level_map.name);
+    assert_eq!(level_map.skybox, reloaded.skybox, "skybox of level '{}'", level_map.name);
+    assert_eq!(level_map.fog, reloaded.fog, "fog of level '{}'", level_map.name);
+    assert_eq!(level_map.lighting, reloaded.lighting, "lighting of level '{}'", level_map.name);
+    assert_eq!(files_json(level_map)?, files_json(&reloaded)?, "files of level '{}'", level_map.name);
+    Ok(())
+}
+
+#[test]
+fn test_levels_roundtrip() -> Result<(), Box<dyn Error>> {
+    for level_name in Level::names() {
+        let level_map = LevelMap::load(&level_name)?;
+        assert_roundtrip(&level_map)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_generated_levels_roundtrip() -> Result<(), Box<dyn Error>> {
+    for seed in 0..32 {
+        for &style in &[GeneratorStyle::Rooms, GeneratorStyle::Caves] {
+            let width = 8 + (seed as usize * 5) % 40;
+            let height = 8 + (seed as usize * 3) % 30;
+            let files = LevelGenerator::new(seed, width, height)?.generate(style, "next")?;
+            let level_map = LevelMap::from_files(format!("generated_{}", seed), &files)?;
+            assert_roundtrip(&level_map)?;
+        }
+    }
+    Ok(())
+}
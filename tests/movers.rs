@@ -0,0 +1,110 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::mover::{DoorState, PushWallState};
+
+mod common;
+use common::with_geometry;
+
+use std::error::Error;
+
+const EAST: Vec2 = Vec2 { x: 1.0, y: 0.0 };
+
+fn door(map: &Map) -> &mover::Door {
+    match map.movers[0] {
+        Mover::Door(ref door) => door,
+        _ => panic!("not a door"),
+    }
+}
+
+fn push_wall(map: &Map) -> &mover::PushWall {
+    match map.movers[0] {
+        Mover::PushWall(ref push_wall) => push_wall,
+        _ => panic!("not a push wall"),
+    }
+}
+
+#[test]
+fn test_door() -> Result<(), Box<dyn Error>> {
+    let (level_map, (tile_x, tile_y)) = with_geometry("ns_door")?;
+    let (mut map, geo_set) = (level_map.map, level_map.geo_set);
+    let inside = Vec2 { x: tile_x as f32 + 0.5, y: tile_y as f32 + 0.5 };
+    assert_eq!(door(&map).state, DoorState::Closed);
+    assert!(map.is_blocking(tile_x, tile_y, &geo_set));
+
+    assert!(map.use_tile(tile_x, tile_y, EAST, &geo_set, &[]));
+    assert_eq!(door(&map).state, DoorState::Opening);
+    map.update(0.5, &geo_set, &[]);
+    assert!((door(&map).progress - 0.5).abs() < 1e-4);
+    map.update(0.6, &geo_set, &[]);
+    assert_eq!(door(&map).state, DoorState::Open { timer: 0.0 });
+    assert!(!map.is_blocking(tile_x, tile_y, &geo_set));
+
+    // does not start closing on a player
+    map.update(5.0, &geo_set, &[inside]);
+    assert_eq!(door(&map).state, DoorState::Open { timer: 0.0 });
+    map.update(5.0, &geo_set, &[]);
+    assert_eq!(door(&map).state, DoorState::Closing);
+    // nor keep closing
+    map.update(0.5, &geo_set, &[inside]);
+    assert_eq!(door(&map).state, DoorState::Opening);
+    map.update(0.5, &geo_set, &[]);
+    map.update(5.0, &geo_set, &[]);
+    map.update(1.0, &geo_set, &[]);
+    assert_eq!(door(&map).state, DoorState::Closed);
+    assert_eq!(door(&map).progress, 0.0);
+    Ok(())
+}
+
+#[test]
+fn test_push_wall() -> Result<(), Box<dyn Error>> {
+    let (level_map, (tile_x, tile_y)) = with_geometry("push_wall")?;
+    let (mut map, geo_set) = (level_map.map, level_map.geo_set);
+    let push_wall_idx = map.tiles[tile_y][tile_x].geo_idx;
+    let floor_idx = map.tiles[tile_y][tile_x + 1].geo_idx;
+
+    // not into a player
+    let blocked = Vec2 { x: tile_x as f32 + 1.5, y: tile_y as f32 + 0.5 };
+    assert!(!map.use_tile(tile_x, tile_y, EAST, &geo_set, &[blocked]));
+    assert_eq!(push_wall(&map).state, PushWallState::Idle);
+
+    assert!(map.use_tile(tile_x, tile_y, EAST, &geo_set, &[]));
+    assert_eq!(push_wall(&map).target(), Some((tile_x + 1, tile_y)));
+    assert!(map.is_blocking(tile_x + 1, tile_y, &geo_set));
+    map.update(1.0, &geo_set, &[]);
+    assert!((push_wall(&map).offset().x - 2.0 / 3.0).abs() < 1e-4);
+
+    // stops before a player in the next tile
+    let ahead = Vec2 { x: tile_x as f32 + 2.5, y: tile_y as f32 + 0.5 };
+    map.update(0.6, &geo_set, &[ahead]);
+    assert_eq!(map.tiles[tile_y][tile_x + 1].geo_idx, push_wall_idx);
+    assert_eq!(map.tiles[tile_y][tile_x].geo_idx, floor_idx);
+    assert_eq!(push_wall(&map).state, PushWallState::Done);
+    assert!(!map.use_tile(tile_x + 1, tile_y, EAST, &geo_set, &[]));
+    Ok(())
+}
+
+#[test]
+fn test_push_wall_save() -> Result<(), Box<dyn Error>> {
+    let (level_map, (tile_x, tile_y)) = with_geometry("push_wall")?;
+    let map = &level_map.map;
+    let tiles: Vec<Vec<[usize; 2]>> = map.tiles.iter().map(|line| line.iter().map(|tile| [tile.geo_idx, tile.mtl_idx]).collect()).collect();
+    let check_save = |tiles: &[Vec<[usize; 2]>], tile: [usize; 2], state: serde_json::Value, progress: f32| {
+        let save = serde_json::json!({
+            "tiles": tiles,
+            "movers": [{ "push_wall": { "tile": tile, "state": state, "progress": progress } }],
+            "fired_triggers": [],
+        });
+        map.check_save(&serde_json::from_value(save).unwrap(), &level_map.geo_set, &level_map.mtl_set)
+    };
+    let moving = |step_x: isize, remaining: usize| serde_json::json!({ "moving": { "step_x": step_x, "step_y": 0, "remaining": remaining } });
+
+    assert!(check_save(&tiles, [tile_x, tile_y], moving(1, 2), 0.5).is_ok());
+    assert!(check_save(&tiles, [tile_x, tile_y], moving(1, 2), 1.5).is_err());
+    assert!(check_save(&tiles, [tile_x, tile_y], moving(2, 1), 0.5).is_err());
+    // off the map, and into a wall
+    assert!(check_save(&tiles, [0, tile_y], moving(-1, 1), 0.5).is_err());
+    let mut walled = tiles.clone();
+    walled[tile_y][tile_x + 1] = walled[tile_y][tile_x];
+    assert!(check_save(&walled, [tile_x, tile_y], moving(1, 2), 0.5).is_err());
+    Ok(())
+}
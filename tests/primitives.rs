@@ -0,0 +1,180 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+
+use std::error::Error;
+
+const EPSILON: f32 = 1e-4;
+
+fn assert_near(a: Vec2, b: Vec2) {
+    assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+fn east_ray(y: f32) -> Ray {
+    Ray::new(Vec2 { x: -1.0, y }, Vec2 { x: 1.0, y: 0.0 })
+}
+
+#[test]
+fn test_off_center_cylinder() {
+    let origin = Vec2 { x: 3.0, y: 2.0 };
+    let cylinder = PrimitiveGeometry::cylinder(Vec2 { x: 0.25, y: 0.75 }, 0.5, false);
+    // kept within the tile
+    match cylinder {
+        PrimitiveGeometry::Cylinder{ radius, .. } => assert_eq!(radius, 0.25),
+        _ => unreachable!(),
+    }
+    let ray = Ray::new(Vec2 { x: 0.0, y: 2.75 }, Vec2 { x: 1.0, y: 0.0 });
+    let hit = cylinder.ray_hit(ray, origin).unwrap();
+    assert!((hit.t - 3.0).abs() < EPSILON);
+    assert_near(hit.normal, Vec2 { x: -1.0, y: 0.0 });
+    assert!((hit.u - 0.5).abs() < EPSILON);
+
+    assert!(cylinder.push_out(Vec2 { x: 3.25, y: 3.2 }, 0.1, origin).is_none());
+    let push = cylinder.push_out(Vec2 { x: 3.25, y: 3.0 }, 0.2, origin).unwrap();
+    assert_near(push, Vec2 { x: 0.0, y: 0.2 });
+}
+
+#[test]
+fn test_arc() -> Result<(), Box<dyn Error>> {
+    // quarter circle around the top left corner, facing the bottom right corner
+    let arc = PrimitiveGeometry::arc(Vec2 { x: 0.0, y: 0.0 }, 1.0, 0.0, 90.0, false)?;
+    let origin = Vec2::default();
+    let diagonal = Ray::new(Vec2 { x: 2.0, y: 2.0 }, Vec2 { x: -1.0, y: -1.0 });
+    let hit = arc.ray_hit(diagonal, origin).unwrap();
+    let half_sqrt2 = std::f32::consts::FRAC_1_SQRT_2;
+    assert!((hit.t - (2.0 - half_sqrt2)).abs() < EPSILON);
+    assert_near(hit.normal, Vec2 { x: half_sqrt2, y: half_sqrt2 });
+    assert!((hit.u - 0.5).abs() < EPSILON);
+    // out of the angles of the arc
+    assert!(arc.ray_hit(Ray::new(Vec2 { x: -2.0, y: -2.0 }, Vec2 { x: 1.0, y: 1.0 }), origin).is_none());
+    // the inner side is not seen
+    assert!(arc.ray_hit(Ray::new(Vec2 { x: 0.1, y: 0.1 }, Vec2 { x: 1.0, y: 1.0 }), origin).is_none());
+
+    let push = arc.push_out(Vec2 { x: 0.9, y: 0.05 }, 0.2, origin).unwrap();
+    assert!(push.x < 0.0);
+    assert!(PrimitiveGeometry::arc(Vec2::default(), 0.5, 90.0, 90.0, false).is_err());
+    // out of the tile
+    assert!(PrimitiveGeometry::arc(Vec2::default(), 1.2, 0.0, 90.0, false).is_err());
+    assert!(PrimitiveGeometry::arc(Vec2 { x: 0.5, y: 0.5 }, 0.5, 0.0, 360.0, false).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_polygon() -> Result<(), Box<dyn Error>> {
+    // the same square, around it in both directions
+    let clockwise = vec![Vec2 { x: 0.25, y: 0.25 }, Vec2 { x: 0.75, y: 0.25 }, Vec2 { x: 0.75, y: 0.75 }, Vec2 { x: 0.25, y: 0.75 }];
+    let counter_clockwise: Vec<Vec2> = clockwise.iter().rev().cloned().collect();
+    for points in [clockwise, counter_clockwise] {
+        let polygon = PrimitiveGeometry::polygon(points)?;
+        let hit = polygon.ray_hit(east_ray(0.5), Vec2::default()).unwrap();
+        assert!((hit.t - 1.25).abs() < EPSILON);
+        assert_near(hit.normal, Vec2 { x: -1.0, y: 0.0 });
+        assert!(hit.u >= 0.0 && hit.u <= 1.0);
+        assert!(polygon.ray_hit(east_ray(0.9), Vec2::default()).is_none());
+
+        // out through the nearest edge
+        let push = polygon.push_out(Vec2 { x: 0.3, y: 0.5 }, 0.1, Vec2::default()).unwrap();
+        assert_near(push, Vec2 { x: -0.15, y: 0.0 });
+        let push = polygon.push_out(Vec2 { x: 0.2, y: 0.5 }, 0.1, Vec2::default()).unwrap();
+        assert_near(push, Vec2 { x: -0.05, y: 0.0 });
+        assert!(polygon.push_out(Vec2 { x: 0.1, y: 0.5 }, 0.1, Vec2::default()).is_none());
+    }
+
+    let concave = vec![Vec2 { x: 0.0, y: 0.0 }, Vec2 { x: 1.0, y: 0.0 }, Vec2 { x: 0.5, y: 0.2 }, Vec2 { x: 0.5, y: 1.0 }];
+    assert!(PrimitiveGeometry::polygon(concave).is_err());
+    assert!(PrimitiveGeometry::polygon(vec![Vec2::default(), Vec2 { x: 1.0, y: 1.0 }]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_two_sided_primitives() {
+    // a vertical wall facing west
+    let plane = |two_sided| PrimitiveGeometry::Plane {
+        p1: Vec2 { x: 0.5, y: 1.0 },
+        p2: Vec2 { x: 0.5, y: 0.0 },
+        n: Vec2 { x: -1.0, y: 0.0 },
+        two_sided,
+        elevation: Elevation::default(),
+    };
+    let from_east = Ray::new(Vec2 { x: 2.0, y: 0.5 }, Vec2 { x: -1.0, y: 0.0 });
+    assert!(plane(false).ray_hit(from_east, Vec2::default()).is_none());
+    let hit = plane(true).ray_hit(from_east, Vec2::default()).unwrap();
+    assert!((hit.t - 1.5).abs() < EPSILON);
+    assert_near(hit.normal, Vec2 { x: 1.0, y: 0.0 });
+
+    // seen from the inside
+    let from_center = Ray::new(Vec2 { x: 0.5, y: 0.5 }, Vec2 { x: 1.0, y: 0.0 });
+    let cylinder = PrimitiveGeometry::cylinder(Vec2 { x: 0.5, y: 0.5 }, 0.5, false);
+    assert!(cylinder.ray_hit(from_center, Vec2::default()).is_none());
+    let inward = PrimitiveGeometry::cylinder(Vec2 { x: 0.5, y: 0.5 }, 0.5, true);
+    let hit = inward.ray_hit(from_center, Vec2::default()).unwrap();
+    assert!((hit.t - 0.5).abs() < EPSILON);
+    assert_near(hit.normal, Vec2 { x: -1.0, y: 0.0 });
+    // the far side of an inward cylinder is seen through its near side
+    let hit = inward.ray_hit(east_ray(0.5), Vec2::default()).unwrap();
+    assert!((hit.t - 2.0).abs() < EPSILON);
+    let hit = cylinder.with_two_sides(true).ray_hit(east_ray(0.5), Vec2::default()).unwrap();
+    assert!((hit.t - 1.0).abs() < EPSILON);
+
+    // the wall of a round room keeps the player inside
+    let push = inward.push_out(Vec2 { x: 0.85, y: 0.5 }, 0.2, Vec2::default()).unwrap();
+    assert_near(push, Vec2 { x: -0.05, y: 0.0 });
+
+    let square = vec![Vec2 { x: 0.25, y: 0.25 }, Vec2 { x: 0.75, y: 0.25 }, Vec2 { x: 0.75, y: 0.75 }, Vec2 { x: 0.25, y: 0.75 }];
+    let polygon = PrimitiveGeometry::polygon(square).unwrap().with_two_sides(true);
+    let hit = polygon.ray_hit(from_center, Vec2::default()).unwrap();
+    assert!((hit.t - 0.25).abs() < EPSILON);
+    assert_near(hit.normal, Vec2 { x: -1.0, y: 0.0 });
+}
+
+#[test]
+fn test_map_primitives() -> Result<(), Box<dyn Error>> {
+    let mut files = generator::LevelGenerator::new(1, 16, 12)?.generate(GeneratorStyle::Rooms, "next")?;
+    files.geo_map.primitives = serde_json::from_str(r#"[
+        { "plane": { "p1": [1, 1], "p2": [6, 4], "two_sided": true } },
+        { "cylinder": { "center": [8, 3], "radius": 1.5 } }
+    ]"#)?;
+    let level_map = LevelMap::from_files("generated", &files)?;
+    let map = &level_map.map;
+    let tiles_of = |idx: usize| {
+        let mut tiles = vec![];
+        for (tile_y, line) in map.tiles.iter().enumerate() {
+            for (tile_x, tile) in line.iter().enumerate() {
+                if tile.primitives.contains(&idx) {
+                    tiles.push((tile_x, tile_y));
+                }
+            }
+        }
+        tiles
+    };
+    // registered in every tile of their bounds, the borders included
+    assert_eq!(tiles_of(0).len(), 7 * 5);
+    assert!(tiles_of(0).contains(&(0, 0)) && tiles_of(0).contains(&(6, 4)));
+    assert_eq!(tiles_of(1), (1..5).flat_map(|tile_y| (6..10).map(move |tile_x| (tile_x, tile_y))).collect::<Vec<_>>());
+    // neither kept within a tile
+    let hit = map.primitives[1].ray_hit(Ray::new(Vec2 { x: 8.0, y: 10.0 }, Vec2 { x: 0.0, y: -1.0 }), Vec2::default()).unwrap();
+    assert!((hit.t - 5.5).abs() < EPSILON);
+
+    let mut files = level_map.to_files(false)?;
+    assert_eq!(files.geo_map.primitives.len(), 2);
+    files.geo_map.primitives = serde_json::from_str(r#"[{ "cylinder": { "center": [30, 3], "radius": 1 } }]"#)?;
+    assert!(LevelMap::from_files("generated", &files).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_elevation() -> Result<(), Box<dyn Error>> {
+    let window_sill = PrimitiveGeometry::new(serde_json::from_str(r#"{ "plane": { "p1": [0, 1], "p2": [0, 0], "height": 0.3 } }"#)?)?;
+    assert_eq!(window_sill.elevation(), Elevation { base: 0.0, height: 0.3 });
+    assert!(!window_sill.elevation().at_eye_level());
+    let lintel = PrimitiveGeometry::new(serde_json::from_str(r#"{ "plane": { "p1": [0, 1], "p2": [0, 0], "base": 0.8, "height": 0.2 } }"#)?)?;
+    assert_eq!(serde_json::to_string(&lintel.to_raw())?, r#"{"plane":{"p1":[0.0,1.0],"p2":[0.0,0.0],"base":0.8,"height":0.2}}"#);
+
+    // a whole wall by default
+    let column = PrimitiveGeometry::new(serde_json::from_str(r#"{ "cylinder": { "radius": 0.25 } }"#)?)?;
+    assert_eq!(column.elevation(), Elevation::default());
+    assert!(column.elevation().at_eye_level());
+    assert_eq!(serde_json::to_string(&column.to_raw())?, r#"{"cylinder":{"radius":0.25}}"#);
+
+    assert!(PrimitiveGeometry::new(serde_json::from_str(r#"{ "cylinder": { "radius": 0.25, "height": 0 } }"#)?).is_err());
+    Ok(())
+}
@@ -0,0 +1,75 @@
+extern crate wolf3d_rs;
+use wolf3d_rs::*;
+use wolf3d_rs::convert::read_tiled;
+
+use std::error::Error;
+
+/// A 3x3 map with a player spawn in its center, whose geometry layer is `width` x `height` and holds `data`.
+/// The tiles 1 and 2 of its tileset are "cell" and "full".
+fn tmx(name: &str, width: usize, height: usize, data: &str) -> Result<LevelMap, Box<dyn Error>> {
+    let text = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="{width}" height="{height}" tilewidth="32" tileheight="32" infinite="0">
+ <properties>
+  <property name="geometry_set" value="default_geo_set"/>
+  <property name="spawn_symbol_set" value="default_spawn_symbols"/>
+  <property name="material_set" value="default_mtl_set"/>
+  <property name="portal_symbol_set" value="default_portal_symbols"/>
+ </properties>
+ <tileset firstgid="1" name="geometries" tilewidth="32" tileheight="32" tilecount="2">
+  <tile id="0"><properties><property name="name" value="cell"/></properties></tile>
+  <tile id="1"><properties><property name="name" value="full"/></properties></tile>
+ </tileset>
+ <layer name="geometry" width="{width}" height="{height}">
+  {data}
+ </layer>
+ <layer name="material" width="{width}" height="{height}">
+  <data encoding="csv">0,0,0,0,0,0,0,0,0</data>
+ </layer>
+ <objectgroup name="spawns">
+  <object id="1" x="32" y="32" width="32" height="32">
+   <properties><property name="player" type="int" value="1"/></properties>
+  </object>
+ </objectgroup>
+</map>
+"#);
+    let path = std::env::temp_dir().join(format!("wolf3d_rs_{}.tmx", name));
+    std::fs::write(&path, text)?;
+    let files = read_tiled(&path);
+    std::fs::remove_file(&path)?;
+    LevelMap::from_files(name, &files?)
+}
+
+fn geometry_names(level_map: &LevelMap) -> Vec<Vec<&str>> {
+    level_map.map.tiles.iter()
+        .map(|line| line.iter().map(|tile| level_map.geo_set.names[tile.geo_idx].as_str()).collect())
+        .collect()
+}
+
+#[test]
+fn test_tiled_layers() -> Result<(), Box<dyn Error>> {
+    let level_map = tmx("csv", 3, 3, r#"<data encoding="csv">1,1,1,1,0,1,1,1,1</data>"#)?;
+    assert_eq!(geometry_names(&level_map), vec![
+        vec!["cell", "cell", "cell"],
+        vec!["cell", "empty", "cell"],
+        vec!["cell", "cell", "cell"],
+    ]);
+    let spawn = &level_map.spawns[0];
+    assert_eq!((spawn.player, spawn.coordinates.x as usize, spawn.coordinates.y as usize), (1, 1, 1));
+
+    // little endian tile ids 2,1,2 1,0,1 2,1,2
+    let level_map = tmx("base64", 3, 3, r#"<data encoding="base64">AgAAAAEAAAACAAAAAQAAAAAAAAABAAAAAgAAAAEAAAACAAAA</data>"#)?;
+    assert_eq!(geometry_names(&level_map), vec![
+        vec!["full", "cell", "full"],
+        vec!["cell", "empty", "cell"],
+        vec!["full", "cell", "full"],
+    ]);
+    Ok(())
+}
+
+#[test]
+fn test_tiled_errors() {
+    let err = tmx("zlib", 3, 3, r#"<data encoding="base64" compression="zlib">eJxjZGBgYAQAAA0ABw==</data>"#).err().unwrap();
+    assert!(err.to_string().ends_with("zlib compressed layers are not supported, save the map with CSV layers"));
+    let err = tmx("empty", 0, 3, r#"<data encoding="csv"></data>"#).err().unwrap();
+    assert!(err.to_string().ends_with("invalid map size 0x3"));
+}